@@ -33,7 +33,7 @@ use foundry_config::Config;
 use foundry_evm::{
     backend::StateLookup,
     constants::DEFAULT_CREATE2_DEPLOYER,
-    fork::{BlockchainDb, BlockchainDbMeta, SharedBackend},
+    fork::{BlockchainDb, BlockchainDbMeta, SharedBackend, SharedBackendConfig},
     revm::primitives::{BlockEnv, CfgEnv, CfgEnvWithHandlerCfg, EnvWithHandlerCfg, SpecId, TxEnv},
     utils::apply_chain_and_block_specific_env_changes,
 };
@@ -1141,11 +1141,12 @@ latest block number: {latest_block}"
         let backend = SharedBackend::spawn_backend_thread(
             Arc::clone(&provider),
             block_chain_db.clone(),
-            fork_block_number,
-            Default::default(), //data_accesses
-            chain_id.into(),
-            StateLookup::RollAt(fork_block_number),
-            Default::default(), //code_cache
+            SharedBackendConfig {
+                pin_block: fork_block_number,
+                chain: chain_id.into(),
+                state_lookup: StateLookup::RollAt(fork_block_number),
+                ..Default::default()
+            },
         );
 
         let config = ClientForkConfig {
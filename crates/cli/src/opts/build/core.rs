@@ -2,6 +2,7 @@ use super::ProjectPathsArgs;
 use crate::{opts::CompilerArgs, utils::LoadConfig};
 use clap::{Parser, ValueHint};
 use eyre::Result;
+use foundry_common::cli_warn;
 use foundry_compilers::{
     artifacts::{remappings::Remapping, RevertStrings},
     compilers::multi::MultiCompiler,
@@ -144,6 +145,9 @@ impl CoreBuildArgs {
     /// `find_project_root_path` and merges the cli `BuildArgs` into it before returning
     /// [`foundry_config::Config::project()`]).
     pub fn project(&self) -> Result<Project<MultiCompiler>> {
+        for warning in self.project_paths.validate_lib_paths()? {
+            cli_warn!("{warning}");
+        }
         let config = self.try_load_config_emit_warnings()?;
         Ok(config.project()?)
     }
@@ -166,9 +170,9 @@ impl<'a> From<&'a CoreBuildArgs> for Figment {
                 panic!("error: the config-path must be a path to a foundry.toml file")
             }
             let config_path = canonicalized(config_path);
-            Config::figment_with_root(config_path.parent().unwrap())
+            args.project_paths.figment_with_root(config_path.parent().unwrap())
         } else {
-            Config::figment_with_root(args.project_paths.project_root())
+            args.project_paths.figment_with_root(args.project_paths.project_root())
         };
 
         // remappings should stack
@@ -272,6 +276,6 @@ impl Provider for CoreBuildArgs {
             dict.insert("revert_strings".to_string(), revert.to_string().into());
         }
 
-        Ok(Map::from([(Config::selected_profile(), dict)]))
+        Ok(Map::from([(self.project_paths.selected_profile(), dict)]))
     }
 }
@@ -1,17 +1,18 @@
 use clap::{Parser, ValueHint};
 use eyre::Result;
-use foundry_compilers::artifacts::remappings::Remapping;
+use foundry_compilers::{artifacts::remappings::Remapping, utils::source_files_iter};
 use foundry_config::{
     figment,
     figment::{
         error::Kind::InvalidType,
         value::{Dict, Map, Value},
-        Metadata, Profile, Provider,
+        Figment, Metadata, Profile, Provider,
     },
-    find_project_root_path, remappings_from_env_var, Config,
+    find_project_root_path, remappings_from_env_var, Config, FigmentProviders,
 };
+use once_cell::sync::OnceCell;
 use serde::Serialize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Common arguments for a project's paths.
 #[derive(Clone, Debug, Default, Serialize, Parser)]
@@ -25,10 +26,14 @@ pub struct ProjectPathsArgs {
     #[serde(skip)]
     pub root: Option<PathBuf>,
 
-    /// The contracts source directory.
+    /// The contracts source directories.
+    ///
+    /// Can be repeated. The first directory is the project's source directory, the others are
+    /// added to the compiler's include and allow paths, so that sources can import each other
+    /// across them.
     #[arg(long, short = 'C', value_hint = ValueHint::DirPath, value_name = "PATH")]
-    #[serde(rename = "src", skip_serializing_if = "Option::is_none")]
-    pub contracts: Option<PathBuf>,
+    #[serde(skip)]
+    pub contracts: Vec<PathBuf>,
 
     /// The project's remappings.
     #[arg(long, short = 'R')]
@@ -50,6 +55,11 @@ pub struct ProjectPathsArgs {
     #[serde(rename = "libs", skip_serializing_if = "Vec::is_empty")]
     pub lib_paths: Vec<PathBuf>,
 
+    /// Fail if a library folder contains no Solidity files, rather than warning.
+    #[arg(long)]
+    #[serde(skip)]
+    pub strict_paths: bool,
+
     /// Use the Hardhat-style project layout.
     ///
     /// This is the same as using: `--contracts contracts --lib-paths node_modules`.
@@ -61,6 +71,30 @@ pub struct ProjectPathsArgs {
     #[arg(long, value_hint = ValueHint::FilePath, value_name = "FILE")]
     #[serde(skip)]
     pub config_path: Option<PathBuf>,
+
+    /// The config profiles to use, merged in order with later profiles overriding earlier ones.
+    ///
+    /// Can be a comma-separated list or repeated. Defaults to the `FOUNDRY_PROFILE` profile.
+    #[arg(long, value_name = "PROFILE", value_delimiter = ',')]
+    #[serde(skip)]
+    pub profile: Vec<String>,
+
+    /// The memoized result of [`ProjectPathsArgs::get_remappings`].
+    #[arg(skip)]
+    #[serde(skip)]
+    resolved_remappings: RemappingsCache,
+}
+
+/// Memoized remappings, along with the arguments they were resolved from.
+///
+/// The cache is dropped when cloned, and ignored if the arguments it was resolved from changed.
+#[derive(Debug, Default)]
+struct RemappingsCache(OnceCell<(Vec<Remapping>, Option<String>, Vec<Remapping>)>);
+
+impl Clone for RemappingsCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
 }
 
 impl ProjectPathsArgs {
@@ -77,8 +111,93 @@ impl ProjectPathsArgs {
             .unwrap_or_else(|| find_project_root_path(None).expect("Failed to find project root"))
     }
 
+    /// Returns the config profiles to merge, in order.
+    ///
+    /// This will be the `--profile` arguments if provided, otherwise [Config::selected_profile()]
+    pub fn profiles(&self) -> Vec<Profile> {
+        if self.profile.is_empty() {
+            return vec![Config::selected_profile()];
+        }
+        self.profile.iter().map(|profile| Profile::new(profile)).collect()
+    }
+
+    /// Returns the effective config profile, the last of [Self::profiles()]
+    pub fn selected_profile(&self) -> Profile {
+        self.profile
+            .last()
+            .map(|profile| Profile::new(profile))
+            .unwrap_or_else(Config::selected_profile)
+    }
+
+    /// Returns the project's [Figment] at the given root, with the config profiles merged in
+    /// order, see [Self::profiles()]
+    pub fn figment_with_root(&self, root: impl Into<PathBuf>) -> Figment {
+        Config::with_root(root).to_figment_with_profiles(FigmentProviders::All, self.profiles())
+    }
+
     /// Returns the remappings to add to the config
+    ///
+    /// The result is memoized, so repeated calls don't re-parse the remappings env var.
     pub fn get_remappings(&self) -> Vec<Remapping> {
+        if let Some((remappings, remappings_env, resolved)) = self.resolved_remappings.0.get() {
+            if *remappings == self.remappings && *remappings_env == self.remappings_env {
+                return resolved.clone();
+            }
+            return self.resolve_remappings();
+        }
+
+        let resolved = self.resolve_remappings();
+        let _ = self.resolved_remappings.0.set((
+            self.remappings.clone(),
+            self.remappings_env.clone(),
+            resolved.clone(),
+        ));
+        resolved
+    }
+
+    /// Returns the `--lib-paths` that exist but contain no Solidity files, e.g. because they point
+    /// at the wrong directory or the dependencies were not installed.
+    ///
+    /// Relative paths are resolved against the project root, if any. Paths that don't exist are
+    /// ignored.
+    pub fn lib_paths_without_solidity(&self) -> Vec<PathBuf> {
+        let root = self.root.clone().or_else(|| find_project_root_path(None).ok());
+        self.lib_paths
+            .iter()
+            .filter(|lib| {
+                let lib = match &root {
+                    Some(root) if lib.is_relative() => root.join(lib),
+                    _ => lib.to_path_buf(),
+                };
+                lib.is_dir() && !contains_solidity(&lib)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Validates the `--lib-paths`, returning a warning for each library folder that contains no
+    /// Solidity files, see [Self::lib_paths_without_solidity()].
+    ///
+    /// Fails instead if `--strict-paths` is set.
+    pub fn validate_lib_paths(&self) -> Result<Vec<String>> {
+        let warnings = self
+            .lib_paths_without_solidity()
+            .into_iter()
+            .map(|lib| {
+                format!(
+                    "library path \"{}\" contains no Solidity files, imports from it won't resolve",
+                    lib.display()
+                )
+            })
+            .collect::<Vec<_>>();
+        if self.strict_paths && !warnings.is_empty() {
+            eyre::bail!("{}", warnings.join("\n"));
+        }
+        Ok(warnings)
+    }
+
+    /// Resolves the remappings from the arguments and the remappings env var
+    fn resolve_remappings(&self) -> Vec<Remapping> {
         let mut remappings = self.remappings.clone();
         if let Some(env_remappings) =
             self.remappings_env.as_ref().and_then(|env| remappings_from_env_var(env))
@@ -89,7 +208,23 @@ impl ProjectPathsArgs {
     }
 }
 
-foundry_config::impl_figment_convert!(ProjectPathsArgs);
+/// Whether the given directory contains a Solidity file, at any depth
+fn contains_solidity(dir: &Path) -> bool {
+    source_files_iter(dir, &["sol"]).next().is_some()
+}
+
+impl<'a> From<&'a ProjectPathsArgs> for Figment {
+    fn from(args: &'a ProjectPathsArgs) -> Self {
+        args.figment_with_root(args.project_root()).merge(args)
+    }
+}
+
+impl<'a> From<&'a ProjectPathsArgs> for Config {
+    fn from(args: &'a ProjectPathsArgs) -> Self {
+        let figment: Figment = args.into();
+        Self::from_provider(figment).sanitized()
+    }
+}
 
 // Make this args a `figment::Provider` so that it can be merged into the `Config`
 impl Provider for ProjectPathsArgs {
@@ -105,6 +240,16 @@ impl Provider for ProjectPathsArgs {
         let mut libs =
             self.lib_paths.iter().map(|p| format!("{}", p.display())).collect::<Vec<_>>();
 
+        // The config holds a single source directory, the others are made importable
+        if let Some((src, others)) = self.contracts.split_first() {
+            dict.insert("src".to_string(), format!("{}", src.display()).into());
+            if !others.is_empty() {
+                let others = others.iter().map(|p| format!("{}", p.display())).collect::<Vec<_>>();
+                dict.insert("include_paths".to_string(), others.clone().into());
+                dict.insert("allow_paths".to_string(), others.into());
+            }
+        }
+
         if self.hardhat {
             dict.insert("src".to_string(), "contracts".to_string().into());
             libs.push("node_modules".to_string());
@@ -114,6 +259,188 @@ impl Provider for ProjectPathsArgs {
             dict.insert("libs".to_string(), libs.into());
         }
 
-        Ok(Map::from([(Config::selected_profile(), dict)]))
+        Ok(Map::from([(self.selected_profile(), dict)]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_remappings_is_memoized() {
+        let env = "FOUNDRY_CLI_TEST_MEMOIZED_REMAPPINGS";
+        std::env::set_var(env, "a/=lib/a/");
+
+        let args = ProjectPathsArgs::parse_from([
+            "foundry-cli",
+            "--remappings",
+            "b/=lib/b/",
+            "--remappings-env",
+            env,
+        ]);
+        let remappings = args.get_remappings();
+        assert_eq!(remappings.len(), 2);
+
+        // the env var is not re-parsed on repeated calls
+        std::env::set_var(env, "c/=lib/c/");
+        assert_eq!(args.get_remappings(), remappings);
+
+        // a clone resolves the remappings again, while the original keeps its memoized ones
+        let cloned = args.clone();
+        assert_ne!(cloned.get_remappings(), remappings);
+        assert_eq!(args.get_remappings(), remappings);
+    }
+
+    #[test]
+    fn warns_on_lib_paths_without_solidity() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("lib/empty/docs")).unwrap();
+        std::fs::create_dir_all(root.path().join("lib/forge-std/src")).unwrap();
+        std::fs::write(root.path().join("lib/forge-std/src/Test.sol"), "").unwrap();
+        let root = root.path().to_str().unwrap();
+
+        let args = ProjectPathsArgs::parse_from([
+            "foundry-cli",
+            "--root",
+            root,
+            "--lib-paths",
+            "lib/empty",
+            "--lib-paths",
+            "lib/forge-std",
+            "--lib-paths",
+            "lib/missing",
+        ]);
+        assert_eq!(args.lib_paths_without_solidity(), [PathBuf::from("lib/empty")]);
+        let warnings = args.validate_lib_paths().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("lib/empty"), "{warnings:?}");
+
+        let args = ProjectPathsArgs { strict_paths: true, ..args };
+        assert!(args.validate_lib_paths().is_err());
+    }
+
+    #[test]
+    fn multiple_contracts_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path().to_str().unwrap();
+
+        let args = ProjectPathsArgs::parse_from([
+            "foundry-cli",
+            "--root",
+            root,
+            "-C",
+            "contracts",
+            "-C",
+            "packages/core/src",
+        ]);
+        assert_eq!(
+            args.contracts,
+            [PathBuf::from("contracts"), PathBuf::from("packages/core/src")]
+        );
+        let config = Config::from(&args);
+        assert!(config.src.ends_with("contracts"), "{}", config.src.display());
+        assert!(config.include_paths.iter().any(|path| path.ends_with("packages/core/src")));
+        assert!(config.allow_paths.iter().any(|path| path.ends_with("packages/core/src")));
+
+        // A single directory is the source directory, as is the Hardhat layout
+        let args = ProjectPathsArgs::parse_from(["foundry-cli", "--root", root, "-C", "contracts"]);
+        let config = Config::from(&args);
+        assert!(config.src.ends_with("contracts"));
+        assert!(config.include_paths.is_empty());
+
+        let args = ProjectPathsArgs::parse_from(["foundry-cli", "--root", root, "--hardhat"]);
+        assert!(Config::from(&args).src.ends_with("contracts"));
+    }
+
+    #[test]
+    fn later_profiles_override_earlier_ones() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(
+            root.path().join(Config::FILE_NAME),
+            r#"
+            [profile.default]
+            optimizer_runs = 1
+
+            [profile.first]
+            src = "first-src"
+            optimizer_runs = 2
+
+            [profile.second]
+            optimizer_runs = 3
+            "#,
+        )
+        .unwrap();
+        let root = root.path().to_str().unwrap();
+
+        let args = ProjectPathsArgs::parse_from(["foundry-cli", "--root", root]);
+        let config = Config::from(&args);
+        assert_eq!(config.profile, Config::DEFAULT_PROFILE);
+        assert_eq!(config.optimizer_runs, 1);
+
+        let args =
+            ProjectPathsArgs::parse_from(["foundry-cli", "--root", root, "--profile", "first"]);
+        let config = Config::from(&args);
+        assert_eq!(config.profile, "first");
+        assert_eq!(config.optimizer_runs, 2);
+
+        for args in [
+            ["foundry-cli", "--root", root, "--profile", "first,second"].as_slice(),
+            ["foundry-cli", "--root", root, "--profile", "first", "--profile", "second"].as_slice(),
+        ] {
+            let args = ProjectPathsArgs::parse_from(args);
+            assert_eq!(args.data().unwrap().keys().collect::<Vec<_>>(), ["second"]);
+
+            let config = Config::from(&args);
+            assert_eq!(config.profile, "second");
+            assert_eq!(config.optimizer_runs, 3);
+            assert!(config.src.ends_with("first-src"));
+        }
+    }
+
+    #[derive(Parser)]
+    struct ProfileArgs {
+        #[command(flatten)]
+        build: crate::opts::CoreBuildArgs,
+        #[command(flatten)]
+        evm: foundry_common::evm::EvmArgs,
+    }
+    foundry_config::impl_figment_convert!(ProfileArgs, build, evm);
+
+    #[test]
+    fn cli_args_override_the_selected_profile() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(
+            root.path().join(Config::FILE_NAME),
+            r#"
+            [profile.ci]
+            optimizer_runs = 2
+            eth_rpc_url = "http://toml.invalid"
+            "#,
+        )
+        .unwrap();
+        let root = root.path().to_str().unwrap();
+
+        let args = ProfileArgs::parse_from(["foundry-cli", "--root", root, "--profile", "ci"]);
+        let config = Config::from(&args);
+        assert_eq!(config.optimizer_runs, 2);
+        assert_eq!(config.eth_rpc_url.as_deref(), Some("http://toml.invalid"));
+
+        // The CLI arguments of every provider win over the values of the selected profile
+        let args = ProfileArgs::parse_from([
+            "foundry-cli",
+            "--root",
+            root,
+            "--profile",
+            "ci",
+            "--optimizer-runs",
+            "5",
+            "--fork-url",
+            "http://cli.invalid",
+        ]);
+        let config = Config::from(&args);
+        assert_eq!(config.profile, "ci");
+        assert_eq!(config.optimizer_runs, 5);
+        assert_eq!(config.eth_rpc_url.as_deref(), Some("http://cli.invalid"));
     }
 }
@@ -301,6 +301,12 @@ pub struct Config {
     ///
     /// The default is 128MiB.
     pub memory_limit: u64,
+    /// The number of block environments cached when forking, e.g. to avoid refetching blocks on
+    /// large runs forking at many blocks. Uses the default capacity if not set.
+    pub block_env_cache_capacity: Option<usize>,
+    /// The number of accounts whose code is cached when forking. Uses the default capacity if
+    /// not set.
+    pub code_cache_capacity: Option<usize>,
     /// Additional output selection for all contracts, such as "ir", "devdoc", "storageLayout",
     /// etc.
     ///
@@ -557,8 +563,25 @@ impl Config {
     ///
     /// This will merge various providers, such as env,toml,remappings into the figment.
     pub fn to_figment(self, providers: FigmentProviders) -> Figment {
+        self.to_figment_with_profiles(providers, [Self::selected_profile()])
+    }
+
+    /// Returns the populated [Figment] using the requested [FigmentProviders] preset, merging the
+    /// given profiles of the config files in order.
+    ///
+    /// Later profiles override earlier ones and the last profile is selected. If no profiles are
+    /// given, this is the same as [Self::to_figment].
+    pub fn to_figment_with_profiles(
+        self,
+        providers: FigmentProviders,
+        profiles: impl IntoIterator<Item = impl Into<Profile>>,
+    ) -> Figment {
         let mut c = self;
-        let profile = Self::selected_profile();
+        let mut profiles = profiles.into_iter().map(Into::into).collect::<Vec<Profile>>();
+        if profiles.is_empty() {
+            profiles.push(Self::selected_profile());
+        }
+        let profile = profiles.last().cloned().unwrap();
         let mut figment = Figment::default().merge(DappHardhatDirProvider(&c.root.0));
 
         // merge global foundry.toml file
@@ -566,14 +589,14 @@ impl Config {
             figment = Self::merge_toml_provider(
                 figment,
                 TomlFileProvider::new(None, global_toml).cached(),
-                profile.clone(),
+                &profiles,
             );
         }
         // merge local foundry.toml file
         figment = Self::merge_toml_provider(
             figment,
             TomlFileProvider::new(Some("FOUNDRY_CONFIG"), c.root.0.join(Self::FILE_NAME)).cached(),
-            profile.clone(),
+            &profiles,
         );
 
         // merge environment variables
@@ -1761,11 +1784,15 @@ impl Config {
         dir_size_recursive(fs::read_dir(chain_path)?)
     }
 
+    /// Merges the given profiles of the toml provider into the figment, in order.
+    ///
+    /// The last profile is selected, all other profiles are merged into it.
     fn merge_toml_provider(
         mut figment: Figment,
         toml_provider: impl Provider,
-        profile: Profile,
+        profiles: &[Profile],
     ) -> Figment {
+        let profile = profiles.last().cloned().unwrap_or_else(Self::selected_profile);
         figment = figment.select(profile.clone());
 
         // add warnings
@@ -1775,11 +1802,13 @@ impl Config {
         };
 
         // use [profile.<profile>] as [<profile>]
-        let mut profiles = vec![Self::DEFAULT_PROFILE];
-        if profile != Self::DEFAULT_PROFILE {
-            profiles.push(profile.clone());
+        let mut selected = vec![Self::DEFAULT_PROFILE];
+        for p in profiles {
+            if !selected.contains(p) {
+                selected.push(p.clone());
+            }
         }
-        let provider = toml_provider.strict_select(profiles);
+        let provider = toml_provider.strict_select(selected);
 
         // apply any key fixes
         let provider = BackwardsCompatTomlProvider(ForcedSnakeCaseData(provider));
@@ -1788,6 +1817,12 @@ impl Config {
         if profile != Self::DEFAULT_PROFILE {
             figment = figment.merge(provider.rename(Self::DEFAULT_PROFILE, profile.clone()));
         }
+        // merge any other profiles in order
+        for p in &profiles[..profiles.len().saturating_sub(1)] {
+            if *p != Self::DEFAULT_PROFILE && *p != profile {
+                figment = figment.merge(provider.rename(p.clone(), profile.clone()));
+            }
+        }
         // merge special keys into config
         for standalone_key in Self::STANDALONE_SECTIONS {
             if let Some((_, fallback)) =
@@ -1989,7 +2024,7 @@ pub fn parse_with_profile<T: serde::de::DeserializeOwned>(
     let figment = Config::merge_toml_provider(
         Figment::new(),
         Toml::string(s).nested(),
-        Config::DEFAULT_PROFILE,
+        &[Config::DEFAULT_PROFILE],
     );
     if figment.profiles().any(|p| p == Config::DEFAULT_PROFILE) {
         Ok(Some((Config::DEFAULT_PROFILE, figment.select(Config::DEFAULT_PROFILE).extract()?)))
@@ -2082,6 +2117,8 @@ impl Default for Config {
             block_gas_limit: None,
             disable_block_gas_limit: false,
             memory_limit: 1 << 27, // 2**27 = 128MiB = 134_217_728 bytes
+            block_env_cache_capacity: None,
+            code_cache_capacity: None,
             eth_rpc_url: None,
             eth_rpc_jwt: None,
             etherscan_api_key: None,
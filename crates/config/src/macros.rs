@@ -7,6 +7,10 @@
 /// `impl_figment` takes the default `Config` and merges additional `Provider`, therefore the
 /// targeted type, requires an implementation of `figment::Profile`.
 ///
+/// The values the additional providers set for [`Config::selected_profile()`] are applied to the
+/// profile selected by the first one instead, e.g. with `--profile`, see
+/// [`SelectedProfileProvider`](crate::providers::SelectedProfileProvider).
+///
 /// # Example
 ///
 /// Use `impl_figment` on a type with a `root: Option<PathBuf>` field, which will be used for
@@ -78,8 +82,12 @@ macro_rules! impl_figment_convert {
         impl<'a> From<&'a $name> for $crate::figment::Figment {
             fn from(args: &'a $name) -> Self {
                 let mut figment: $crate::figment::Figment = From::from(&args.$start);
+                let profile = figment.profile().clone();
                 $(
-                    figment = figment.merge(&args.$more);
+                    figment = figment.merge($crate::providers::SelectedProfileProvider::new(
+                        &args.$more,
+                        profile.clone(),
+                    ));
                 )*
                 figment
             }
@@ -96,10 +104,15 @@ macro_rules! impl_figment_convert {
         impl<'a> From<&'a $name> for $crate::figment::Figment {
             fn from(args: &'a $name) -> Self {
                 let mut figment: $crate::figment::Figment = From::from(&args.$start);
+                let profile = figment.profile().clone();
                 $(
-                    figment = figment.merge(&args.$more);
+                    figment = figment.merge($crate::providers::SelectedProfileProvider::new(
+                        &args.$more,
+                        profile.clone(),
+                    ));
                 )*
-                figment = figment.merge(args);
+                figment = figment
+                    .merge($crate::providers::SelectedProfileProvider::new(args, profile));
                 figment
             }
         }
@@ -167,10 +180,15 @@ macro_rules! merge_impl_figment_convert {
         impl<'a> From<&'a $name> for $crate::figment::Figment {
             fn from(args: &'a $name) -> Self {
                 let mut figment: $crate::figment::Figment = From::from(&args.$start);
-                $ (
-                  figment =  figment.merge(&args.$more);
+                let profile = figment.profile().clone();
+                $(
+                    figment = figment.merge($crate::providers::SelectedProfileProvider::new(
+                        &args.$more,
+                        profile.clone(),
+                    ));
                 )*
-                figment = figment.merge(args);
+                figment = figment
+                    .merge($crate::providers::SelectedProfileProvider::new(args, profile));
                 figment
             }
         }
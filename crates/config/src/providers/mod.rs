@@ -155,3 +155,34 @@ impl<P: Provider> Provider for FallbackProfileProvider<P> {
         Some(self.profile.clone())
     }
 }
+
+/// Provides the values the given provider sets for [`Config::selected_profile`] for the given
+/// profile instead.
+///
+/// This is used to apply the values of CLI arguments to the profile selected with `--profile`,
+/// see [`crate::impl_figment_convert`].
+pub struct SelectedProfileProvider<P> {
+    provider: P,
+    profile: Profile,
+}
+
+impl<P> SelectedProfileProvider<P> {
+    /// Creates a new provider setting the values of `provider` for the given profile.
+    pub fn new(provider: P, profile: impl Into<Profile>) -> Self {
+        Self { provider, profile: profile.into() }
+    }
+}
+
+impl<P: Provider> Provider for SelectedProfileProvider<P> {
+    fn metadata(&self) -> Metadata {
+        self.provider.metadata()
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let mut data = self.provider.data()?;
+        if let Some(dict) = data.remove(&Config::selected_profile()) {
+            data.entry(self.profile.clone()).or_default().extend(dict);
+        }
+        Ok(data)
+    }
+}
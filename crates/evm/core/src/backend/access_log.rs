@@ -0,0 +1,77 @@
+use crate::backend::{compression, Access};
+use alloy_primitives::{B256, U256};
+use revm::primitives::AccountInfo;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+/// The value read by a data access, see [`AccessLogEntry`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccessValue {
+    /// The basic account, including its code
+    Basic(AccountInfo),
+    /// The value of a storage slot
+    Storage(U256),
+    /// The hash of a block
+    BlockHash(B256),
+}
+
+/// A data access along with the value it read
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessLogEntry {
+    /// The data access
+    pub access: Access,
+    /// The value read by the access
+    pub value: AccessValue,
+}
+
+/// The data accesses of a run along with the values they read, in the order they were read.
+///
+/// Replaying the log into a fork, see
+/// [`SharedBackend::replay_access_log`](crate::fork::SharedBackend::replay_access_log), serves the
+/// same values without hitting the provider, for fully offline and deterministic reruns.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessLog {
+    /// The logged accesses, in order
+    pub entries: Vec<AccessLogEntry>,
+}
+
+impl AccessLog {
+    /// Saves the log to the given path as newline delimited JSON, an entry per line.
+    ///
+    /// The log is gzip compressed if the path has a `.gz` extension, see [`AccessLog::save_with`].
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        self.save_with(path, compression::is_gz_path(path))
+    }
+
+    /// Saves the log to the given path as newline delimited JSON, gzip compressed if `compress` is
+    /// set
+    pub fn save_with(&self, path: &Path, compress: bool) -> eyre::Result<()> {
+        let mut writer = compression::create(path, compress)?;
+        for entry in &self.entries {
+            serde_json::to_writer(&mut writer, entry)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Loads a log from the newline delimited JSON file at the given path, decompressing it if it
+    /// was saved compressed
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let mut entries = Vec::new();
+        for (index, line) in compression::open(path)?.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue
+            }
+            let entry = serde_json::from_str(&line).map_err(|err| {
+                eyre::eyre!("invalid access log entry on line {}: {err}", index + 1)
+            })?;
+            entries.push(entry);
+        }
+        Ok(Self { entries })
+    }
+}
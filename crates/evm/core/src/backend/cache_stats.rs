@@ -0,0 +1,140 @@
+use quick_cache::Lifecycle;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+/// A snapshot of the effectiveness of a cache, see e.g.
+/// [`CodeCache::stats`](crate::backend::CodeCache::stats)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of lookups served from the cache
+    pub hits: u64,
+    /// The number of lookups that fell through to the provider
+    pub misses: u64,
+    /// The number of values inserted into the cache
+    pub insertions: u64,
+    /// The number of entries currently held by the cache
+    pub entries: usize,
+}
+
+/// Counters of the lookups and insertions of a cache.
+///
+/// The counters are only read for reporting, so they are updated with relaxed ordering to stay off
+/// the hot path.
+#[derive(Debug, Default)]
+pub(crate) struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    insertions: AtomicU64,
+}
+
+impl CacheCounters {
+    /// Records a lookup served from the cache
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a lookup that fell through to the provider
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an insertion into the cache
+    pub(crate) fn insertion(&self) {
+        self.insertions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the counters along with the given number of entries held by the cache
+    pub(crate) fn stats(&self, entries: usize) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            insertions: self.insertions.load(Ordering::Relaxed),
+            entries,
+        }
+    }
+}
+
+/// A callback invoked with the key of an entry evicted from a cache
+type EvictionCallback<K> = dyn Fn(&K) + Send + Sync;
+
+/// The lifecycle of a bounded cache that reports evicted entries to an eviction listener, e.g. to
+/// detect caches thrashing under capacity pressure.
+///
+/// The listener can be set after the cache is created, and is shared by the clones of the
+/// lifecycle, so caches created with the same lifecycle report to the same listener. Until it is
+/// set, evictions cost a single atomic load on top of the default lifecycle.
+pub(crate) struct EvictionLifecycle<K> {
+    /// The listener shared by the clones of the lifecycle
+    listener: Arc<OnceLock<Box<EvictionCallback<K>>>>,
+    /// Invoked with the key of every evicted entry before the listener, see [`Self::with_hook`]
+    hook: Option<Arc<EvictionCallback<K>>>,
+}
+
+impl<K> EvictionLifecycle<K> {
+    /// Returns a clone of the lifecycle that also invokes the given hook with the key of every
+    /// entry evicted from the caches created with it, e.g. to keep track of the cached keys.
+    ///
+    /// Like the listener, the hook is invoked while the cache is locked, so it must not access the
+    /// cache.
+    pub(crate) fn with_hook(&self, hook: impl Fn(&K) + Send + Sync + 'static) -> Self {
+        Self { listener: Arc::clone(&self.listener), hook: Some(Arc::new(hook)) }
+    }
+
+    /// Sets the listener invoked with the key of every entry evicted from the caches created with
+    /// this lifecycle.
+    ///
+    /// # Panics
+    ///
+    /// If a listener was already set
+    pub(crate) fn set_listener(&self, listener: impl Fn(&K) + Send + Sync + 'static) {
+        if self.listener.set(Box::new(listener)).is_err() {
+            panic!("an eviction listener was already set");
+        }
+    }
+}
+
+impl<K> Default for EvictionLifecycle<K> {
+    fn default() -> Self {
+        Self { listener: Default::default(), hook: None }
+    }
+}
+
+impl<K> Clone for EvictionLifecycle<K> {
+    fn clone(&self) -> Self {
+        Self { listener: Arc::clone(&self.listener), hook: self.hook.clone() }
+    }
+}
+
+impl<K> fmt::Debug for EvictionLifecycle<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EvictionLifecycle")
+            .field("listener", &self.listener.get().is_some())
+            .field("hook", &self.hook.is_some())
+            .finish()
+    }
+}
+
+impl<K, V> Lifecycle<K, V> for EvictionLifecycle<K> {
+    /// Like the default lifecycle, stashes an evicted entry to drop it outside the cache locks
+    type RequestState = Option<(K, V)>;
+
+    fn begin_request(&self) -> Self::RequestState {
+        None
+    }
+
+    /// The listener is invoked while the cache is locked, so it must not access the cache
+    fn on_evict(&self, state: &mut Self::RequestState, key: K, val: V) {
+        if let Some(hook) = &self.hook {
+            hook(&key);
+        }
+        if let Some(listener) = self.listener.get() {
+            listener(&key);
+        }
+        *state = Some((key, val));
+    }
+}
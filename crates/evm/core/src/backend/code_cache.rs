@@ -1,38 +1,195 @@
+use crate::{
+    backend::{
+        cache_stats::{CacheCounters, EvictionLifecycle},
+        CacheStats,
+    },
+    fork::JsonBlockCacheDB,
+};
 use alloy_chains::Chain;
 use alloy_provider::{Network, Provider};
-use alloy_transport::{Transport, TransportResult};
-use quick_cache::sync::Cache;
-use revm::primitives::{Address, Bytes};
+use alloy_transport::{Transport, TransportErrorKind, TransportResult};
+use dashmap::DashMap;
+use eyre::WrapErr;
+use quick_cache::{sync::Cache, DefaultHashBuilder, UnitWeighter};
+use revm::primitives::{Address, Bytes, KECCAK_EMPTY};
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+use tokio::sync::Mutex;
 
 /// Type alias for a block number.
 type BlockNumber = u64;
 
+/// The number of accounts the cache holds the code of by default
+const CODE_CACHE_CAPACITY: usize = 10_000;
+
+/// The prefix of an EIP-7702 delegation indicator, followed by the address delegated to
+const EIP7702_DELEGATION_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+/// Returns whether the given code is an EIP-7702 delegation indicator, i.e. `0xef0100 || address`
+fn is_delegation(code: &Bytes) -> bool {
+    code.len() == EIP7702_DELEGATION_PREFIX.len() + Address::len_bytes() &&
+        code.starts_with(&EIP7702_DELEGATION_PREFIX)
+}
+
 /// Struct for cacheing code history of an account for a chain.
-/// This is used for returning the correct code for a given block number, under the assumption that
-/// code is immutable.
+/// This is used for returning the correct code for a given block number.
+///
+/// Code is not immutable: an account can be redeployed with `CREATE2` after a `SELFDESTRUCT`, or
+/// change its EIP-7702 delegation, so the code is only known at the observed blocks, and between
+/// two adjacent observations of the same code.
 #[derive(Debug, Default, Clone)]
 struct CodeCacheEntry {
-    /// The earliest block number at which code was detected by a get_code request, along with the
-    /// code. None if there has not been a get_code request that returned code for this address
-    /// on this chain.
-    code_detected: Option<(BlockNumber, Bytes)>,
-    /// The latest block number at which no code was detected by a get_code request.
-    /// None if there has not been a get_code request that returned no code for this address on
-    /// this chain.
-    no_code_detected_block_number: Option<BlockNumber>,
+    /// The code observed by get_code requests, empty if there was none, by block number
+    samples: BTreeMap<BlockNumber, Bytes>,
+}
+
+impl CodeCacheEntry {
+    /// Returns the code at the given block if it was observed at the block, or the same code was
+    /// observed at the adjacent observed blocks before and after it.
+    ///
+    /// EIP-7702 delegations can be revoked and re-authorized in between any two blocks without the
+    /// account's code otherwise changing, so a delegation is only known at the blocks it was
+    /// observed at.
+    fn code_at(&self, block_number: BlockNumber) -> Option<&Bytes> {
+        if let Some(code) = self.samples.get(&block_number) {
+            return Some(code);
+        }
+        let (_, before) = self.samples.range(..block_number).next_back()?;
+        let (_, after) = self.samples.range(block_number..).next()?;
+        (before == after && !is_delegation(before)).then_some(before)
+    }
+
+    /// Returns the block of the observation the given code observed at the given block conflicts
+    /// with, if any.
+    ///
+    /// The code conflicts with code observed at the same block if it differs from it. It also
+    /// conflicts with the code observed at the adjacent observed blocks before and after it if both
+    /// are different non-empty code: the account would have to be destructed before being
+    /// redeployed, but wasn't observed without code in between. Accounts can be destructed and
+    /// redeployed within a block, and EIP-7702 delegations can change at any block, so code at
+    /// consecutive blocks or delegations never conflict with adjacent observations.
+    fn conflicting_block(&self, block_number: BlockNumber, code: &Bytes) -> Option<BlockNumber> {
+        if let Some(observed) = self.samples.get(&block_number) {
+            return (observed != code).then_some(block_number);
+        }
+        if code.is_empty() || is_delegation(code) {
+            return None;
+        }
+        let before = self.samples.range(..block_number).next_back();
+        let after = self.samples.range(block_number..).next();
+        before.into_iter().chain(after).find_map(|(&observed_block_number, observed)| {
+            let replaced = !observed.is_empty() && !is_delegation(observed) && observed != code;
+            (replaced && observed_block_number.abs_diff(block_number) > 1)
+                .then_some(observed_block_number)
+        })
+    }
+}
+
+/// Error for a provider returning code for an account that conflicts with the code already observed
+/// at the same block or an adjacent block, e.g. a provider lagging behind.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "inconsistent code for {address} on chain {chain}: code fetched at block {block_number} \
+     differs from code detected at block {detected_block_number}"
+)]
+pub struct InconsistentCodeError {
+    /// The address of the account
+    pub address: Address,
+    /// The chain of the account
+    pub chain: Chain,
+    /// The block number the conflicting code was fetched at
+    pub block_number: BlockNumber,
+    /// The block number the conflicting cached code was detected at
+    pub detected_block_number: BlockNumber,
 }
 
+/// Whether [`CodeCache::get_code_traced`] was served from the cache or fetched from the provider
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheHit {
+    /// The code was served from the cache
+    Hit,
+    /// The code was fetched from the provider
+    Miss,
+}
+
+/// A bounded map of account & chain -> code history
+type CodeCacheMap = Cache<
+    (Address, Chain),
+    CodeCacheEntry,
+    UnitWeighter,
+    DefaultHashBuilder,
+    EvictionLifecycle<(Address, Chain)>,
+>;
+
 /// Struct for cacheing code history of an account for a chain.
 #[derive(Debug)]
-pub struct CodeCache(Cache<(Address, Chain), CodeCacheEntry>);
+pub struct CodeCache {
+    cache: CodeCacheMap,
+    /// The lifecycle of the cache, reporting accounts whose code was evicted
+    evictions: EvictionLifecycle<(Address, Chain)>,
+    /// Whether inconsistent code returned by the provider is an error, rather than a warning
+    strict: bool,
+    /// The hits, misses and insertions of the cache
+    counters: CacheCounters,
+    /// The code fetches in flight, concurrent requests for the same code wait on the first one
+    in_flight: DashMap<(Address, Chain, BlockNumber), Arc<Mutex<()>>>,
+}
 
 impl Default for CodeCache {
     fn default() -> Self {
-        Self(Cache::new(10_000))
+        Self::with_capacity(CODE_CACHE_CAPACITY)
     }
 }
 
 impl CodeCache {
+    /// Creates a new cache holding the code of up to `capacity` accounts, e.g. to avoid refetching
+    /// code on large runs touching many accounts
+    pub fn with_capacity(capacity: usize) -> Self {
+        let evictions = EvictionLifecycle::default();
+        Self {
+            cache: Cache::with(
+                capacity,
+                capacity as u64,
+                UnitWeighter,
+                DefaultHashBuilder::default(),
+                evictions.clone(),
+            ),
+            evictions,
+            strict: false,
+            counters: Default::default(),
+            in_flight: Default::default(),
+        }
+    }
+
+    /// Creates a new cache that fails [`CodeCache::get_code`] with an [`InconsistentCodeError`]
+    /// if the provider returns code that conflicts with the cached code, instead of warning.
+    pub fn strict() -> Self {
+        Self { strict: true, ..Default::default() }
+    }
+
+    /// Sets a listener invoked with the address and chain of every account whose code is evicted
+    /// from the cache because it is at capacity, e.g. to log thrashing and decide to raise the
+    /// capacity, see [`CodeCache::with_capacity`].
+    ///
+    /// The listener is invoked while the cache is locked, so it must not access the cache.
+    ///
+    /// # Panics
+    ///
+    /// If an eviction listener was already set
+    pub fn with_eviction_listener(
+        self,
+        on_evict: impl Fn(Address, Chain) + Send + Sync + 'static,
+    ) -> Self {
+        self.evictions
+            .set_listener(move |&(address, chain): &(Address, Chain)| on_evict(address, chain));
+        self
+    }
+
+    /// Returns the hits and misses of [`CodeCache::get_code`] so far, the code observations
+    /// inserted and the number of accounts whose code is cached
+    pub fn stats(&self) -> CacheStats {
+        self.counters.stats(self.cache.len())
+    }
+
     /// Get the code of an account at a specific block, using the cache if possible.
     /// If the code is not in the cache, it will be fetched from the provider and cached.
     pub async fn get_code<N: Network, T: Transport + Clone, P: Provider<T, N>>(
@@ -42,66 +199,202 @@ impl CodeCache {
         chain: Chain,
         block_number: BlockNumber,
     ) -> TransportResult<Bytes> {
+        self.get_code_traced(provider, address, chain, block_number).await.map(|(code, _)| code)
+    }
+
+    /// Get the code of an account at a specific block like [`CodeCache::get_code`], along with
+    /// whether it was served from the cache, e.g. for telemetry.
+    ///
+    /// Concurrent requests for the same uncached code share a single fetch: the first request
+    /// fetches the code while the others wait for it, and are then served from the cache.
+    pub async fn get_code_traced<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        address: Address,
+        chain: Chain,
+        block_number: BlockNumber,
+    ) -> TransportResult<(Bytes, CacheHit)> {
         if let Some(code) = self.check_cache(address, chain, block_number) {
-            return Ok(code);
+            self.counters.hit();
+            return Ok((code, CacheHit::Hit));
         }
 
+        let key = (address, chain, block_number);
+        let fetch = self.in_flight.entry(key).or_default().clone();
+        let _guard = fetch.lock().await;
+        let result = self.fetch_code(provider, address, chain, block_number).await;
+        self.in_flight.remove_if(&key, |_, current| Arc::ptr_eq(current, &fetch));
+        result
+    }
+
+    /// Fetches the code of an account at a specific block and caches it, unless another request
+    /// cached it in the meantime
+    async fn fetch_code<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        address: Address,
+        chain: Chain,
+        block_number: BlockNumber,
+    ) -> TransportResult<(Bytes, CacheHit)> {
+        if let Some(code) = self.check_cache(address, chain, block_number) {
+            self.counters.hit();
+            return Ok((code, CacheHit::Hit));
+        }
+        self.counters.miss();
+
         let code = provider.get_code_at(address).block_id(block_number.into()).await?;
 
-        self.cache_code(address, chain, block_number, code.clone());
+        if let Err(err) = self.cache_code(address, chain, block_number, code.clone()) {
+            if self.strict {
+                return Err(TransportErrorKind::custom(err));
+            }
+            warn!(target: "backend::code_cache", %err, "keeping previously cached code");
+        }
 
-        Ok(code)
+        Ok((code, CacheHit::Miss))
     }
 
-    /// Check the cache for the code of an account at a specific block.
-    /// Returns the code if it is in the cache, otherwise None.
+    /// Narrows down the block at which the code of an account changed between the given blocks,
+    /// e.g. its deployment block, so that later requests for blocks on either side of the change
+    /// are served from the cache.
     ///
-    /// If the account had code at the time of the block or earlier, it had code at the time of the
-    /// block. If the account had no code at the time of the block or later, it had no code at
-    /// the time of the block.
-    fn check_cache(
+    /// The code observed at an account's first request is often far from the block it was
+    /// deployed at, so requests in between miss the cache. This binary searches the blocks between
+    /// `from` and `to` for the first block whose code differs from the code at the lower of them,
+    /// caching the code at every probed block, and returns it. Returns `None` if the code at both
+    /// blocks is the same, as a change in between can't be located then.
+    ///
+    /// This is opt-in, as it costs up to `log2(to - from)` requests up front. The code is assumed
+    /// to change at most once in the range; otherwise the change found is one of them.
+    pub async fn refine<N: Network, T: Transport + Clone, P: Provider<T, N>>(
         &self,
+        provider: &P,
         address: Address,
         chain: Chain,
-        block_number: BlockNumber,
-    ) -> Option<Bytes> {
-        if let Some(CodeCacheEntry { code_detected, no_code_detected_block_number }) =
-            self.0.get(&(address, chain))
-        {
-            if let Some((code_detected, code)) = code_detected {
-                if code_detected <= block_number {
-                    return Some(code.clone());
-                }
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> TransportResult<Option<BlockNumber>> {
+        let (mut low, mut high) = (from.min(to), from.max(to));
+        let low_code = self.get_code(provider, address, chain, low).await?;
+        if self.get_code(provider, address, chain, high).await? == low_code {
+            return Ok(None);
+        }
+
+        // The code at `low` is the code at the lower block, the code at `high` differs from it
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if self.get_code(provider, address, chain, mid).await? == low_code {
+                low = mid;
+            } else {
+                high = mid;
             }
+        }
+        trace!(target: "backend::code_cache", %address, %chain, block_number = high, "located code change");
+        Ok(Some(high))
+    }
+
+    /// Seeds the cache with the code of the accounts of the given chain persisted in foundry's rpc
+    /// cache directory, e.g. `~/.foundry/cache/rpc`, which holds a `<chain>/<block>/storage.json`
+    /// file per cached block.
+    ///
+    /// Returns the number of entries loaded.
+    pub fn load_from_foundry_cache(
+        &self,
+        path: impl AsRef<Path>,
+        chain: Chain,
+    ) -> eyre::Result<usize> {
+        let chain_dir = path.as_ref().join(chain.to_string());
+        let entries = std::fs::read_dir(&chain_dir)
+            .wrap_err_with(|| format!("failed to read cache dir {}", chain_dir.display()))?;
+
+        let mut blocks = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(block_number) =
+                entry.file_name().to_str().and_then(|name| name.parse::<BlockNumber>().ok())
+            else {
+                continue
+            };
+            let cache_file = entry.path().join("storage.json");
+            if !cache_file.is_file() {
+                continue
+            }
+
+            let cache = JsonBlockCacheDB::load(cache_file)?;
+            let accounts = cache
+                .db()
+                .accounts
+                .read()
+                .iter()
+                .filter_map(|(address, info)| {
+                    let code = match &info.code {
+                        Some(code) => code.original_bytes(),
+                        None if info.code_hash == KECCAK_EMPTY => Bytes::new(),
+                        // The code is unknown
+                        None => return None,
+                    };
+                    Some((*address, code))
+                })
+                .collect::<Vec<_>>();
+            blocks.push((block_number, accounts));
+        }
 
-            if let Some(no_code_detected_block_number) = no_code_detected_block_number {
-                if no_code_detected_block_number >= block_number {
-                    return Some(Bytes::new());
+        let mut loaded = 0;
+        for (block_number, accounts) in &blocks {
+            for (address, code) in accounts {
+                match self.cache_code(*address, chain, *block_number, code.clone()) {
+                    Ok(()) => loaded += 1,
+                    Err(err) => warn!(target: "backend::code_cache", %err, "skipping cached code"),
                 }
             }
         }
 
-        None
+        Ok(loaded)
+    }
+
+    /// Check the cache for the code of an account at a specific block.
+    /// Returns the code if it is in the cache, otherwise None.
+    ///
+    /// The code is known if it was observed at the block, or if the same code was observed at the
+    /// adjacent observed blocks before and after it, as it can't have changed in between without
+    /// being observed. Blocks outside of the observed range are never answered from the cache.
+    fn check_cache(
+        &self,
+        address: Address,
+        chain: Chain,
+        block_number: BlockNumber,
+    ) -> Option<Bytes> {
+        self.cache.get(&(address, chain))?.code_at(block_number).cloned()
     }
 
     /// Cache the code of an account at a specific block.
-    fn cache_code(&self, address: Address, chain: Chain, block_number: BlockNumber, code: Bytes) {
-        let entry: CodeCacheEntry = self
-            .0
-            .get_or_insert_with(&(address, chain), || {
-                Ok::<CodeCacheEntry, ()>(CodeCacheEntry::default())
-            })
-            .map(|mut history| {
-                if code.is_empty() {
-                    history.no_code_detected_block_number = Some(block_number);
-                } else {
-                    history.code_detected = Some((block_number, code));
-                }
-                history
-            })
-            .unwrap();
+    ///
+    /// Code conflicting with the code already observed at the same block or the adjacent observed
+    /// blocks is inconsistent, see [`CodeCacheEntry::conflicting_block`]. In that case the cached
+    /// code is kept and an error reporting the block of the conflicting observation is returned.
+    fn cache_code(
+        &self,
+        address: Address,
+        chain: Chain,
+        block_number: BlockNumber,
+        code: Bytes,
+    ) -> Result<(), InconsistentCodeError> {
+        // Inserting an empty entry first could evict another account for nothing
+        let mut entry = self.cache.get(&(address, chain)).unwrap_or_default();
 
-        self.0.insert((address, chain), entry);
+        if let Some(detected_block_number) = entry.conflicting_block(block_number, &code) {
+            return Err(InconsistentCodeError {
+                address,
+                chain,
+                block_number,
+                detected_block_number,
+            });
+        }
+        entry.samples.insert(block_number, code);
+
+        self.cache.insert((address, chain), entry);
+        self.counters.insertion();
+        Ok(())
     }
 }
 
@@ -117,21 +410,118 @@ fn test_check_code_cache() {
 
     let code = Bytes::from(vec![1, 2, 3]);
 
-    // Cache with code
-    cache.cache_code(address, chain, block_number, code.clone());
+    // Cache with code, only known at the observed block
+    cache.cache_code(address, chain, block_number, code.clone()).unwrap();
     assert_eq!(cache.check_cache(address, chain, block_number), Some(code.clone()));
-    assert_eq!(cache.check_cache(address, chain, block_number + 1), Some(code));
-
+    assert_eq!(cache.check_cache(address, chain, block_number + 1), None);
     assert_eq!(cache.check_cache(address, chain, block_number - 1), None);
 
-    let block_number = block_number - 10;
+    // The same code observed later is known in between
+    cache.cache_code(address, chain, block_number + 10, code.clone()).unwrap();
+    assert_eq!(cache.check_cache(address, chain, block_number + 5), Some(code));
 
-    // Cache with no code
-    cache.cache_code(address, chain, block_number, Bytes::new());
+    // Cache with no code, the code in between is unknown
+    let block_number = block_number - 10;
+    cache.cache_code(address, chain, block_number, Bytes::new()).unwrap();
     assert_eq!(cache.check_cache(address, chain, block_number), Some(Bytes::new()));
-    assert_eq!(cache.check_cache(address, chain, block_number - 1), Some(Bytes::new()));
-
     assert_eq!(cache.check_cache(address, chain, block_number + 1), None);
+    assert_eq!(cache.check_cache(address, chain, block_number - 1), None);
+}
+
+#[test]
+fn test_cache_redeployed_code() {
+    let cache = CodeCache::default();
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+    let (deployed, redeployed) = (Bytes::from(vec![1, 2, 3]), Bytes::from(vec![4, 5, 6]));
+
+    // Deployed at 100, destructed at 200, redeployed with different code at 300
+    for (block_number, code) in [
+        (100, deployed.clone()),
+        (150, deployed.clone()),
+        (200, Bytes::new()),
+        (250, Bytes::new()),
+        (300, redeployed.clone()),
+        (350, redeployed.clone()),
+    ] {
+        cache.cache_code(address, chain, block_number, code).unwrap();
+    }
+
+    assert_eq!(cache.check_cache(address, chain, 120), Some(deployed));
+    assert_eq!(cache.check_cache(address, chain, 220), Some(Bytes::new()));
+    assert_eq!(cache.check_cache(address, chain, 320), Some(redeployed));
+
+    // The code may have changed between differing observations, or outside of them
+    assert_eq!(cache.check_cache(address, chain, 175), None);
+    assert_eq!(cache.check_cache(address, chain, 275), None);
+    assert_eq!(cache.check_cache(address, chain, 400), None);
+    assert_eq!(cache.check_cache(address, chain, 50), None);
+}
+
+#[test]
+fn test_cache_delegated_code() {
+    let cache = CodeCache::default();
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+    let delegation =
+        Bytes::from([&EIP7702_DELEGATION_PREFIX[..], Address::from([2; 20]).as_slice()].concat());
+    assert!(is_delegation(&delegation));
+    assert!(!is_delegation(&Bytes::from_static(&EIP7702_DELEGATION_PREFIX)));
+
+    // A delegation is only known at the observed blocks, not in between
+    cache.cache_code(address, chain, 100, delegation.clone()).unwrap();
+    assert_eq!(cache.check_cache(address, chain, 100), Some(delegation.clone()));
+    assert_eq!(cache.check_cache(address, chain, 101), None);
+    assert_eq!(cache.check_cache(address, chain, 99), None);
+
+    cache.cache_code(address, chain, 110, delegation.clone()).unwrap();
+    assert_eq!(cache.check_cache(address, chain, 105), None);
+    assert_eq!(cache.check_cache(address, chain, 110), Some(delegation));
+
+    // Other code is still known in between its observations
+    let code = Bytes::from(vec![1, 2, 3]);
+    let other = Address::from([3; 20]);
+    cache.cache_code(other, chain, 100, code.clone()).unwrap();
+    cache.cache_code(other, chain, 110, code.clone()).unwrap();
+    assert_eq!(cache.check_cache(other, chain, 105), Some(code));
+}
+
+#[test]
+fn test_code_cache_capacity() {
+    let cache = CodeCache::with_capacity(10);
+    let chain = Chain::mainnet();
+    for i in 0..100u64 {
+        let address = Address::left_padding_from(&i.to_be_bytes());
+        cache.cache_code(address, chain, 1000, Bytes::from(vec![1, 2, 3])).unwrap();
+    }
+    assert!(cache.cache.len() <= 10, "{}", cache.cache.len());
+    assert_eq!(cache.cache.capacity(), 10);
+    assert_eq!(CodeCache::default().cache.capacity(), CODE_CACHE_CAPACITY as u64);
+}
+
+#[test]
+fn test_code_cache_eviction_listener() {
+    let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let cache = CodeCache::with_capacity(2).with_eviction_listener({
+        let evicted = evicted.clone();
+        move |address, chain| evicted.lock().unwrap().push((address, chain))
+    });
+    let chain = Chain::mainnet();
+    let addresses = (0..10u64).map(|i| Address::left_padding_from(&i.to_be_bytes()));
+    for address in addresses.clone() {
+        cache.cache_code(address, chain, 1000, Bytes::from(vec![1, 2, 3])).unwrap();
+    }
+
+    // Every account inserted is either still cached or was reported as evicted
+    let evicted = evicted.lock().unwrap();
+    assert!(evicted.len() >= 8, "{evicted:?}");
+    assert_eq!(evicted.len() + cache.cache.len(), 10);
+    for address in addresses {
+        assert_ne!(
+            evicted.contains(&(address, chain)),
+            cache.check_cache(address, chain, 1000).is_some()
+        );
+    }
 }
 
 #[test]
@@ -143,16 +533,353 @@ fn test_cache_code() {
 
     let code = Bytes::from(vec![1, 2, 3]);
 
-    cache.cache_code(address, chain, block_number, code.clone());
-    assert!(cache.0.get(&(address, chain)).unwrap().no_code_detected_block_number.is_none());
-    assert_eq!(cache.0.get(&(address, chain)).unwrap().code_detected, Some((block_number, code)));
+    cache.cache_code(address, chain, block_number, code.clone()).unwrap();
+    assert_eq!(
+        cache.cache.get(&(address, chain)).unwrap().samples,
+        BTreeMap::from([(block_number, code.clone())])
+    );
+
+    cache.cache_code(address, chain, block_number - 10, Bytes::new()).unwrap();
+    assert_eq!(
+        cache.cache.get(&(address, chain)).unwrap().samples,
+        BTreeMap::from([(block_number - 10, Bytes::new()), (block_number, code)])
+    );
+}
 
-    let code = Bytes::new();
-    let block_number = block_number - 10;
+#[test]
+fn test_cache_inconsistent_code() {
+    let cache = CodeCache::default();
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+    let block_number = 1000;
+
+    let code = Bytes::from(vec![1, 2, 3]);
+    let redeployed = Bytes::from(vec![4, 5, 6]);
+    cache.cache_code(address, chain, block_number, code.clone()).unwrap();
+
+    // Differing code fetched at the same block is inconsistent, and the cached code is kept
+    assert_eq!(
+        cache.cache_code(address, chain, block_number, Bytes::from(vec![7, 8, 9])),
+        Err(InconsistentCodeError {
+            address,
+            chain,
+            block_number,
+            detected_block_number: block_number,
+        })
+    );
+    assert_eq!(cache.check_cache(address, chain, block_number), Some(code));
+
+    // Differing code fetched at an adjacent block without the account being observed destructed
+    // in between is inconsistent with the code at that block, whether it's before or after it
+    for conflicting_block_number in [block_number - 10, block_number + 10] {
+        assert_eq!(
+            cache.cache_code(address, chain, conflicting_block_number, redeployed.clone()),
+            Err(InconsistentCodeError {
+                address,
+                chain,
+                block_number: conflicting_block_number,
+                detected_block_number: block_number,
+            })
+        );
+        assert_eq!(cache.check_cache(address, chain, conflicting_block_number), None);
+    }
+
+    // Code changing at consecutive blocks can be a redeploy within the block
+    cache.cache_code(address, chain, block_number + 1, redeployed.clone()).unwrap();
+
+    // Once the account was observed destructed, differing code is a redeploy
+    cache.cache_code(address, chain, block_number - 5, Bytes::new()).unwrap();
+    cache.cache_code(address, chain, block_number - 10, redeployed).unwrap();
+
+    // Delegations can change at any block
+    let mut delegation = EIP7702_DELEGATION_PREFIX.to_vec();
+    delegation.extend_from_slice(address.as_slice());
+    cache.cache_code(address, chain, block_number + 10, Bytes::from(delegation)).unwrap();
+}
+
+/// A transport serving the same code for every `eth_getCode` request
+#[cfg(test)]
+fn code_transport(code: Bytes) -> crate::mock_transport::MockTransport {
+    crate::mock_transport::MockTransport::new(move |method, _| {
+        assert_eq!(method, "eth_getCode");
+        crate::mock_transport::success(&code)
+    })
+}
+
+/// A log writer capturing the formatted logs, to assert on them
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedLogs {
+    /// Captures the logs of the current thread until the returned guard is dropped
+    fn capture(&self) -> tracing::subscriber::DefaultGuard {
+        tracing::subscriber::set_default(
+            tracing_subscriber::fmt().with_writer(self.clone()).with_ansi(false).finish(),
+        )
+    }
+
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl tracing_subscriber::fmt::MakeWriter<'_> for CapturedLogs {
+    type Writer = Self;
+
+    fn make_writer(&self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn test_get_code_inconsistent() {
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
+
+    let (cached, fetched) = (Bytes::from(vec![1, 2, 3]), Bytes::from(vec![4, 5, 6]));
+    let provider =
+        RootProvider::<_, Ethereum>::new(RpcClient::new(code_transport(fetched.clone()), true));
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+
+    // The fetched code conflicts with the code cached at an earlier block, which is kept with a
+    // warning
+    let cache = CodeCache::default();
+    cache.cache_code(address, chain, 900, cached.clone()).unwrap();
+    let logs = CapturedLogs::default();
+    let guard = logs.capture();
+    assert_eq!(cache.get_code(&provider, address, chain, 1000).await.unwrap(), fetched);
+    drop(guard);
+    let logs = logs.contents();
+    assert!(logs.contains("WARN"), "{logs}");
+    assert!(logs.contains("keeping previously cached code"), "{logs}");
+    assert_eq!(cache.check_cache(address, chain, 900), Some(cached.clone()));
+    assert_eq!(cache.check_cache(address, chain, 1000), None);
+
+    // In strict mode, the conflict is an error
+    let cache = CodeCache::strict();
+    cache.cache_code(address, chain, 900, cached).unwrap();
+    let err = cache.get_code(&provider, address, chain, 1000).await.unwrap_err();
+    let expected =
+        InconsistentCodeError { address, chain, block_number: 1000, detected_block_number: 900 };
+    assert!(err.to_string().contains(&expected.to_string()), "{err}");
+    assert_eq!(cache.check_cache(address, chain, 1000), None);
+}
+
+#[tokio::test]
+async fn test_get_code_traced() {
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
+
+    let code = Bytes::from(vec![1, 2, 3]);
+    let provider =
+        RootProvider::<_, Ethereum>::new(RpcClient::new(code_transport(code.clone()), true));
+    let cache = CodeCache::default();
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+
+    assert_eq!(
+        cache.get_code_traced(&provider, address, chain, 1000).await.unwrap(),
+        (code.clone(), CacheHit::Miss)
+    );
+    assert_eq!(
+        cache.get_code_traced(&provider, address, chain, 1000).await.unwrap(),
+        (code, CacheHit::Hit)
+    );
+}
+
+/// A transport serving the same code for every `eth_getCode` request after a delay
+#[cfg(test)]
+fn slow_code_transport() -> crate::mock_transport::MockTransport {
+    code_transport(Bytes::from(vec![1, 2, 3])).with_delay(std::time::Duration::from_millis(50))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_get_code_single_flight() {
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
+
+    let transport = slow_code_transport();
+    let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(transport.clone(), true));
+    let cache = Arc::new(CodeCache::default());
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+
+    let tasks = (0..16)
+        .map(|_| {
+            let (cache, provider) = (cache.clone(), provider.clone());
+            tokio::spawn(async move { cache.get_code(&provider, address, chain, 1000).await })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        assert_eq!(task.await.unwrap().unwrap(), Bytes::from(vec![1, 2, 3]));
+    }
+
+    assert_eq!(transport.requests(), 1);
+    assert_eq!(cache.stats(), CacheStats { hits: 15, misses: 1, insertions: 1, entries: 1 });
+    assert!(cache.in_flight.is_empty());
+}
+
+#[tokio::test]
+async fn test_code_cache_stats() {
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
+
+    let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+        code_transport(Bytes::from(vec![1, 2, 3])),
+        true,
+    ));
+    let cache = CodeCache::default();
+    let chain = Chain::mainnet();
+    let (first, second) = (Address::from([1; 20]), Address::from([2; 20]));
+
+    for (address, block_number) in [(first, 1000), (first, 1000), (second, 1000), (first, 1001)] {
+        cache.get_code(&provider, address, chain, block_number).await.unwrap();
+    }
+    cache.get_code(&provider, first, chain, 1000).await.unwrap();
+
+    assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 3, insertions: 3, entries: 2 });
+}
+
+/// A transport serving empty code before the given block and the same code from it on
+#[cfg(test)]
+fn deployment_transport(deployed_at: BlockNumber) -> crate::mock_transport::MockTransport {
+    crate::mock_transport::MockTransport::new(move |method, params| {
+        assert_eq!(method, "eth_getCode");
+        let block_number =
+            u64::from_str_radix(params[1].as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+        let code =
+            if block_number < deployed_at { Bytes::new() } else { Bytes::from(vec![1, 2, 3]) };
+        crate::mock_transport::success(&code)
+    })
+}
+
+#[tokio::test]
+async fn test_refine_code_change() {
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
+
+    let deployed_at = 421;
+    let transport = deployment_transport(deployed_at);
+    let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(transport.clone(), true));
+    let cache = CodeCache::default();
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+
+    // Without refining, the blocks between the observations are unknown
+    cache.get_code(&provider, address, chain, 0).await.unwrap();
+    cache.get_code(&provider, address, chain, 1000).await.unwrap();
+    assert_eq!(cache.check_cache(address, chain, deployed_at), None);
 
-    cache.cache_code(address, chain, block_number, code.clone());
+    assert_eq!(cache.refine(&provider, address, chain, 0, 1000).await.unwrap(), Some(deployed_at));
+    let requests = transport.requests();
+    assert!(requests <= 2 + 10, "{requests}");
+
+    // Both sides of the deployment are now served from the cache
+    assert_eq!(
+        cache.get_code_traced(&provider, address, chain, deployed_at - 1).await.unwrap(),
+        (Bytes::new(), CacheHit::Hit)
+    );
+    assert_eq!(
+        cache.get_code_traced(&provider, address, chain, deployed_at).await.unwrap(),
+        (Bytes::from(vec![1, 2, 3]), CacheHit::Hit)
+    );
+    assert_eq!(cache.check_cache(address, chain, 10), Some(Bytes::new()));
+    assert_eq!(cache.check_cache(address, chain, 900), Some(Bytes::from(vec![1, 2, 3])));
+    assert_eq!(transport.requests(), requests);
+
+    // Nothing to locate if the code is the same at both blocks
+    assert_eq!(cache.refine(&provider, address, chain, 500, 1000).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_cache_context_shared_between_backends() {
+    use crate::backend::{Backend, CacheContext};
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
+
+    let code = Bytes::from(vec![1, 2, 3]);
+    let provider =
+        RootProvider::<_, Ethereum>::new(RpcClient::new(code_transport(code.clone()), true));
+    let address = Address::from([1; 20]);
+    let chain = Chain::mainnet();
+
+    let ctx = CacheContext::default();
+    let first = Backend::spawn_with_caches(None, ctx.clone());
+    let second = Backend::spawn_with_caches(None, ctx);
+    assert!(std::sync::Arc::ptr_eq(&first.environment_cache, &second.environment_cache));
+
+    assert_eq!(
+        first.code_cache.get_code_traced(&provider, address, chain, 1000).await.unwrap(),
+        (code.clone(), CacheHit::Miss)
+    );
     assert_eq!(
-        cache.0.get(&(address, chain)).unwrap().no_code_detected_block_number,
-        Some(block_number)
+        second.code_cache.get_code_traced(&provider, address, chain, 1000).await.unwrap(),
+        (code.clone(), CacheHit::Hit)
     );
+
+    // Backends spawned without a context don't share their caches
+    let third = Backend::spawn(None);
+    assert_eq!(
+        third.code_cache.get_code_traced(&provider, address, chain, 1000).await.unwrap(),
+        (code, CacheHit::Miss)
+    );
+}
+
+#[test]
+fn test_load_from_foundry_cache() {
+    use crate::fork::{BlockchainDb, BlockchainDbMeta};
+    use revm::primitives::{AccountInfo, Bytecode, U256};
+
+    let dir = tempfile::tempdir().unwrap();
+    let rpc_cache_dir = dir.path();
+    let chain = Chain::mainnet();
+
+    let contract = Address::from([1; 20]);
+    let eoa = Address::from([2; 20]);
+    let code = Bytes::from(vec![0x60, 0x00, 0x00]);
+    let bytecode = Bytecode::new_raw(code.clone());
+    let contract_info = AccountInfo::new(U256::ZERO, 1, bytecode.hash_slow(), bytecode);
+
+    // Write a fixture cache of two blocks, like foundry does
+    for (block_number, accounts) in
+        [(100, vec![(eoa, AccountInfo::default())]), (200, vec![(contract, contract_info)])]
+    {
+        let cache_file = rpc_cache_dir
+            .join(chain.to_string())
+            .join(block_number.to_string())
+            .join("storage.json");
+        let db = BlockchainDb::new(
+            BlockchainDbMeta::new(Default::default(), String::new()),
+            Some(cache_file),
+        );
+        db.accounts().write().extend(accounts);
+        db.cache().flush();
+    }
+
+    let cache = CodeCache::default();
+    assert_eq!(cache.load_from_foundry_cache(rpc_cache_dir, chain).unwrap(), 2);
+
+    assert_eq!(cache.check_cache(contract, chain, 200), Some(code));
+    assert_eq!(cache.check_cache(contract, chain, 300), None);
+    assert_eq!(cache.check_cache(contract, chain, 199), None);
+    assert_eq!(cache.check_cache(eoa, chain, 100), Some(Bytes::new()));
+    assert_eq!(cache.check_cache(eoa, chain, 101), None);
+
+    // Nothing is cached for other chains
+    assert!(cache.load_from_foundry_cache(rpc_cache_dir, Chain::optimism_mainnet()).is_err());
 }
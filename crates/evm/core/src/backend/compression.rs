@@ -0,0 +1,69 @@
+use flate2::{bufread::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// The magic bytes every gzip stream starts with, which mark a saved file as compressed
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Returns whether the given path has a `.gz` extension, i.e. is saved compressed by default
+pub(crate) fn is_gz_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "gz")
+}
+
+/// A writer to a saved file, gzip compressed or not, see [`create`]
+pub(crate) enum FileWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl FileWriter {
+    /// Flushes the writer, writing the gzip trailer if compressed
+    pub(crate) fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(mut writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Creates the file at the given path, gzip compressing what's written if `compress` is set.
+///
+/// The writer must be finished with [`FileWriter::finish`].
+pub(crate) fn create(path: &Path, compress: bool) -> io::Result<FileWriter> {
+    let writer = BufWriter::new(File::create(path)?);
+    Ok(if compress {
+        FileWriter::Gzip(GzEncoder::new(writer, Compression::default()))
+    } else {
+        FileWriter::Plain(writer)
+    })
+}
+
+/// Opens the file at the given path, transparently decompressing it if it's gzip compressed,
+/// regardless of its extension
+pub(crate) fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(GzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
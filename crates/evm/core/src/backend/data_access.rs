@@ -1,12 +1,30 @@
 use crate::{
-    backend::{DatabaseError, DatabaseRef},
-    fork::{CreateFork, SharedBackend},
+    backend::{
+        compression, reproducer::redact_url, AccessLog, AccessLogEntry, DatabaseError, DatabaseRef,
+        RetryPolicy,
+    },
+    fork::CreateFork,
+    InspectorExt,
 };
 use alloy_chains::Chain;
 use alloy_primitives::{Address, B256, U256};
+use eyre::WrapErr;
+use revm::{
+    interpreter::{opcode, Interpreter},
+    primitives::Env,
+    Database, EvmContext, Inspector,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt,
+    ops::Deref,
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
 
 /// Struct to represent an evm data access
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Serialize, Deserialize)]
 pub struct Access {
     /// The data access type
     pub access_type: AccessType,
@@ -16,11 +34,30 @@ pub struct Access {
     pub state_lookup: StateLookup,
 }
 
+impl Access {
+    /// Returns the key identifying what was accessed regardless of when, i.e. the access type and
+    /// chain without the state lookup, see [`dedup_by_location`].
+    pub fn access_key_ignoring_lookup(&self) -> (AccessType, Chain) {
+        (self.access_type.clone(), self.chain)
+    }
+}
+
+/// Displays the access on one line, e.g.
+/// `storage 0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2[0x0] @ chain=1 roll=latest`
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} @ chain={} roll={}", self.access_type, self.chain.id(), self.state_lookup)
+    }
+}
+
 impl RevmDbAccess {
-    /// Executes the RevmDbAccess against the SharedBackend
-    pub fn execute(&self, db: &mut SharedBackend) -> Result<(), DatabaseError> {
+    /// Executes the RevmDbAccess against the given database, e.g. a [crate::fork::SharedBackend]
+    pub fn execute<DB: DatabaseRef<Error = DatabaseError>>(
+        &self,
+        db: &DB,
+    ) -> Result<(), DatabaseError> {
         match self {
-            RevmDbAccess::Basic(addr) => {
+            RevmDbAccess::Basic(addr) | RevmDbAccess::Exists(addr) => {
                 db.basic_ref(*addr)?;
             }
             RevmDbAccess::Storage(addr, key) => {
@@ -41,17 +78,419 @@ impl RevmDbAccess {
     }
 }
 
+/// A caller-supplied sink receiving recorded accesses, see [`DataAccesses::set_sink`]
+pub type AccessSink = Box<dyn Fn(Access) + Send + Sync>;
+
+/// Records the accesses of a backend into its access set, with a method per access category, see
+/// [`DataAccesses::set_recorder`].
+///
+/// Every method adds the access to the set by default, implementors can override them to skip
+/// categories or to capture custom metadata.
+pub trait AccessRecorder: Send + Sync {
+    /// Records an access to a basic account
+    fn record_basic(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+
+    /// Records an access to a storage slot
+    fn record_storage(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+
+    /// Records an access to a code hash
+    fn record_code_by_hash(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+
+    /// Records an access to a block hash
+    fn record_block_hash(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+
+    /// Records an account existence check
+    fn record_exists(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+
+    /// Records the creation of a fork
+    fn record_fork(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+
+    /// Records an access to transient storage
+    fn record_transient_storage(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+
+    /// Records a read of the block or chain environment
+    fn record_environment(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+        accesses.insert(access);
+    }
+}
+
+/// The [`AccessRecorder`] adding every access to the access set
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultAccessRecorder;
+
+impl AccessRecorder for DefaultAccessRecorder {}
+
+impl Default for Box<dyn AccessRecorder> {
+    fn default() -> Self {
+        Box::new(DefaultAccessRecorder)
+    }
+}
+
+/// The set of data accesses recorded by a backend, shared with its forks.
+///
+/// Derefs to the set of accesses. Accesses recorded with [`DataAccesses::record`] are also passed
+/// to the sink, if one is set.
+#[derive(Default)]
+pub struct DataAccesses {
+    accesses: dashmap::DashSet<Access>,
+    sink: RwLock<Option<(AccessSink, bool)>>,
+    record_from_block: RwLock<Option<u64>>,
+    recorder: RwLock<Box<dyn AccessRecorder>>,
+    value_log: Mutex<Option<Vec<AccessLogEntry>>>,
+}
+
+impl DataAccesses {
+    /// Records the given access, passing it to the sink if one is set
+    ///
+    /// Accesses at a block below the block set with [`DataAccesses::set_record_from_block`] are
+    /// ignored. Only [`StateLookup::RollAt`] accesses can be resolved to a block here, use
+    /// [`DataAccesses::record_at`] if the block of the access is known.
+    pub fn record(&self, access: Access) {
+        match access.state_lookup {
+            StateLookup::RollAt(block_number) => self.record_at(access, block_number),
+            StateLookup::RollN(_) | StateLookup::RollTransaction(_) => self.insert(access),
+        }
+    }
+
+    /// Records the given access, whose state lookup resolves to the given block number
+    pub fn record_at(&self, access: Access, block_number: u64) {
+        if self.record_from_block.read().unwrap().is_some_and(|from| block_number < from) {
+            return;
+        }
+        self.insert(access);
+    }
+
+    /// Sets the block from which on accesses are recorded, accesses at earlier blocks are ignored.
+    ///
+    /// Passing `None` records accesses at all blocks.
+    pub fn set_record_from_block(&self, block_number: Option<u64>) {
+        *self.record_from_block.write().unwrap() = block_number;
+    }
+
+    fn insert(&self, access: Access) {
+        match &*self.sink.read().unwrap() {
+            Some((sink, accumulate)) => {
+                if *accumulate {
+                    self.accumulate(access.clone());
+                }
+                sink(access);
+            }
+            None => self.accumulate(access),
+        }
+    }
+
+    /// Adds the access to the set through the recorder method of its category
+    fn accumulate(&self, access: Access) {
+        let recorder = self.recorder.read().unwrap();
+        let accesses = &self.accesses;
+        match &access.access_type {
+            AccessType::RevmDbAccess(RevmDbAccess::Basic(_)) => {
+                recorder.record_basic(accesses, access)
+            }
+            AccessType::RevmDbAccess(RevmDbAccess::Storage(..)) => {
+                recorder.record_storage(accesses, access)
+            }
+            AccessType::RevmDbAccess(RevmDbAccess::CodeByHash(_)) => {
+                recorder.record_code_by_hash(accesses, access)
+            }
+            AccessType::RevmDbAccess(RevmDbAccess::BlockHash(_)) => {
+                recorder.record_block_hash(accesses, access)
+            }
+            AccessType::RevmDbAccess(RevmDbAccess::Exists(_)) => {
+                recorder.record_exists(accesses, access)
+            }
+            AccessType::CreateFork { .. } => recorder.record_fork(accesses, access),
+            AccessType::TransientStorage(_) => recorder.record_transient_storage(accesses, access),
+            AccessType::Environment(_) => recorder.record_environment(accesses, access),
+        }
+    }
+
+    /// Sets whether the values read by the accesses of forks are logged in order from now on, see
+    /// [`DataAccesses::take_value_log`].
+    ///
+    /// Disabling the log drops the entries logged so far.
+    pub fn set_log_values(&self, enabled: bool) {
+        let mut value_log = self.value_log.lock().unwrap();
+        match (enabled, value_log.is_some()) {
+            (true, false) => *value_log = Some(Vec::new()),
+            (false, true) => *value_log = None,
+            _ => {}
+        }
+    }
+
+    /// Returns whether the values read by accesses are logged, see
+    /// [`DataAccesses::set_log_values`]
+    pub fn logs_values(&self) -> bool {
+        self.value_log.lock().unwrap().is_some()
+    }
+
+    /// Appends the given entry to the value log, if enabled
+    pub fn log_value(&self, entry: AccessLogEntry) {
+        if let Some(value_log) = &mut *self.value_log.lock().unwrap() {
+            value_log.push(entry);
+        }
+    }
+
+    /// Takes the entries logged so far, in the order they were read, keeping the log enabled
+    pub fn take_value_log(&self) -> AccessLog {
+        let entries =
+            self.value_log.lock().unwrap().as_mut().map(std::mem::take).unwrap_or_default();
+        AccessLog { entries }
+    }
+
+    /// Sets the recorder adding the accesses recorded from now on to the set, e.g. to only keep
+    /// certain categories of accesses.
+    ///
+    /// The sink, if set, still receives every access.
+    pub fn set_recorder(&self, recorder: Box<dyn AccessRecorder>) {
+        *self.recorder.write().unwrap() = recorder;
+    }
+
+    /// Sets the sink receiving every access recorded from now on, including repeated ones.
+    ///
+    /// If `accumulate` is false, the accesses passed to the sink are not added to the set.
+    /// Passing `None` restores accumulating into the set only.
+    pub fn set_sink(&self, sink: Option<AccessSink>, accumulate: bool) {
+        *self.sink.write().unwrap() = sink.map(|sink| (sink, accumulate));
+    }
+}
+
+impl Deref for DataAccesses {
+    type Target = dashmap::DashSet<Access>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.accesses
+    }
+}
+
+impl fmt::Debug for DataAccesses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataAccesses")
+            .field("accesses", &self.accesses)
+            .field("has_sink", &self.sink.read().unwrap().is_some())
+            .field("record_from_block", &*self.record_from_block.read().unwrap())
+            .field("logs_values", &self.logs_values())
+            .finish()
+    }
+}
+
 /// Enum to represent the different types of evm data accesses
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Serialize, Deserialize)]
 pub enum AccessType {
     /// Access to a block hash by the block number
     RevmDbAccess(RevmDbAccess),
-    /// Create a fork with the given url
-    CreateFork(String),
+    /// Create a fork with the given url, along with the env it resolved to, including the chain
+    /// specific adjustments, and the block number the fork resolved to, if recorded.
+    ///
+    /// Replaying the access re-creates the fork pinned to the recorded block, even if the state
+    /// lookup is relative to a head that has moved on since.
+    CreateFork {
+        /// The url of the fork
+        url: String,
+        /// The env of the fork, including the chain specific adjustments, if recorded
+        env_adjustments: Option<Box<ChainEnvAdjustments>>,
+        /// The block number the fork resolved to, if recorded
+        block_number: Option<u64>,
+    },
+    /// Access to transient storage (EIP-1153).
+    ///
+    /// Transient storage doesn't persist across transactions, so this is recorded for analysis
+    /// only and never fetched from a remote.
+    TransientStorage(TransientStorageAccess),
+    /// Read of the block or chain environment, e.g. `block.basefee`.
+    ///
+    /// The environment is part of the fork's env, so this is recorded for reproducibility analysis
+    /// only and never fetched from a remote.
+    Environment(EnvironmentAccess),
+}
+
+impl AccessType {
+    /// Returns whether the value read by the access can change with the block the fork is pinned
+    /// at, e.g. to assess how safe it is to re-pin a fork.
+    ///
+    /// Account state and the block environment are block sensitive. Code by hash, historical block
+    /// hashes, the chain id and transient storage are not: they are either immutable or not read
+    /// from the fork.
+    pub fn is_block_sensitive(&self) -> bool {
+        match self {
+            Self::RevmDbAccess(
+                RevmDbAccess::Basic(_) | RevmDbAccess::Storage(..) | RevmDbAccess::Exists(_),
+            ) |
+            Self::CreateFork { .. } => true,
+            Self::RevmDbAccess(RevmDbAccess::CodeByHash(_) | RevmDbAccess::BlockHash(_)) |
+            Self::TransientStorage(_) => false,
+            Self::Environment(access) => !matches!(access, EnvironmentAccess::ChainId),
+        }
+    }
+
+    /// Returns the name of the kind of the access, e.g. `storage`, as used by
+    /// [`AccessSummary`] and the [`Display`](fmt::Display) of accesses
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::RevmDbAccess(RevmDbAccess::Storage(..)) => "storage",
+            Self::RevmDbAccess(RevmDbAccess::Basic(_)) => "basic",
+            Self::RevmDbAccess(RevmDbAccess::CodeByHash(_)) => "code-by-hash",
+            Self::RevmDbAccess(RevmDbAccess::BlockHash(_)) => "block-hash",
+            Self::RevmDbAccess(RevmDbAccess::Exists(_)) => "exists",
+            Self::CreateFork { .. } => "fork",
+            Self::TransientStorage(_) => "transient-storage",
+            Self::Environment(_) => "environment",
+        }
+    }
+}
+
+/// Displays the kind of the access followed by what was accessed, with checksummed addresses and
+/// hex slots, e.g. `basic 0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2`.
+///
+/// The credentials of fork urls are redacted.
+impl fmt::Display for AccessType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = self.kind();
+        match self {
+            Self::RevmDbAccess(RevmDbAccess::Storage(address, slot)) => {
+                write!(f, "{kind} {address}[{slot:#x}]")
+            }
+            Self::RevmDbAccess(RevmDbAccess::Basic(address) | RevmDbAccess::Exists(address)) => {
+                write!(f, "{kind} {address}")
+            }
+            Self::RevmDbAccess(RevmDbAccess::CodeByHash(hash)) => write!(f, "{kind} {hash}"),
+            Self::RevmDbAccess(RevmDbAccess::BlockHash(number)) => write!(f, "{kind} {number}"),
+            Self::CreateFork { url, block_number: None, .. } => {
+                write!(f, "{kind} {}", redact_url(url))
+            }
+            Self::CreateFork { url, block_number: Some(block), .. } => {
+                write!(f, "{kind} {} at {block}", redact_url(url))
+            }
+            Self::TransientStorage(TransientStorageAccess::Load(address, slot)) => {
+                write!(f, "{kind} load {address}[{slot:#x}]")
+            }
+            Self::TransientStorage(TransientStorageAccess::Store(address, slot)) => {
+                write!(f, "{kind} store {address}[{slot:#x}]")
+            }
+            Self::Environment(access) => {
+                let field = match access {
+                    EnvironmentAccess::BaseFee => "basefee",
+                    EnvironmentAccess::Timestamp => "timestamp",
+                    EnvironmentAccess::Number => "number",
+                    EnvironmentAccess::ChainId => "chainid",
+                };
+                write!(f, "{kind} {field}")
+            }
+        }
+    }
+}
+
+/// The env of a fork as resolved when it was created, including the chain and block specific
+/// adjustments, see
+/// [`apply_chain_and_block_specific_env_changes`](crate::utils::apply_chain_and_block_specific_env_changes).
+///
+/// These depend on the fork's block as served by the provider, so they are recorded to reconstruct
+/// the same env on replay, e.g. the L1 block number used as `block.number` on Arbitrum.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChainEnvAdjustments {
+    /// The chain id of the env
+    pub chain_id: u64,
+    /// The block number of the env, e.g. the L1 block number on Arbitrum
+    pub block_number: U256,
+    /// The timestamp of the block
+    pub timestamp: U256,
+    /// The coinbase of the block
+    pub coinbase: Address,
+    /// The base fee of the block
+    pub basefee: U256,
+    /// The difficulty of the env, i.e. the prevrandao after the merge
+    pub difficulty: U256,
+    /// The prevrandao of the block, if after the merge
+    pub prevrandao: Option<B256>,
+    /// The gas limit of the block
+    pub gas_limit: U256,
+    /// The gas price of the env
+    pub gas_price: U256,
+}
+
+impl ChainEnvAdjustments {
+    /// Captures the adjustments from the env of a fork
+    pub fn from_env(env: &Env) -> Self {
+        Self {
+            chain_id: env.cfg.chain_id,
+            block_number: env.block.number,
+            timestamp: env.block.timestamp,
+            coinbase: env.block.coinbase,
+            basefee: env.block.basefee,
+            difficulty: env.block.difficulty,
+            prevrandao: env.block.prevrandao,
+            gas_limit: env.block.gas_limit,
+            gas_price: env.tx.gas_price,
+        }
+    }
+
+    /// Applies the adjustments to the given env
+    pub fn apply(&self, env: &mut Env) {
+        env.cfg.chain_id = self.chain_id;
+        env.tx.chain_id = Some(self.chain_id);
+        env.block.number = self.block_number;
+        env.block.timestamp = self.timestamp;
+        env.block.coinbase = self.coinbase;
+        env.block.basefee = self.basefee;
+        env.block.difficulty = self.difficulty;
+        env.block.prevrandao = self.prevrandao;
+        env.block.gas_limit = self.gas_limit;
+        env.tx.gas_price = self.gas_price;
+    }
+}
+
+/// Enum to represent the different types of transient storage accesses
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Serialize, Deserialize)]
+pub enum TransientStorageAccess {
+    /// Read of a transient storage slot with `TLOAD`
+    Load(Address, U256),
+    /// Write to a transient storage slot with `TSTORE`
+    Store(Address, U256),
+}
+
+impl TransientStorageAccess {
+    /// Converts the TransientStorageAccess to an Access
+    pub fn to_access(self, chain: Chain, state_lookup: StateLookup) -> Access {
+        Access { access_type: AccessType::TransientStorage(self), chain, state_lookup }
+    }
+}
+
+/// Enum to represent the environment values read by opcodes
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum EnvironmentAccess {
+    /// Read of the block base fee with `BASEFEE`
+    BaseFee,
+    /// Read of the block timestamp with `TIMESTAMP`
+    Timestamp,
+    /// Read of the block number with `NUMBER`
+    Number,
+    /// Read of the chain id with `CHAINID`
+    ChainId,
+}
+
+impl EnvironmentAccess {
+    /// Converts the EnvironmentAccess to an Access
+    pub fn to_access(self, chain: Chain, state_lookup: StateLookup) -> Access {
+        Access { access_type: AccessType::Environment(self), chain, state_lookup }
+    }
 }
 
 /// Enum to represent the different types of evm data accesses
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Serialize, Deserialize)]
 pub enum RevmDbAccess {
     /// Access to a storage slot
     Storage(Address, U256),
@@ -61,14 +500,98 @@ pub enum RevmDbAccess {
     CodeByHash(B256),
     /// Access to a block hash by the block number
     BlockHash(U256),
+    /// Check of whether an account exists.
+    ///
+    /// Reads the basic account like [`RevmDbAccess::Basic`], but records that only the existence
+    /// of the account was needed, not its balance, nonce or code.
+    Exists(Address),
+}
+
+/// Which [`RevmDbAccess`] variants
+/// [`Backend::load_accesses`](crate::backend::Backend::load_accesses) executes, e.g. to skip
+/// expensive block hash fetches during a targeted warmup.
+///
+/// Fork creations, transient storage and environment accesses are always executed. The default mask
+/// executes every variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessTypeMask {
+    /// Whether storage slot accesses are executed
+    pub storage: bool,
+    /// Whether basic account accesses are executed
+    pub basic: bool,
+    /// Whether code by hash accesses are executed
+    pub code_by_hash: bool,
+    /// Whether block hash accesses are executed
+    pub block_hash: bool,
+    /// Whether account existence checks are executed
+    pub exists: bool,
+}
+
+impl Default for AccessTypeMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl AccessTypeMask {
+    /// The mask executing every variant
+    pub const ALL: Self =
+        Self { storage: true, basic: true, code_by_hash: true, block_hash: true, exists: true };
+
+    /// Returns whether accesses of the given type are executed
+    pub fn allows(&self, access_type: &AccessType) -> bool {
+        match access_type {
+            AccessType::RevmDbAccess(RevmDbAccess::Storage(..)) => self.storage,
+            AccessType::RevmDbAccess(RevmDbAccess::Basic(_)) => self.basic,
+            AccessType::RevmDbAccess(RevmDbAccess::CodeByHash(_)) => self.code_by_hash,
+            AccessType::RevmDbAccess(RevmDbAccess::BlockHash(_)) => self.block_hash,
+            AccessType::RevmDbAccess(RevmDbAccess::Exists(_)) => self.exists,
+            AccessType::CreateFork { .. } |
+            AccessType::TransientStorage(_) |
+            AccessType::Environment(_) => true,
+        }
+    }
+}
+
+/// Options for [`Backend::load_accesses`](crate::backend::Backend::load_accesses).
+///
+/// The default options load every access, with one access in flight per thread of the global
+/// thread pool and the default [`RetryPolicy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadAccessesOptions {
+    /// The maximum number of accesses fetched from the provider, accesses that are already cached
+    /// are always loaded
+    pub max_fetches: Option<usize>,
+    /// The maximum number of accesses in flight at once
+    pub max_concurrency: Option<usize>,
+    /// Which access types are loaded
+    pub mask: AccessTypeMask,
+    /// How reads failing with a transient error are retried
+    pub retry: RetryPolicy,
+}
+
+/// The outcome of [`Backend::load_accesses`](crate::backend::Backend::load_accesses)
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum LoadAccessesOutcome {
+    /// All accesses were loaded
+    Loaded,
+    /// The fetch budget was exhausted before all accesses were loaded
+    BudgetExhausted {
+        /// The accesses that were not loaded, in no particular order
+        remaining: Vec<Access>,
+    },
 }
 
 /// Enum to represent the different ways to look up state
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Serialize, Deserialize)]
 pub enum StateLookup {
+    /// The block at the given offset from the latest block
     RollN(i64),
+    /// The given block
     RollAt(u64),
-    //RollTransaction(B256),
+    /// The state right before the given transaction, i.e. after the preceding transactions of its
+    /// block
+    RollTransaction(B256),
 }
 
 impl Default for StateLookup {
@@ -77,22 +600,1264 @@ impl Default for StateLookup {
     }
 }
 
+/// Displays the block relative to the latest one, e.g. `latest` or `latest-2`, the block number,
+/// or the transaction, e.g. `tx 0x…`
+impl fmt::Display for StateLookup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RollN(0) => write!(f, "latest"),
+            Self::RollN(offset) => write!(f, "latest{offset:+}"),
+            Self::RollAt(block_number) => write!(f, "{block_number}"),
+            Self::RollTransaction(tx_hash) => write!(f, "tx {tx_hash}"),
+        }
+    }
+}
+
 impl From<&CreateFork> for StateLookup {
     fn from(create_fork: &CreateFork) -> Self {
         create_fork.evm_opts.fork_block_number.map(StateLookup::RollAt).unwrap_or_default()
     }
 }
 
+/// An inspector that records the transient storage accesses of the executed calls into an access
+/// set, e.g. the data accesses of a [`Backend`](crate::backend::Backend).
+#[derive(Clone, Debug)]
+pub struct TransientStorageRecorder {
+    data_accesses: Arc<DataAccesses>,
+    chain: Chain,
+    state_lookup: StateLookup,
+}
+
+impl TransientStorageRecorder {
+    /// Creates a new recorder, tagging the recorded accesses with the given chain and state lookup
+    pub fn new(data_accesses: Arc<DataAccesses>, chain: Chain, state_lookup: StateLookup) -> Self {
+        Self { data_accesses, chain, state_lookup }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for TransientStorageRecorder {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let access = match interp.current_opcode() {
+            opcode::TLOAD => TransientStorageAccess::Load,
+            opcode::TSTORE => TransientStorageAccess::Store,
+            _ => return,
+        };
+        let Ok(slot) = interp.stack().peek(0) else { return };
+        self.data_accesses.record(
+            access(interp.contract.target_address, slot)
+                .to_access(self.chain, self.state_lookup.clone()),
+        );
+    }
+}
+
+impl<DB: Database> InspectorExt<DB> for TransientStorageRecorder {}
+
+/// An inspector that records the environment values read by the executed calls, e.g.
+/// `block.basefee`, into an access set, e.g. the data accesses of a
+/// [`Backend`](crate::backend::Backend).
+#[derive(Clone, Debug)]
+pub struct EnvironmentRecorder {
+    data_accesses: Arc<DataAccesses>,
+    chain: Chain,
+    state_lookup: StateLookup,
+}
+
+impl EnvironmentRecorder {
+    /// Creates a new recorder, tagging the recorded accesses with the given chain and state lookup
+    pub fn new(data_accesses: Arc<DataAccesses>, chain: Chain, state_lookup: StateLookup) -> Self {
+        Self { data_accesses, chain, state_lookup }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for EnvironmentRecorder {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let access = match interp.current_opcode() {
+            opcode::BASEFEE => EnvironmentAccess::BaseFee,
+            opcode::TIMESTAMP => EnvironmentAccess::Timestamp,
+            opcode::NUMBER => EnvironmentAccess::Number,
+            opcode::CHAINID => EnvironmentAccess::ChainId,
+            _ => return,
+        };
+        self.data_accesses.record(access.to_access(self.chain, self.state_lookup.clone()));
+    }
+}
+
+impl<DB: Database> InspectorExt<DB> for EnvironmentRecorder {}
+
+impl CreateFork {
+    /// Returns the [StateLookup] of the fork, offset by the given number of blocks.
+    ///
+    /// Forks pinned to a block resolve to [StateLookup::RollAt] the offset block, other forks
+    /// resolve to [StateLookup::RollN] relative to the latest block, e.g. an offset of `-5` is the
+    /// latest block minus 5.
+    ///
+    /// Errors if the offset block would be before genesis, or after the latest block.
+    pub fn to_state_lookup_with_offset(&self, offset: i64) -> eyre::Result<StateLookup> {
+        match self.evm_opts.fork_block_number {
+            Some(block_number) => block_number
+                .checked_add_signed(offset)
+                .map(StateLookup::RollAt)
+                .ok_or_else(|| eyre::eyre!("offset {offset} from block {block_number} is invalid")),
+            None if offset > 0 => {
+                eyre::bail!("offset {offset} is after the latest block")
+            }
+            None => Ok(StateLookup::RollN(offset)),
+        }
+    }
+}
+
+/// Statistics of a set of recorded accesses, e.g. for run reports.
+///
+/// Displays as a one-line summary, e.g. `47 accesses: 30 storage (12 contracts), 10 basic, 5
+/// code-by-hash, 2 block-hash across 3 blocks on chain 1`.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct AccessSummary {
+    /// The total number of accesses
+    pub total: usize,
+    /// The number of storage slot accesses
+    pub storage: usize,
+    /// The number of distinct contracts whose storage was accessed
+    pub storage_contracts: usize,
+    /// The number of basic account accesses
+    pub basic: usize,
+    /// The number of code by hash accesses
+    pub code_by_hash: usize,
+    /// The number of block hash accesses
+    pub block_hash: usize,
+    /// The number of account existence checks
+    pub exists: usize,
+    /// The number of fork creations
+    pub forks: usize,
+    /// The number of transient storage accesses
+    pub transient_storage: usize,
+    /// The number of environment reads
+    pub environment: usize,
+    /// The number of distinct blocks accessed, i.e. of distinct state lookups
+    pub blocks: usize,
+    /// The ids of the chains accessed
+    pub chains: BTreeSet<u64>,
+}
+
+impl AccessSummary {
+    /// Computes the summary of the given accesses, e.g. references into an access set
+    pub fn new<A: Deref<Target = Access>>(accesses: impl IntoIterator<Item = A>) -> Self {
+        let mut summary = Self::default();
+        let mut storage_contracts = HashSet::new();
+        let mut blocks = HashSet::new();
+
+        for access in accesses {
+            summary.total += 1;
+            match &access.access_type {
+                AccessType::RevmDbAccess(RevmDbAccess::Storage(address, _)) => {
+                    summary.storage += 1;
+                    storage_contracts.insert(*address);
+                }
+                AccessType::RevmDbAccess(RevmDbAccess::Basic(_)) => summary.basic += 1,
+                AccessType::RevmDbAccess(RevmDbAccess::CodeByHash(_)) => summary.code_by_hash += 1,
+                AccessType::RevmDbAccess(RevmDbAccess::BlockHash(_)) => summary.block_hash += 1,
+                AccessType::RevmDbAccess(RevmDbAccess::Exists(_)) => summary.exists += 1,
+                AccessType::CreateFork { .. } => summary.forks += 1,
+                AccessType::TransientStorage(_) => summary.transient_storage += 1,
+                AccessType::Environment(_) => summary.environment += 1,
+            }
+            blocks.insert((access.chain, access.state_lookup.clone()));
+            summary.chains.insert(access.chain.id());
+        }
+
+        summary.storage_contracts = storage_contracts.len();
+        summary.blocks = blocks.len();
+        summary
+    }
+}
+
+/// The accesses a warmup plans to load, i.e. those [`Backend::load_accesses`] loads with the given
+/// options.
+///
+/// [`Backend::load_accesses`]: crate::backend::Backend::load_accesses
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct AccessPlan {
+    /// The accesses to load, in order
+    pub accesses: Vec<Access>,
+}
+
+impl AccessPlan {
+    /// Plans loading the given accesses with the given options, skipping those whose type is not
+    /// allowed by the mask of the options
+    pub fn new<A: Deref<Target = Access>>(
+        accesses: impl IntoIterator<Item = A>,
+        options: &LoadAccessesOptions,
+    ) -> Self {
+        let accesses = accesses
+            .into_iter()
+            .filter(|access| options.mask.allows(&access.access_type))
+            .map(|access| Access::clone(&access))
+            .collect();
+        Self { accesses }
+    }
+
+    /// Returns the statistics of the planned accesses
+    pub fn summary(&self) -> AccessSummary {
+        AccessSummary::new(&self.accesses)
+    }
+
+    /// Estimates the cost of fetching the planned accesses from a provider in request units,
+    /// weighing each access by its type, e.g. to budget a warmup against the pricing of a provider.
+    ///
+    /// Every planned access is assumed to be fetched, so accesses that are already cached or left
+    /// over by a fetch budget make this an upper bound.
+    pub fn estimated_cost(&self, weights: &RequestUnitWeights) -> u64 {
+        self.accesses.iter().map(|access| weights.weight(&access.access_type)).sum()
+    }
+}
+
+/// The cost in request units of fetching an access of each type from a provider, see
+/// [`AccessPlan::estimated_cost`].
+///
+/// The defaults follow common compute unit pricing, where a basic account fetch costs the
+/// balance, nonce and code requests it takes, and code by hash is served from the fetched code.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct RequestUnitWeights {
+    /// The cost of a storage slot fetch, i.e. `eth_getStorageAt`
+    pub storage: u64,
+    /// The cost of a basic account fetch, i.e. `eth_getBalance`, `eth_getTransactionCount` and
+    /// `eth_getCode`
+    pub basic: u64,
+    /// The cost of a code by hash fetch
+    pub code_by_hash: u64,
+    /// The cost of a block hash fetch, i.e. `eth_getBlockByNumber`
+    pub block_hash: u64,
+    /// The cost of an account existence check, fetched like a basic account
+    pub exists: u64,
+}
+
+impl RequestUnitWeights {
+    /// Returns the cost of fetching an access of the given type.
+    ///
+    /// Accesses that are never fetched, i.e. fork creations, transient storage and environment
+    /// reads, are free.
+    pub fn weight(&self, access_type: &AccessType) -> u64 {
+        match access_type {
+            AccessType::RevmDbAccess(RevmDbAccess::Storage(..)) => self.storage,
+            AccessType::RevmDbAccess(RevmDbAccess::Basic(_)) => self.basic,
+            AccessType::RevmDbAccess(RevmDbAccess::CodeByHash(_)) => self.code_by_hash,
+            AccessType::RevmDbAccess(RevmDbAccess::BlockHash(_)) => self.block_hash,
+            AccessType::RevmDbAccess(RevmDbAccess::Exists(_)) => self.exists,
+            AccessType::CreateFork { .. } |
+            AccessType::TransientStorage(_) |
+            AccessType::Environment(_) => 0,
+        }
+    }
+}
+
+impl Default for RequestUnitWeights {
+    fn default() -> Self {
+        Self { storage: 17, basic: 71, code_by_hash: 0, block_hash: 16, exists: 71 }
+    }
+}
+
+impl fmt::Display for AccessSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize, singular: &'static str, plural: &'static str| {
+            if count == 1 {
+                singular
+            } else {
+                plural
+            }
+        };
+
+        write!(
+            f,
+            "{} {}: {} storage ({} {}), {} basic, {} code-by-hash, {} block-hash",
+            self.total,
+            plural(self.total, "access", "accesses"),
+            self.storage,
+            self.storage_contracts,
+            plural(self.storage_contracts, "contract", "contracts"),
+            self.basic,
+            self.code_by_hash,
+            self.block_hash,
+        )?;
+        if self.exists > 0 {
+            write!(f, ", {} exists", self.exists)?;
+        }
+        if self.forks > 0 {
+            write!(f, ", {} {}", self.forks, plural(self.forks, "fork", "forks"))?;
+        }
+        if self.transient_storage > 0 {
+            write!(f, ", {} transient storage", self.transient_storage)?;
+        }
+        if self.environment > 0 {
+            write!(f, ", {} environment", self.environment)?;
+        }
+        write!(f, " across {} {}", self.blocks, plural(self.blocks, "block", "blocks"))?;
+        if !self.chains.is_empty() {
+            let chains = self.chains.iter().map(u64::to_string).collect::<Vec<_>>().join(", ");
+            write!(f, " on {} {chains}", plural(self.chains.len(), "chain", "chains"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the accesses present in both access sets, e.g. the data dependencies shared by two runs.
+pub fn intersect(a: &HashSet<Access>, b: &HashSet<Access>) -> HashSet<Access> {
+    a.intersection(b).cloned().collect()
+}
+
+/// Returns the union of the given access sets, e.g. to combine the accesses recorded by the
+/// backends of several shards into a single warmup manifest.
+pub fn merge_accesses(sets: &[&HashSet<Access>]) -> HashSet<Access> {
+    sets.iter().flat_map(|set| set.iter().cloned()).collect()
+}
+
+/// Returns the accesses re-tagged for the given chain, e.g. to replay the same logical accesses
+/// on another network.
+///
+/// Everything but the chain, including the addresses and slots accessed, is preserved.
+pub fn retag_chain(accesses: &[Access], new_chain: Chain) -> Vec<Access> {
+    accesses.iter().map(|access| Access { chain: new_chain, ..access.clone() }).collect()
+}
+
+/// Returns the accesses with the ones differing only in their state lookup collapsed into the first
+/// of them, e.g. to analyze what was accessed regardless of when, see
+/// [`Access::access_key_ignoring_lookup`].
+///
+/// Unlike the full equality of accesses, which keeps the same location at different blocks apart
+/// for warmups, this collapses them. The order of the first occurrences is preserved.
+pub fn dedup_by_location(accesses: &[Access]) -> Vec<Access> {
+    let mut seen = HashSet::new();
+    accesses
+        .iter()
+        .filter(|access| seen.insert(access.access_key_ignoring_lookup()))
+        .cloned()
+        .collect()
+}
+
+/// Returns the accesses with the redundant ones removed, e.g. to shrink the accesses of a long run
+/// before warming a cache with them.
+///
+/// An access subsumes another if both have the same chain and access type and read the same
+/// effective block. Without a current block to resolve them against, the effective block of a state
+/// lookup is the lookup itself: the blocks of different lookups, e.g. `RollN(0)` and `RollN(5)`,
+/// can't be assumed to share state. Accesses that aren't
+/// [block sensitive](AccessType::is_block_sensitive) read the same value at any block, so they
+/// subsume each other regardless of their state lookup. See [`minimize_accesses_at`] to also
+/// collapse the lookups resolving to the same block.
+///
+/// The result doesn't depend on the order of the given accesses: it is sorted by chain, access type
+/// and state lookup, and of the accesses subsuming each other the one with the lowest state lookup
+/// is kept.
+pub fn minimize_accesses(accesses: &[Access]) -> Vec<Access> {
+    minimize_by_effective_block(accesses, StateLookup::clone)
+}
+
+/// Returns the accesses with the redundant ones removed like [`minimize_accesses`], with the state
+/// lookups resolved against the given current block.
+///
+/// The effective block of a [`StateLookup::RollN`] is the block at its offset from the current
+/// block, so e.g. `RollN(-5)` at block `100` subsumes `RollAt(95)`. The effective block of a
+/// [`StateLookup::RollTransaction`] is its transaction, as the state right before a transaction is
+/// not the state of any block.
+pub fn minimize_accesses_at(accesses: &[Access], current_block: u64) -> Vec<Access> {
+    minimize_by_effective_block(accesses, |state_lookup| match *state_lookup {
+        StateLookup::RollN(n) => StateLookup::RollAt(((current_block as i64) + n).max(0) as u64),
+        _ => state_lookup.clone(),
+    })
+}
+
+/// Keeps the first of the sorted accesses with the same chain, access type and, for block sensitive
+/// accesses, effective block, see [`minimize_accesses`]
+fn minimize_by_effective_block(
+    accesses: &[Access],
+    effective_block: impl Fn(&StateLookup) -> StateLookup,
+) -> Vec<Access> {
+    let mut sorted = accesses.iter().collect::<Vec<_>>();
+    sorted.sort_unstable_by(|a, b| {
+        (a.chain.id(), &a.access_type, &a.state_lookup).cmp(&(
+            b.chain.id(),
+            &b.access_type,
+            &b.state_lookup,
+        ))
+    });
+    let mut seen = HashSet::new();
+    sorted
+        .into_iter()
+        .filter(|access| {
+            let block = access
+                .access_type
+                .is_block_sensitive()
+                .then(|| effective_block(&access.state_lookup));
+            seen.insert((access.chain, &access.access_type, block))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Renders the number of accesses of each kind on each chain as a table, e.g. to see at a glance
+/// what a warmup will fetch, see [`AccessType::kind`].
+///
+/// Rows are sorted by kind and chain id.
+pub fn access_table(accesses: &[Access]) -> String {
+    let mut counts = BTreeMap::<(&str, u64), usize>::new();
+    for access in accesses {
+        *counts.entry((access.access_type.kind(), access.chain.id())).or_default() += 1;
+    }
+
+    let mut table = format!("{:<18} {:>10} {:>8}\n", "kind", "chain", "count");
+    for ((kind, chain), count) in counts {
+        table.push_str(&format!("{kind:<18} {chain:>10} {count:>8}\n"));
+    }
+    table
+}
+
+/// Writes the given accesses to the given path as a JSON array, e.g. the recorded accesses of a run
+/// to warm the cache of later runs with
+/// [`Backend::load_accesses`](crate::backend::Backend::load_accesses).
+///
+/// The file is gzip compressed if the path has a `.gz` extension.
+pub fn write_accesses(accesses: &[Access], path: &Path) -> eyre::Result<()> {
+    let mut writer = compression::create(path, compression::is_gz_path(path))
+        .wrap_err_with(|| format!("failed to create {}", path.display()))?;
+    serde_json::to_writer(&mut writer, accesses)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Reads the accesses written with [`write_accesses`] from the given path, decompressing them if
+/// they were written compressed
+pub fn read_accesses(path: &Path) -> eyre::Result<Vec<Access>> {
+    let reader =
+        compression::open(path).wrap_err_with(|| format!("failed to open {}", path.display()))?;
+    serde_json::from_reader(reader)
+        .wrap_err_with(|| format!("invalid accesses in {}", path.display()))
+}
+
+#[test]
+fn test_write_read_accesses() {
+    let address = Address::repeat_byte(1);
+    let accesses = vec![
+        RevmDbAccess::Storage(address, U256::from(255))
+            .to_access(Chain::mainnet(), StateLookup::RollN(-1)),
+        RevmDbAccess::Basic(address).to_access(Chain::mainnet(), StateLookup::RollAt(10)),
+        RevmDbAccess::CodeByHash(B256::repeat_byte(2))
+            .to_access(Chain::mainnet(), StateLookup::RollTransaction(B256::repeat_byte(3))),
+        RevmDbAccess::BlockHash(U256::from(9)).to_access(Chain::mainnet(), StateLookup::default()),
+        RevmDbAccess::Exists(address).to_access(Chain::optimism_mainnet(), StateLookup::default()),
+        Access {
+            access_type: AccessType::CreateFork {
+                url: "http://fake.com".to_string(),
+                env_adjustments: None,
+                block_number: None,
+            },
+            chain: Chain::mainnet(),
+            state_lookup: StateLookup::RollAt(10),
+        },
+        Access {
+            access_type: AccessType::CreateFork {
+                url: "http://fake.com".to_string(),
+                env_adjustments: Some(Box::new(ChainEnvAdjustments {
+                    chain_id: 1,
+                    block_number: U256::from(5),
+                    ..Default::default()
+                })),
+                block_number: None,
+            },
+            chain: Chain::mainnet(),
+            state_lookup: StateLookup::RollAt(10),
+        },
+        TransientStorageAccess::Load(address, U256::from(1))
+            .to_access(Chain::mainnet(), StateLookup::default()),
+        TransientStorageAccess::Store(address, U256::from(1))
+            .to_access(Chain::mainnet(), StateLookup::default()),
+        EnvironmentAccess::BaseFee.to_access(Chain::mainnet(), StateLookup::default()),
+        EnvironmentAccess::Timestamp.to_access(Chain::mainnet(), StateLookup::default()),
+        EnvironmentAccess::Number.to_access(Chain::mainnet(), StateLookup::default()),
+        EnvironmentAccess::ChainId.to_access(Chain::mainnet(), StateLookup::default()),
+    ];
+
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("accesses.json");
+    for path in [plain.clone(), dir.path().join("accesses.json.gz")] {
+        write_accesses(&accesses, &path).unwrap();
+        assert_eq!(read_accesses(&path).unwrap(), accesses);
+    }
+
+    // Numbers, hashes and addresses are hex strings
+    let json = std::fs::read_to_string(&plain).unwrap();
+    assert!(
+        json.contains(r#""Storage":["0x0101010101010101010101010101010101010101","0xff"]"#),
+        "{json}"
+    );
+    assert!(json.contains(&format!("{:?}", B256::repeat_byte(3))), "{json}");
+
+    let invalid = dir.path().join("accesses-invalid.json");
+    std::fs::write(&invalid, "[{}]").unwrap();
+    assert!(read_accesses(&invalid).is_err());
+}
+
 #[test]
 fn test_default_state_lookup() {
     assert_eq!(StateLookup::default(), StateLookup::RollN(0));
 }
 
+#[test]
+fn test_resolve_roll_transaction() {
+    use crate::backend::{resolve_state_lookup, transaction_fork_block};
+    use alloy_rpc_types::Transaction;
+    use std::collections::HashMap;
+
+    // The first and later transactions of a block are both built on the fork of the parent block,
+    // later ones with the preceding transactions replayed on top, see
+    // `test_replay_until_transaction`
+    let first =
+        Transaction { block_number: Some(100), transaction_index: Some(0), ..Default::default() };
+    let later =
+        Transaction { block_number: Some(100), transaction_index: Some(3), ..Default::default() };
+    assert_eq!(transaction_fork_block(&first, 200), 99);
+    assert_eq!(transaction_fork_block(&later, 200), 99);
+
+    // Pending transactions are built on the current block
+    assert_eq!(transaction_fork_block(&Transaction::default(), 200), 200);
+
+    let known = B256::repeat_byte(1);
+    let blocks = HashMap::from([(known, 99)]);
+    assert_eq!(
+        resolve_state_lookup(&StateLookup::RollTransaction(known), 200, &blocks).unwrap(),
+        99
+    );
+    assert_eq!(resolve_state_lookup(&StateLookup::RollN(-1), 200, &blocks).unwrap(), 199);
+
+    let unknown = B256::repeat_byte(2);
+    assert!(matches!(
+        resolve_state_lookup(&StateLookup::RollTransaction(unknown), 200, &blocks),
+        Err(DatabaseError::TransactionNotFound(tx_hash)) if tx_hash == unknown
+    ));
+}
+
+#[test]
+fn test_resolve_pinned_fork_block() {
+    use crate::backend::resolve_access_block;
+    use std::collections::HashMap;
+
+    let fork = |block_number| Access {
+        access_type: AccessType::CreateFork {
+            url: "http://fake.com".to_string(),
+            env_adjustments: None,
+            block_number,
+        },
+        chain: Chain::mainnet(),
+        state_lookup: StateLookup::RollN(0),
+    };
+    let blocks = HashMap::new();
+
+    // A fork created at "latest" replays at the block latest resolved to when it was recorded
+    assert_eq!(resolve_access_block(&fork(Some(150)), 200, &blocks).unwrap(), 150);
+    // Forks recorded without their block fall back to the state lookup
+    assert_eq!(resolve_access_block(&fork(None), 200, &blocks).unwrap(), 200);
+}
+
+#[test]
+fn test_fork_partitions() {
+    use crate::backend::fork_partitions;
+    use std::collections::HashMap;
+
+    let fork = |chain, url: &str, block_number, state_lookup| Access {
+        access_type: AccessType::CreateFork {
+            url: url.to_string(),
+            env_adjustments: None,
+            block_number,
+        },
+        chain,
+        state_lookup,
+    };
+    let storage = |chain| {
+        RevmDbAccess::Storage(Address::ZERO, U256::ZERO).to_access(chain, StateLookup::RollN(0))
+    };
+    let mut accesses = vec![
+        // The given url takes precedence for the given chain
+        fork(Chain::mainnet(), "http://other-mainnet.com", Some(10), StateLookup::RollN(0)),
+        storage(Chain::mainnet()),
+        // Forks are pinned to their resolved block, or to their state lookup's absolute block, and
+        // each fork of a chain is loaded from its own url
+        fork(Chain::optimism_mainnet(), "http://optimism.com", Some(120), StateLookup::RollN(0)),
+        fork(Chain::optimism_mainnet(), "http://optimism-2.com", Some(130), StateLookup::RollN(0)),
+        storage(Chain::optimism_mainnet()),
+        fork(Chain::base_mainnet(), "http://base.com", None, StateLookup::RollAt(50)),
+        // Chains without a recorded fork are left out
+        storage(Chain::from_id(42_161)),
+    ];
+
+    let partitions =
+        fork_partitions(&accesses, Chain::mainnet(), 1000, "http://mainnet.com").unwrap();
+    let partitions = partitions
+        .into_iter()
+        .map(|((chain, url), (block, accesses))| ((chain, url), (block, accesses.len())))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        partitions,
+        HashMap::from([
+            ((Chain::mainnet(), "http://mainnet.com".to_string()), (1000, 2)),
+            // The other accesses of a chain are loaded from its first fork
+            ((Chain::optimism_mainnet(), "http://optimism.com".to_string()), (120, 2)),
+            ((Chain::optimism_mainnet(), "http://optimism-2.com".to_string()), (130, 1)),
+            ((Chain::base_mainnet(), "http://base.com".to_string()), (50, 1)),
+        ])
+    );
+
+    // A relative fork without its block can't be resolved
+    accesses.push(fork(Chain::from_id(42_161), "http://arbitrum.com", None, StateLookup::RollN(0)));
+    let err = fork_partitions(&accesses, Chain::mainnet(), 1000, "http://mainnet.com").unwrap_err();
+    assert!(err.to_string().contains("http://arbitrum.com"), "{err}");
+}
+
+#[test]
+fn test_to_state_lookup_with_offset() {
+    use crate::opts::EvmOpts;
+    use revm::primitives::Env;
+
+    let mut fork = CreateFork {
+        enable_caching: false,
+        url: "http://fake.com".to_string(),
+        env: Env::default(),
+        evm_opts: EvmOpts::default(),
+    };
+
+    // Unpinned forks are relative to the latest block
+    assert_eq!(fork.to_state_lookup_with_offset(0).unwrap(), StateLookup::RollN(0));
+    assert_eq!(fork.to_state_lookup_with_offset(-5).unwrap(), StateLookup::RollN(-5));
+    assert!(fork.to_state_lookup_with_offset(1).is_err());
+
+    // Pinned forks are relative to the pinned block
+    fork.evm_opts.fork_block_number = Some(100);
+    assert_eq!(fork.to_state_lookup_with_offset(-5).unwrap(), StateLookup::RollAt(95));
+    assert_eq!(fork.to_state_lookup_with_offset(5).unwrap(), StateLookup::RollAt(105));
+    assert!(fork.to_state_lookup_with_offset(-101).is_err());
+}
+
+#[test]
+fn test_intersect() {
+    let storage = |slot: u64| {
+        RevmDbAccess::Storage(Address::ZERO, U256::from(slot))
+            .to_access(Chain::mainnet(), StateLookup::default())
+    };
+
+    let a = HashSet::from([storage(0), storage(1), storage(2)]);
+    let b = HashSet::from([storage(1), storage(2), storage(3)]);
+
+    assert_eq!(intersect(&a, &b), HashSet::from([storage(1), storage(2)]));
+    assert!(intersect(&a, &HashSet::new()).is_empty());
+}
+
+#[test]
+fn test_merge_accesses() {
+    let storage = |slot: u64| {
+        RevmDbAccess::Storage(Address::ZERO, U256::from(slot))
+            .to_access(Chain::mainnet(), StateLookup::default())
+    };
+
+    let a = HashSet::from([storage(0), storage(1)]);
+    let b = HashSet::from([storage(1), storage(2)]);
+    let c = HashSet::from([storage(0), storage(2), storage(3)]);
+
+    assert_eq!(
+        merge_accesses(&[&a, &b, &c]),
+        HashSet::from([storage(0), storage(1), storage(2), storage(3)])
+    );
+    assert_eq!(merge_accesses(&[&a]), a);
+    assert!(merge_accesses(&[]).is_empty());
+}
+
+#[test]
+fn test_dedup_by_location() {
+    let storage = |slot: u64, chain, state_lookup| {
+        RevmDbAccess::Storage(Address::ZERO, U256::from(slot)).to_access(chain, state_lookup)
+    };
+    let accesses = vec![
+        storage(0, Chain::mainnet(), StateLookup::RollAt(10)),
+        storage(1, Chain::mainnet(), StateLookup::RollAt(10)),
+        storage(0, Chain::mainnet(), StateLookup::RollN(-1)),
+        storage(0, Chain::optimism_mainnet(), StateLookup::RollAt(10)),
+    ];
+
+    // The same slot at another block collapses, the same slot on another chain doesn't
+    assert_eq!(accesses[0].access_key_ignoring_lookup(), accesses[2].access_key_ignoring_lookup());
+    assert_eq!(
+        dedup_by_location(&accesses),
+        vec![accesses[0].clone(), accesses[1].clone(), accesses[3].clone()]
+    );
+    assert!(dedup_by_location(&[]).is_empty());
+}
+
+#[test]
+fn test_display_access() {
+    let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+    let storage =
+        RevmDbAccess::Storage(weth, U256::ZERO).to_access(Chain::mainnet(), StateLookup::RollN(0));
+    assert_eq!(
+        storage.to_string(),
+        "storage 0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2[0x0] @ chain=1 roll=latest"
+    );
+
+    let block_hash = RevmDbAccess::BlockHash(U256::from(99))
+        .to_access(Chain::optimism_mainnet(), StateLookup::RollN(-2));
+    assert_eq!(block_hash.to_string(), "block-hash 99 @ chain=10 roll=latest-2");
+
+    let fork = Access {
+        access_type: AccessType::CreateFork {
+            url: "https://eth-mainnet.g.alchemy.com/v2/abcdefghijklmnopqrstuvwxyz123456"
+                .to_string(),
+            env_adjustments: None,
+            block_number: Some(20_000_000),
+        },
+        chain: Chain::mainnet(),
+        state_lookup: StateLookup::RollAt(20_000_000),
+    };
+    assert_eq!(
+        fork.to_string(),
+        "fork https://eth-mainnet.g.alchemy.com/v2/REDACTED at 20000000 @ chain=1 roll=20000000"
+    );
+    assert_eq!(
+        EnvironmentAccess::BaseFee
+            .to_access(Chain::mainnet(), StateLookup::RollTransaction(B256::ZERO))
+            .to_string(),
+        format!("environment basefee @ chain=1 roll=tx {}", B256::ZERO)
+    );
+
+    assert_eq!(
+        access_table(&[storage.clone(), storage, block_hash]),
+        "kind                    chain    count\n\
+         block-hash                 10        1\n\
+         storage                     1        2\n"
+    );
+}
+
+#[test]
+fn test_retag_chain() {
+    let accesses = vec![
+        RevmDbAccess::Storage(Address::repeat_byte(1), U256::from(2))
+            .to_access(Chain::mainnet(), StateLookup::RollAt(10)),
+        RevmDbAccess::Basic(Address::repeat_byte(3))
+            .to_access(Chain::mainnet(), StateLookup::RollN(-1)),
+        Access {
+            access_type: AccessType::CreateFork {
+                url: "http://fake.com".to_string(),
+                env_adjustments: None,
+                block_number: None,
+            },
+            chain: Chain::mainnet(),
+            state_lookup: StateLookup::default(),
+        },
+    ];
+
+    let retagged = retag_chain(&accesses, Chain::optimism_mainnet());
+
+    assert_eq!(retagged.len(), accesses.len());
+    for (original, retagged) in accesses.iter().zip(&retagged) {
+        assert_eq!(retagged.chain, Chain::optimism_mainnet());
+        assert_eq!(retagged.access_type, original.access_type);
+        assert_eq!(retagged.state_lookup, original.state_lookup);
+    }
+}
+
+#[test]
+fn test_data_accesses_sink() {
+    use std::sync::Mutex;
+
+    let accesses = DataAccesses::default();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = |received: Arc<Mutex<Vec<Access>>>| -> AccessSink {
+        Box::new(move |access| received.lock().unwrap().push(access))
+    };
+    let basic = |byte| {
+        RevmDbAccess::Basic(Address::repeat_byte(byte))
+            .to_access(Chain::mainnet(), StateLookup::default())
+    };
+
+    // Streamed to the sink only
+    accesses.set_sink(Some(sink(received.clone())), false);
+    accesses.record(basic(1));
+    accesses.record(basic(1));
+    assert_eq!(*received.lock().unwrap(), vec![basic(1), basic(1)]);
+    assert!(accesses.is_empty());
+
+    // Streamed to the sink and accumulated
+    accesses.set_sink(Some(sink(received.clone())), true);
+    accesses.record(basic(2));
+    assert_eq!(*received.lock().unwrap(), vec![basic(1), basic(1), basic(2)]);
+    assert!(accesses.contains(&basic(2)));
+
+    // Accumulated only
+    accesses.set_sink(None, false);
+    accesses.record(basic(3));
+    assert_eq!(received.lock().unwrap().len(), 3);
+    assert_eq!(accesses.len(), 2);
+}
+
+#[test]
+fn test_record_from_block() {
+    let accesses = DataAccesses::default();
+    let basic = |byte, state_lookup| {
+        RevmDbAccess::Basic(Address::repeat_byte(byte)).to_access(Chain::mainnet(), state_lookup)
+    };
+
+    accesses.set_record_from_block(Some(15));
+    accesses.record_at(basic(1, StateLookup::RollN(0)), 10);
+    accesses.record_at(basic(2, StateLookup::RollN(0)), 20);
+    accesses.record(basic(3, StateLookup::RollAt(14)));
+    accesses.record(basic(4, StateLookup::RollAt(15)));
+    assert_eq!(accesses.len(), 2);
+    assert!(accesses.contains(&basic(2, StateLookup::RollN(0))));
+    assert!(accesses.contains(&basic(4, StateLookup::RollAt(15))));
+
+    // Everything is recorded once the floor is removed
+    accesses.set_record_from_block(None);
+    accesses.record_at(basic(1, StateLookup::RollN(0)), 10);
+    assert_eq!(accesses.len(), 3);
+}
+
+#[test]
+fn test_access_recorder() {
+    /// Ignores basic accesses and counts the recorded storage accesses
+    struct StorageRecorder(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl AccessRecorder for StorageRecorder {
+        fn record_basic(&self, _: &dashmap::DashSet<Access>, _: Access) {}
+
+        fn record_storage(&self, accesses: &dashmap::DashSet<Access>, access: Access) {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            accesses.insert(access);
+        }
+    }
+
+    let chain = Chain::mainnet();
+    let basic =
+        RevmDbAccess::Basic(Address::repeat_byte(1)).to_access(chain, StateLookup::RollN(0));
+    let storage = RevmDbAccess::Storage(Address::repeat_byte(1), U256::from(1))
+        .to_access(chain, StateLookup::RollN(0));
+    let fork = Access {
+        access_type: AccessType::CreateFork {
+            url: "http://localhost:8545".to_string(),
+            env_adjustments: None,
+            block_number: None,
+        },
+        chain,
+        state_lookup: StateLookup::RollN(0),
+    };
+
+    let accesses = DataAccesses::default();
+    let recorded_storage = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    accesses.set_recorder(Box::new(StorageRecorder(recorded_storage.clone())));
+    for access in [&basic, &storage, &fork] {
+        accesses.record(access.clone());
+    }
+    assert_eq!(accesses.len(), 2);
+    assert!(!accesses.contains(&basic));
+    assert!(accesses.contains(&storage));
+    assert!(accesses.contains(&fork));
+    assert_eq!(recorded_storage.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+    // The default recorder records every category
+    accesses.set_recorder(Box::new(DefaultAccessRecorder));
+    accesses.record(basic.clone());
+    assert!(accesses.contains(&basic));
+}
+
+#[test]
+fn test_access_summary() {
+    let chain = Chain::mainnet();
+    let mut accesses = Vec::new();
+    for (contract, slot) in [(1, 0), (1, 1), (2, 0)] {
+        accesses.push(
+            RevmDbAccess::Storage(Address::repeat_byte(contract), U256::from(slot))
+                .to_access(chain, StateLookup::RollAt(10)),
+        );
+    }
+    accesses.push(
+        RevmDbAccess::Basic(Address::repeat_byte(1)).to_access(chain, StateLookup::RollAt(10)),
+    );
+    accesses.push(
+        RevmDbAccess::Basic(Address::repeat_byte(1)).to_access(chain, StateLookup::RollAt(11)),
+    );
+    accesses.push(RevmDbAccess::CodeByHash(B256::ZERO).to_access(chain, StateLookup::RollAt(10)));
+    accesses.push(RevmDbAccess::BlockHash(U256::from(9)).to_access(chain, StateLookup::RollN(0)));
+
+    let summary = AccessSummary::new(&accesses);
+    assert_eq!(
+        summary,
+        AccessSummary {
+            total: 7,
+            storage: 3,
+            storage_contracts: 2,
+            basic: 2,
+            code_by_hash: 1,
+            block_hash: 1,
+            exists: 0,
+            forks: 0,
+            transient_storage: 0,
+            environment: 0,
+            blocks: 3,
+            chains: BTreeSet::from([1]),
+        }
+    );
+    assert_eq!(
+        summary.to_string(),
+        "7 accesses: 3 storage (2 contracts), 2 basic, 1 code-by-hash, 1 block-hash across 3 blocks on chain 1"
+    );
+
+    accesses.push(
+        TransientStorageAccess::Load(Address::ZERO, U256::ZERO)
+            .to_access(Chain::optimism_mainnet(), StateLookup::RollN(0)),
+    );
+    assert_eq!(
+        AccessSummary::new(&accesses).to_string(),
+        "8 accesses: 3 storage (2 contracts), 2 basic, 1 code-by-hash, 1 block-hash, 1 transient storage across 4 blocks on chains 1, 10"
+    );
+    assert_eq!(
+        AccessSummary::default().to_string(),
+        "0 accesses: 0 storage (0 contracts), 0 basic, 0 code-by-hash, 0 block-hash across 0 blocks"
+    );
+}
+
+#[test]
+fn test_access_plan_estimated_cost() {
+    let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+    let mut accesses = [
+        RevmDbAccess::Storage(weth, U256::ZERO),
+        RevmDbAccess::Storage(weth, U256::from(1)),
+        RevmDbAccess::Storage(weth, U256::from(2)),
+        RevmDbAccess::Basic(weth),
+        RevmDbAccess::CodeByHash(B256::repeat_byte(1)),
+        RevmDbAccess::BlockHash(U256::from(5)),
+        RevmDbAccess::Exists(Address::ZERO),
+    ]
+    .map(|access| access.to_access(Chain::default(), StateLookup::RollN(0)))
+    .to_vec();
+    accesses.push(EnvironmentAccess::ChainId.to_access(Chain::default(), StateLookup::RollN(0)));
+    let weights =
+        RequestUnitWeights { storage: 10, basic: 30, code_by_hash: 5, block_hash: 7, exists: 2 };
+
+    let plan = AccessPlan::new(&accesses, &LoadAccessesOptions::default());
+    assert_eq!(plan.summary().total, 8);
+    assert_eq!(plan.estimated_cost(&weights), 3 * 10 + 30 + 5 + 7 + 2);
+    assert_eq!(plan.estimated_cost(&Default::default()), 3 * 17 + 71 + 16 + 71);
+    assert_eq!(AccessPlan::default().estimated_cost(&weights), 0);
+
+    // Accesses skipped by the mask are not planned
+    let mask = AccessTypeMask { storage: false, block_hash: false, ..Default::default() };
+    let plan = AccessPlan::new(&accesses, &LoadAccessesOptions { mask, ..Default::default() });
+    assert_eq!(plan.summary().storage, 0);
+    assert_eq!(plan.estimated_cost(&weights), 30 + 5 + 2);
+}
+
+#[test]
+fn test_chain_env_adjustments() {
+    use crate::utils::apply_chain_and_block_specific_env_changes;
+    use alloy_chains::NamedChain;
+    use alloy_rpc_types::Block;
+
+    // On Arbitrum, `block.number` is the L1 block number and the difficulty is the prevrandao
+    let mut block = Block::default();
+    block.header.number = Some(200_000_000);
+    block.other.insert("l1BlockNumber".to_string(), serde_json::json!("0x12d687"));
+
+    let unadjusted_env = {
+        let mut env = Env::default();
+        env.cfg.chain_id = Chain::from_named(NamedChain::Arbitrum).id();
+        env.block.number = U256::from(200_000_000);
+        env.block.difficulty = U256::from(1);
+        env.block.prevrandao = Some(B256::repeat_byte(0x11));
+        env
+    };
+    let mut env = unadjusted_env.clone();
+    apply_chain_and_block_specific_env_changes(&mut env, &block);
+    assert_eq!(env.block.number, U256::from(1_234_567));
+
+    let access = Access {
+        access_type: AccessType::CreateFork {
+            url: "http://fake.com".to_string(),
+            env_adjustments: Some(Box::new(ChainEnvAdjustments::from_env(&env))),
+            block_number: None,
+        },
+        chain: Chain::from_named(NamedChain::Arbitrum),
+        state_lookup: StateLookup::RollAt(200_000_000),
+    };
+
+    // The recorded access reconstructs the adjusted env, without the block
+    let access: Access = serde_json::from_str(&serde_json::to_string(&access).unwrap()).unwrap();
+    let AccessType::CreateFork { env_adjustments: Some(adjustments), .. } = access.access_type
+    else {
+        panic!("expected recorded env adjustments")
+    };
+    let mut replayed_env = unadjusted_env;
+    adjustments.apply(&mut replayed_env);
+    assert_eq!(replayed_env.block, env.block);
+}
+
+#[test]
+fn test_replay_applies_recorded_env() {
+    use crate::{backend::Backend, fork::ForkId, mock_transport::node_transport};
+
+    // The mocked node serves another env for the block than the one the fork was recorded with
+    let url = node_transport(1, 100).serve();
+    let recorded = ChainEnvAdjustments {
+        chain_id: 1,
+        block_number: U256::from(50),
+        timestamp: U256::from(1_700_000_000),
+        coinbase: Address::repeat_byte(0xc0),
+        basefee: U256::from(7),
+        difficulty: U256::ZERO,
+        prevrandao: Some(B256::repeat_byte(0x11)),
+        gas_limit: U256::from(15_000_000),
+        gas_price: U256::from(9),
+    };
+    let access = Access {
+        access_type: AccessType::CreateFork {
+            url: url.clone(),
+            env_adjustments: Some(Box::new(recorded.clone())),
+            block_number: Some(50),
+        },
+        chain: Chain::mainnet(),
+        state_lookup: StateLookup::RollAt(50),
+    };
+
+    let backend = Backend::spawn(None);
+    let outcome = backend
+        .load_accesses(
+            &[access],
+            Chain::mainnet(),
+            100,
+            url.clone(),
+            LoadAccessesOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(outcome, LoadAccessesOutcome::Loaded);
+
+    let env = backend.forks.get_env(ForkId::new(&url, 50)).unwrap().unwrap();
+    assert_eq!(ChainEnvAdjustments::from_env(&env), recorded);
+}
+
+#[test]
+fn test_transient_storage_recorder() {
+    use crate::backend::Backend;
+    use revm::{
+        db::{CacheDB, EmptyDB},
+        primitives::{AccountInfo, Bytecode, SpecId, TxKind},
+    };
+
+    // PUSH1 0x2a PUSH1 0x01 TSTORE PUSH1 0x01 TLOAD POP PUSH1 0x02 SLOAD POP STOP
+    let code = Bytecode::new_raw(
+        [0x60, 0x2a, 0x60, 0x01, 0x5d, 0x60, 0x01, 0x5c, 0x50, 0x60, 0x02, 0x54, 0x50, 0x00].into(),
+    );
+    let target = Address::repeat_byte(0x42);
+    let mut db = CacheDB::new(EmptyDB::default());
+    db.insert_account_info(
+        target,
+        AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+    );
+
+    let backend = Backend::spawn(None);
+    let mut evm = revm::Evm::builder()
+        .with_db(db)
+        .with_external_context(
+            backend.transient_storage_recorder(Chain::mainnet(), StateLookup::default()),
+        )
+        .with_spec_id(SpecId::CANCUN)
+        .modify_tx_env(|tx| tx.transact_to = TxKind::Call(target))
+        .append_handler_register(revm::inspector_handle_register)
+        .build();
+    assert!(evm.transact().unwrap().result.is_success());
+
+    let load = TransientStorageAccess::Load(target, U256::from(1))
+        .to_access(Chain::mainnet(), StateLookup::default());
+    let store = TransientStorageAccess::Store(target, U256::from(1))
+        .to_access(Chain::mainnet(), StateLookup::default());
+    let mut recorded = backend.transient_storage_accesses();
+    recorded.sort_by_key(|access| {
+        matches!(
+            access.access_type,
+            AccessType::TransientStorage(TransientStorageAccess::Store(..))
+        )
+    });
+    assert_eq!(recorded, vec![load.clone(), store]);
+
+    // The persistent storage read is not recorded as a transient storage access
+    assert!(backend.storage_accesses().is_empty());
+
+    // Transient storage accesses are never fetched from a remote
+    let mut backend = backend;
+    assert!(backend.execute_access(&load, 0, "http://fake.com").is_ok());
+}
+
+#[test]
+fn test_environment_recorder() {
+    use crate::backend::Backend;
+    use revm::{
+        db::{CacheDB, EmptyDB},
+        primitives::{AccountInfo, Bytecode, TxKind},
+    };
+
+    // BASEFEE POP STOP
+    let code = Bytecode::new_raw([0x48, 0x50, 0x00].into());
+    let target = Address::repeat_byte(0x42);
+    let mut db = CacheDB::new(EmptyDB::default());
+    db.insert_account_info(
+        target,
+        AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+    );
+
+    let backend = Backend::spawn(None);
+    let mut evm = revm::Evm::builder()
+        .with_db(db)
+        .with_external_context(
+            backend.environment_recorder(Chain::mainnet(), StateLookup::default()),
+        )
+        .modify_tx_env(|tx| tx.transact_to = TxKind::Call(target))
+        .append_handler_register(revm::inspector_handle_register)
+        .build();
+    assert!(evm.transact().unwrap().result.is_success());
+
+    let base_fee = EnvironmentAccess::BaseFee.to_access(Chain::mainnet(), StateLookup::default());
+    assert_eq!(backend.environment_accesses(), vec![base_fee.clone()]);
+    assert_eq!(backend.access_summary().environment, 1);
+
+    // The environment is never fetched from a remote
+    let mut backend = backend;
+    assert!(backend.execute_access(&base_fee, 0, "http://fake.com").is_ok());
+}
+
+#[test]
+fn test_load_accesses_fetch_budget() {
+    use crate::{backend::Backend, mock_transport::node_transport};
+
+    let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+    let block = 20_000_000;
+    let url = node_transport(1, block).serve();
+    let data_accesses = (0..10u64)
+        .map(|slot| {
+            RevmDbAccess::Storage(weth, U256::from(slot))
+                .to_access(Chain::default(), StateLookup::RollAt(block))
+        })
+        .collect::<Vec<_>>();
+
+    let db = Backend::spawn(None);
+    let load = |max_fetches| {
+        db.load_accesses(
+            &data_accesses,
+            Chain::default(),
+            block,
+            url.clone(),
+            LoadAccessesOptions { max_fetches: Some(max_fetches), ..Default::default() },
+        )
+        .unwrap()
+    };
+
+    let LoadAccessesOutcome::BudgetExhausted { remaining } = load(3) else {
+        panic!("expected the fetch budget to be exhausted")
+    };
+    assert_eq!(remaining.len(), 7);
+    assert!(remaining.iter().all(|access| data_accesses.contains(access)));
+
+    // Cached accesses don't count against the budget
+    let LoadAccessesOutcome::BudgetExhausted { remaining } = load(3) else {
+        panic!("expected the fetch budget to be exhausted")
+    };
+    assert_eq!(remaining.len(), 4);
+
+    assert_eq!(load(10), LoadAccessesOutcome::Loaded);
+}
+
+#[test]
+fn test_load_accesses_type_mask() {
+    use crate::{backend::Backend, fork::ForkId, mock_transport::node_transport};
+
+    let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+    let block = 20_000_000;
+    let url = node_transport(1, block).serve();
+    let storage = RevmDbAccess::Storage(weth, U256::ZERO);
+    let basic = RevmDbAccess::Basic(weth);
+    let block_hash = RevmDbAccess::BlockHash(U256::from(block - 1));
+    let data_accesses = [storage.clone(), basic.clone(), block_hash.clone()]
+        .into_iter()
+        .map(|access| access.to_access(Chain::default(), StateLookup::RollAt(block)))
+        .collect::<Vec<_>>();
+
+    let db = Backend::spawn(None);
+    let mask = AccessTypeMask { block_hash: false, ..Default::default() };
+    assert_eq!(
+        db.load_accesses(
+            &data_accesses,
+            Chain::default(),
+            block,
+            url.clone(),
+            LoadAccessesOptions { mask, ..Default::default() },
+        )
+        .unwrap(),
+        LoadAccessesOutcome::Loaded
+    );
+
+    let fork = db.forks.get_fork(ForkId::new(&url, block)).unwrap().unwrap();
+    assert!(fork.is_cached(&storage));
+    assert!(fork.is_cached(&basic));
+    assert!(!fork.is_cached(&block_hash));
+}
+
+#[test]
+fn test_load_accesses_sequential_and_parallel() {
+    use crate::{backend::Backend, fork::ForkId, mock_transport::node_transport};
+
+    let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+    let blocks = [20_000_000, 20_000_001];
+    let url = node_transport(1, blocks[1]).serve();
+    let data_accesses = blocks
+        .into_iter()
+        .flat_map(|block| {
+            (0..10u64)
+                .map(|slot| RevmDbAccess::Storage(weth, U256::from(slot)))
+                .chain([RevmDbAccess::Basic(weth)])
+                .map(move |access| access.to_access(Chain::default(), StateLookup::RollAt(block)))
+        })
+        .collect::<Vec<_>>();
+
+    let load = |max_concurrency| {
+        let db = Backend::spawn(None);
+        let outcome = db
+            .load_accesses(
+                &data_accesses,
+                Chain::default(),
+                blocks[1],
+                url.clone(),
+                LoadAccessesOptions {
+                    max_concurrency: Some(max_concurrency),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(outcome, LoadAccessesOutcome::Loaded);
+        db
+    };
+    let (sequential, parallel) = (load(1), load(8));
+
+    for block in blocks {
+        let fork_id = ForkId::new(&url, block);
+        let sequential = sequential.forks.get_fork(fork_id.clone()).unwrap().unwrap();
+        let parallel = parallel.forks.get_fork(fork_id).unwrap().unwrap();
+        for slot in 0..10u64 {
+            let slot = U256::from(slot);
+            assert!(parallel.is_cached(&RevmDbAccess::Storage(weth, slot)));
+            assert_eq!(
+                sequential.storage_ref(weth, slot).unwrap(),
+                parallel.storage_ref(weth, slot).unwrap()
+            );
+        }
+        assert!(parallel.is_cached(&RevmDbAccess::Basic(weth)));
+        assert_eq!(sequential.basic_ref(weth).unwrap(), parallel.basic_ref(weth).unwrap());
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::{
-        backend::{Backend, DatabaseExt},
+        backend::{Backend, DatabaseExt, RetryPolicy},
         fork::CreateFork,
         opts::EvmOpts,
     };
@@ -113,11 +1878,16 @@ mod test {
     async fn test_create_fork_latest() {
         let db = get_forked_db(None);
 
-        db.data_accesses.contains(&Access {
-            access_type: AccessType::CreateFork(ENDPOINT.to_string()),
-            chain: Chain::default(),
-            state_lookup: StateLookup::RollN(0),
-        });
+        let forks = db.fork_accesses();
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].state_lookup, StateLookup::RollN(0));
+        // The fork is pinned to the block "latest" resolved to when it was created
+        let AccessType::CreateFork { url, block_number: Some(block), .. } = &forks[0].access_type
+        else {
+            panic!("unexpected access {:?}", forks[0]);
+        };
+        assert_eq!(url, ENDPOINT);
+        assert!(*block > 0);
     }
 
     #[test]
@@ -132,11 +1902,13 @@ mod test {
 
         db.create_fork(create_fork).unwrap();
 
-        db.data_accesses.contains(&Access {
-            access_type: AccessType::CreateFork(ENDPOINT.to_string()),
-            chain: Chain::default(),
-            state_lookup: StateLookup::RollAt(1),
-        });
+        let forks = db.fork_accesses();
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].state_lookup, StateLookup::RollAt(1));
+        assert!(matches!(
+            &forks[0].access_type,
+            AccessType::CreateFork { url, block_number: Some(1), .. } if url == ENDPOINT
+        ));
     }
     #[test]
     fn test_basic_ref() {
@@ -155,13 +1927,158 @@ mod test {
         assert_eq!(db.get_accesses(), vec![expected_access]);
     }
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
-    async fn test_load_state() {
+    #[test]
+    fn test_access_sink() {
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+        let db = get_forked_db(None);
+
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        db.set_access_sink(
+            Some(Box::new({
+                let received = received.clone();
+                move |access| received.lock().unwrap().push(access)
+            })),
+            false,
+        );
+
+        let _ = db.basic_ref(weth).unwrap();
+
+        let expected_access =
+            RevmDbAccess::Basic(weth).to_access(Chain::default(), StateLookup::RollN(0));
+        assert_eq!(*received.lock().unwrap(), vec![expected_access.clone()]);
+
+        // Not accumulated in the backend
+        assert!(!db.get_accesses().contains(&expected_access));
+    }
+
+    #[test]
+    fn test_block_hash_value_recorded() {
+        let db = get_forked_db(None);
+        let number = U256::from(1_000_000);
+
+        let hash = db.block_hash_ref(number).unwrap();
+
+        let access =
+            RevmDbAccess::BlockHash(number).to_access(Chain::default(), StateLookup::RollN(0));
+        assert_eq!(db.recorded_block_hash(&access), Some(hash));
+    }
+
+    #[test]
+    fn test_accesses_of() {
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+        let db = Backend::spawn(None);
+
+        let storage = RevmDbAccess::Storage(weth, U256::ZERO)
+            .to_access(Chain::default(), StateLookup::RollN(0));
+        let code =
+            RevmDbAccess::CodeByHash(B256::ZERO).to_access(Chain::default(), StateLookup::RollN(0));
+        let basic = RevmDbAccess::Basic(weth).to_access(Chain::default(), StateLookup::RollN(0));
+        let fork = Access {
+            access_type: AccessType::CreateFork {
+                url: ENDPOINT.to_string(),
+                env_adjustments: None,
+                block_number: None,
+            },
+            chain: Chain::default(),
+            state_lookup: StateLookup::RollAt(1),
+        };
+
+        for access in [&storage, &code, &basic, &fork] {
+            db.data_accesses.insert(access.clone());
+        }
+
+        assert_eq!(db.storage_accesses(), vec![storage]);
+        assert_eq!(db.code_accesses(), vec![code]);
+        assert_eq!(db.fork_accesses(), vec![fork]);
+        assert_eq!(
+            db.accesses_of(|access_type| {
+                matches!(access_type, AccessType::RevmDbAccess(RevmDbAccess::Basic(_)))
+            }),
+            vec![basic]
+        );
+
+        // Filtering does not clear the recorded accesses
+        assert_eq!(db.get_accesses().len(), 4);
+    }
+
+    #[test]
+    fn test_touched_contracts() {
+        let db = Backend::spawn(None);
+        let address = Address::repeat_byte;
+        for access in [
+            RevmDbAccess::Basic(address(1)),
+            RevmDbAccess::Storage(address(1), U256::ZERO),
+            RevmDbAccess::Storage(address(2), U256::ZERO),
+            RevmDbAccess::Storage(address(2), U256::from(1)),
+            RevmDbAccess::Exists(address(3)),
+            RevmDbAccess::CodeByHash(B256::repeat_byte(4)),
+            RevmDbAccess::BlockHash(U256::from(5)),
+        ] {
+            db.data_accesses.insert(access.to_access(Chain::default(), StateLookup::RollN(0)));
+        }
+        db.data_accesses.insert(
+            TransientStorageAccess::Load(address(6), U256::ZERO)
+                .to_access(Chain::default(), StateLookup::RollN(0)),
+        );
+
+        assert_eq!(db.touched_contracts(), HashSet::from([address(1), address(2), address(3)]));
+        assert!(Backend::spawn(None).touched_contracts().is_empty());
+    }
+
+    #[test]
+    fn test_block_sensitive_accesses() {
+        let db = Backend::spawn(None);
         let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+        let storage = RevmDbAccess::Storage(weth, U256::ZERO)
+            .to_access(Chain::default(), StateLookup::RollN(0));
+        let basic = RevmDbAccess::Basic(weth).to_access(Chain::default(), StateLookup::RollN(0));
+        for access in [
+            storage.clone(),
+            basic.clone(),
+            RevmDbAccess::CodeByHash(B256::repeat_byte(1))
+                .to_access(Chain::default(), StateLookup::RollN(0)),
+            RevmDbAccess::BlockHash(U256::from(5))
+                .to_access(Chain::default(), StateLookup::RollN(0)),
+            EnvironmentAccess::ChainId.to_access(Chain::default(), StateLookup::RollN(0)),
+        ] {
+            db.data_accesses.insert(access);
+        }
+
+        let sensitive = db.block_sensitive_accesses().into_iter().collect::<HashSet<_>>();
+        assert_eq!(sensitive, HashSet::from([storage, basic]));
+        assert!(AccessType::Environment(EnvironmentAccess::Timestamp).is_block_sensitive());
+    }
+
+    #[test]
+    fn test_accesses_iter() {
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+        let db = Backend::spawn(None);
+
+        for slot in 0..10u64 {
+            db.data_accesses.insert(
+                RevmDbAccess::Storage(weth, U256::from(slot))
+                    .to_access(Chain::default(), StateLookup::RollN(0)),
+            );
+        }
 
-        let data_accesses = vec![
+        // The iterator borrows the recorded accesses rather than cloning them
+        let recorded: HashSet<*const Access> =
+            db.data_accesses.iter().map(|access| access.key() as *const Access).collect();
+        let iterated: HashSet<*const Access> =
+            db.accesses_iter().map(|access| access.key() as *const Access).collect();
+        assert_eq!(iterated, recorded);
+
+        let iterated: HashSet<Access> = db.accesses_iter().map(|access| access.clone()).collect();
+        let accesses: HashSet<Access> = db.get_accesses().into_iter().collect();
+        assert_eq!(iterated, accesses);
+    }
+
+    /// Reads of WETH's account and first storage slot at different blocks
+    fn weth_accesses() -> Vec<Access> {
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+        vec![
             Access {
-                access_type: AccessType::RevmDbAccess(RevmDbAccess::Basic(weth.clone())),
+                access_type: AccessType::RevmDbAccess(RevmDbAccess::Basic(weth)),
                 chain: Chain::default(),
                 state_lookup: StateLookup::RollN(0),
             },
@@ -180,7 +2097,45 @@ mod test {
                 chain: Chain::default(),
                 state_lookup: StateLookup::RollAt(10_000_000),
             },
-        ];
+        ]
+    }
+
+    #[test]
+    fn test_minimize_accesses() {
+        let accesses = weth_accesses();
+
+        // Every access of the fixture reads a distinct location or block
+        let minimized = minimize_accesses(&accesses);
+        assert_eq!(minimized.len(), 4);
+
+        // Duplicates are removed, regardless of the order
+        let mut duplicated = accesses.iter().chain(&accesses).rev().cloned().collect::<Vec<_>>();
+        assert_eq!(minimize_accesses(&duplicated), minimized);
+        duplicated.rotate_left(3);
+        assert_eq!(minimize_accesses(&duplicated), minimized);
+
+        // Resolved against block 10_000_000, the account reads of the latest block and of block
+        // 10_000_000 read the same block, and the latter is subsumed
+        let minimized = minimize_accesses_at(&accesses, 10_000_000);
+        assert_eq!(minimized.len(), 3);
+        assert!(!minimized.contains(&accesses[3]));
+        assert_eq!(minimize_accesses_at(&accesses, 10_000_001).len(), 4);
+
+        // Block insensitive accesses collapse into the lowest lookup
+        let code = |state_lookup| {
+            RevmDbAccess::CodeByHash(B256::repeat_byte(1)).to_access(Chain::default(), state_lookup)
+        };
+        let with_code =
+            [code(StateLookup::RollAt(10)), accesses[0].clone(), code(StateLookup::RollN(5))];
+        let minimized = minimize_accesses(&with_code);
+        assert_eq!(minimized.len(), 2);
+        assert!(minimized.contains(&code(StateLookup::RollN(5))));
+        assert!(minimize_accesses(&[]).is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_load_state() {
+        let data_accesses = weth_accesses();
 
         let db = get_forked_db(None);
 
@@ -189,11 +2144,103 @@ mod test {
         let run = |label: &str| {
             println!("run {label}");
             let now = std::time::Instant::now();
-            db.load_accesses(&data_accesses, Chain::default(), 69, ENDPOINT.to_string()).unwrap();
+            db.load_accesses(
+                &data_accesses,
+                Chain::default(),
+                69,
+                ENDPOINT.to_string(),
+                LoadAccessesOptions::default(),
+            )
+            .unwrap();
             println!("{}: {:?}", label, now.elapsed());
         };
 
         run("a");
         run("b");
     }
+
+    #[test]
+    fn test_replay_until_transaction() {
+        use alloy_rpc_types::BlockTransactions;
+
+        let block = 20_000_000;
+        let db = Backend::spawn(None);
+        let full_block =
+            db.get_or_create_fork(ENDPOINT, block).unwrap().get_full_block(block).unwrap();
+        let BlockTransactions::Full(txs) = full_block.transactions else {
+            panic!("expected full transactions");
+        };
+        let tx = &txs[3];
+
+        let prestate =
+            db.replay_until_transaction(tx, block, ENDPOINT, RetryPolicy::default()).unwrap();
+        assert_eq!(prestate.fork_block, block - 1);
+
+        // The preceding transactions of the block paid their fees to the fee recipient on top of
+        // the state of the parent block
+        let coinbase = full_block.header.miner;
+        let parent = prestate.db.db.basic_ref(coinbase).unwrap().unwrap_or_default();
+        let replayed = prestate.db.basic_ref(coinbase).unwrap().unwrap_or_default();
+        assert!(replayed.balance > parent.balance);
+        assert_eq!(prestate.db.basic_ref(tx.from).unwrap().unwrap().nonce, tx.nonce);
+    }
+
+    #[test]
+    fn test_load_accesses_multiple_chains() {
+        const OPTIMISM_ENDPOINT: &str = "https://mainnet.optimism.io";
+        let weth = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse::<Address>().unwrap();
+        let optimism_weth =
+            "0x4200000000000000000000000000000000000006".parse::<Address>().unwrap();
+        let (block, optimism_block) = (20_000_000, 120_000_000);
+
+        let accesses = vec![
+            RevmDbAccess::Basic(weth).to_access(Chain::mainnet(), StateLookup::RollN(0)),
+            Access {
+                access_type: AccessType::CreateFork {
+                    url: OPTIMISM_ENDPOINT.to_string(),
+                    env_adjustments: None,
+                    block_number: Some(optimism_block),
+                },
+                chain: Chain::optimism_mainnet(),
+                state_lookup: StateLookup::RollN(0),
+            },
+            RevmDbAccess::Basic(optimism_weth)
+                .to_access(Chain::optimism_mainnet(), StateLookup::RollN(0)),
+            RevmDbAccess::Storage(optimism_weth, U256::ZERO)
+                .to_access(Chain::optimism_mainnet(), StateLookup::RollN(0)),
+        ];
+
+        let db = Backend::spawn(None);
+        let outcome = db
+            .load_accesses(
+                &accesses,
+                Chain::mainnet(),
+                block,
+                ENDPOINT.to_string(),
+                LoadAccessesOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(outcome, LoadAccessesOutcome::Loaded);
+
+        // Each chain's accesses were loaded from its own fork, and no fork was created for the
+        // other chain's accesses
+        let mainnet =
+            db.forks.get_fork(crate::fork::ForkId::new(ENDPOINT, block)).unwrap().unwrap();
+        assert!(mainnet.is_cached(&RevmDbAccess::Basic(weth)));
+        assert!(!mainnet.is_cached(&RevmDbAccess::Basic(optimism_weth)));
+
+        let optimism = db
+            .forks
+            .get_fork(crate::fork::ForkId::new(OPTIMISM_ENDPOINT, optimism_block))
+            .unwrap()
+            .unwrap();
+        assert!(optimism.is_cached(&RevmDbAccess::Basic(optimism_weth)));
+        assert!(optimism.is_cached(&RevmDbAccess::Storage(optimism_weth, U256::ZERO)));
+        assert!(!optimism.is_cached(&RevmDbAccess::Basic(weth)));
+        assert!(db
+            .forks
+            .get_fork(crate::fork::ForkId::new(ENDPOINT, optimism_block))
+            .unwrap()
+            .is_none());
+    }
 }
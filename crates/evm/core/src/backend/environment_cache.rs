@@ -1,39 +1,518 @@
+use crate::backend::{
+    cache_stats::{CacheCounters, EvictionLifecycle},
+    compression, CacheStats,
+};
+use alloy_primitives::B256;
 use alloy_provider::{Network, Provider};
-use alloy_rpc_types::{Block, BlockNumberOrTag};
+use alloy_rpc_types::{
+    Block, BlockNumberOrTag, BlockTransactions, BlockTransactionsKind, Transaction, Withdrawal,
+};
 use alloy_transport::Transport;
-use dashmap::DashMap;
-use quick_cache::sync::Cache;
+use dashmap::{DashMap, DashSet};
+use eyre::WrapErr;
+use futures::{stream, StreamExt};
+use quick_cache::{sync::Cache, DefaultHashBuilder, Weighter};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    mem::size_of,
+    path::Path,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+/// The number of block environments the cache holds by default
+const BLOCK_ENV_CAPACITY: usize = 1000;
+
+/// The maximum number of concurrent block requests when prefetching a range of block environments
+const BLOCK_ENV_PREFETCH_CONCURRENCY: usize = 16;
+
+/// A priority fee to add to the base fee of a block when the gas price reported by the provider is
+/// rejected, see [`EnvironmentCache::with_gas_price_fallback_tip`]
+pub const DEFAULT_GAS_PRICE_FALLBACK_TIP: u128 = 1_000_000_000;
+
+/// The maximum number of blocks a range of block environments can span to be prefetched by
+/// default, see [`EnvironmentCache::with_max_prefetch_span`]
+pub const DEFAULT_MAX_PREFETCH_SPAN: u64 = BLOCK_ENV_CAPACITY as u64;
+
+/// The number of latest block number refreshes in flight at once across all fork urls by default,
+/// see [`EnvironmentCache::spawn_latest_block_refresh`]
+pub const DEFAULT_MAX_CONCURRENT_REFRESHES: usize = 4;
 
 #[derive(Debug)]
 pub struct EnvironmentCache {
     /// A map of fork url -> chain id
     chain_ids_by_fork_url: DashMap<String, u64>,
-    /// A map of fork url -> latest block number
-    latest_block_map: DashMap<String, u64>,
+    /// A map of fork url -> chain id the provider is expected to be on
+    expected_chain_ids: DashMap<String, u64>,
+    /// A map of fork url -> latest block number, along with when it was set
+    latest_block_map: DashMap<String, (u64, Instant)>,
+    /// A map of fork url -> latest block number pinned for the duration of a run
+    pinned_latest_block_map: DashMap<String, u64>,
+    /// A map of fork url -> earliest block number served by the node
+    earliest_block_map: DashMap<String, u64>,
     /// A map of url & block number -> block environment
-    block_env_map: Cache<(String, u64), BlockEnvironment>,
+    block_env_map: BlockEnvCache,
+    /// The keys of the block environment map, to enumerate its entries, e.g. when saving the
+    /// cache. Keys are forgotten once their entry is evicted.
+    block_env_keys: BlockEnvKeys,
+    /// A map of url & block number -> block environment, with the full transactions of the block
+    full_block_env_map: BlockEnvCache,
+    /// The keys of the full block environment map
+    full_block_env_keys: BlockEnvKeys,
+    /// The lifecycle of both block environment maps, reporting evicted block environments
+    block_env_evictions: EvictionLifecycle<(String, u64)>,
+    /// Whether to resolve the chain id along with the first latest block number of a fork url
+    eager_chain_id: bool,
+    /// Invoked when the latest block number of a fork url advances
+    on_latest_block_advance: LatestBlockListener,
+    /// The priority fee added to the base fee when the reported gas price is below the base fee,
+    /// `None` if reported gas prices are not validated
+    gas_price_fallback_tip: Option<u128>,
+    /// The maximum number of blocks a prefetched range of block environments can span
+    max_prefetch_span: u64,
+    /// Bounds the number of background latest block number refreshes in flight at once
+    refresh_permits: Arc<Semaphore>,
+    /// How long a latest block number is served before it's fetched again, `None` if forever
+    latest_block_ttl: Option<Duration>,
+    /// The clock the age of latest block numbers is measured with
+    clock: Clock,
+    /// The hits, misses and insertions of the block environment caches
+    block_env_counters: CacheCounters,
+}
+
+/// A bounded map of url & block number -> block environment
+type BlockEnvCache = Cache<
+    (String, u64),
+    BlockEnvironment,
+    BlockEnvironmentWeighter,
+    DefaultHashBuilder,
+    EvictionLifecycle<(String, u64)>,
+>;
+
+/// The keys of a block environment map, see [`block_env_cache`]
+type BlockEnvKeys = Arc<DashSet<(String, u64)>>;
+
+/// A callback invoked with the fork url and the new latest block number
+type LatestBlockCallback = dyn Fn(&str, u64) + Send + Sync;
+
+#[derive(Clone)]
+struct LatestBlockListener(Arc<LatestBlockCallback>);
+
+impl Default for LatestBlockListener {
+    fn default() -> Self {
+        Self(Arc::new(|_, _| {}))
+    }
+}
+
+impl fmt::Debug for LatestBlockListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LatestBlockListener")
+    }
+}
+
+/// Returns the current instant, swappable to control time in tests
+#[derive(Clone)]
+struct Clock(Arc<dyn Fn() -> Instant + Send + Sync>);
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self(Arc::new(Instant::now))
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
 }
 
 impl Default for EnvironmentCache {
     fn default() -> Self {
+        let block_env_evictions = EvictionLifecycle::default();
+        let (block_env_keys, full_block_env_keys) = Default::default();
+        let block_env_cache = |keys: &BlockEnvKeys| {
+            block_env_cache(
+                BLOCK_ENV_CAPACITY,
+                BLOCK_ENV_CAPACITY as u64,
+                BlockEnvironmentWeighter::Count,
+                &block_env_evictions,
+                keys,
+            )
+        };
         Self {
             chain_ids_by_fork_url: DashMap::new(),
+            expected_chain_ids: DashMap::new(),
             latest_block_map: DashMap::new(),
-            block_env_map: Cache::new(1000),
+            pinned_latest_block_map: DashMap::new(),
+            earliest_block_map: DashMap::new(),
+            block_env_map: block_env_cache(&block_env_keys),
+            block_env_keys,
+            full_block_env_map: block_env_cache(&full_block_env_keys),
+            full_block_env_keys,
+            block_env_evictions,
+            eager_chain_id: false,
+            on_latest_block_advance: LatestBlockListener::default(),
+            gas_price_fallback_tip: None,
+            max_prefetch_span: DEFAULT_MAX_PREFETCH_SPAN,
+            refresh_permits: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_REFRESHES)),
+            latest_block_ttl: None,
+            clock: Clock::default(),
+            block_env_counters: CacheCounters::default(),
+        }
+    }
+}
+
+/// Creates a block environment map holding up to `weight_capacity` in weight, reporting evictions
+/// to the given lifecycle and forgetting the keys of evicted entries in the given keys
+fn block_env_cache(
+    estimated_items_capacity: usize,
+    weight_capacity: u64,
+    weighter: BlockEnvironmentWeighter,
+    evictions: &EvictionLifecycle<(String, u64)>,
+    keys: &BlockEnvKeys,
+) -> BlockEnvCache {
+    let keys = Arc::clone(keys);
+    Cache::with(
+        estimated_items_capacity,
+        weight_capacity,
+        weighter,
+        DefaultHashBuilder::default(),
+        evictions.with_hook(move |key| {
+            keys.remove(key);
+        }),
+    )
+}
+
+/// How the cached block environments are weighed against the capacity of the cache
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockEnvironmentWeighter {
+    /// Every block environment weighs the same, the capacity is a number of entries
+    #[default]
+    Count,
+    /// Block environments weigh their estimated size in bytes, the capacity is a byte budget
+    Bytes,
+}
+
+impl Weighter<(String, u64), BlockEnvironment> for BlockEnvironmentWeighter {
+    fn weight(&self, (fork_url, _): &(String, u64), block_env: &BlockEnvironment) -> u32 {
+        match self {
+            Self::Count => 1,
+            Self::Bytes => {
+                (fork_url.len() + block_env.estimated_size()).try_into().unwrap_or(u32::MAX)
+            }
         }
     }
 }
 
+/// A task refreshing the latest block number of a fork url in the background, see
+/// [`EnvironmentCache::spawn_latest_block_refresh`].
+///
+/// The task is canceled when the handle is dropped.
+#[derive(Debug)]
+pub struct LatestBlockRefresh(JoinHandle<()>);
+
+impl LatestBlockRefresh {
+    /// Cancels the refresh task
+    pub fn cancel(&self) {
+        self.0.abort();
+    }
+}
+
+impl Drop for LatestBlockRefresh {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// The outcome of [`EnvironmentCache::check_reorg`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReorgStatus {
+    /// The cached block hash matches the one served by the provider
+    Consistent,
+    /// The cached block hash differs from the one served by the provider, the block was reorged
+    Reorged {
+        /// The hash of the cached block
+        cached: B256,
+        /// The hash of the block served by the provider
+        live: B256,
+    },
+    /// No block with a hash is cached for the block number
+    NotCached,
+}
+
+/// A field whose value differs between two block environments, see
+/// [`BlockEnvironment::discrepancies`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldDiscrepancy {
+    /// The name of the field, e.g. `baseFeePerGas` for a header field
+    pub field: String,
+    /// The value of the field in the first block environment, JSON encoded
+    pub left: String,
+    /// The value of the field in the second block environment, JSON encoded
+    pub right: String,
+}
+
+/// The discrepancies between the block environments served by two providers for a block, see
+/// [`EnvironmentCache::diff_providers`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockEnvDiscrepancy {
+    /// The block number
+    pub block_number: u64,
+    /// The fields that differ
+    pub fields: Vec<FieldDiscrepancy>,
+}
+
 /// Cached Data for a block
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct BlockEnvironment {
     /// The [`Block`] object for a given block
     pub block: Option<Block>,
     /// The gas_price for the block
     pub gas_price: u128,
+    /// The excess blob gas of the block, `None` for pre-Cancun blocks
+    pub excess_blob_gas: Option<u128>,
+    /// The blob gas used by the block, `None` for pre-Cancun blocks
+    pub blob_gas_used: Option<u128>,
+    /// Whether the gas price was estimated from the block's base fee, because the provider failed
+    /// to report it, e.g. as it doesn't implement `eth_gasPrice`, or reported one below the base
+    /// fee
+    pub gas_price_estimated: bool,
+}
+
+impl BlockEnvironment {
+    /// Returns an approximation of the memory used by the block environment in bytes
+    pub fn estimated_size(&self) -> usize {
+        let block_size = self.block.as_ref().map_or(0, |block| {
+            let transactions_size = match &block.transactions {
+                BlockTransactions::Full(txs) => {
+                    txs.iter().map(|tx| size_of::<Transaction>() + tx.input.len()).sum()
+                }
+                BlockTransactions::Hashes(hashes) => hashes.len() * size_of::<B256>(),
+                BlockTransactions::Uncle => 0,
+            };
+            let withdrawals_size =
+                block.withdrawals.as_ref().map_or(0, |w| w.len() * size_of::<Withdrawal>());
+
+            transactions_size +
+                withdrawals_size +
+                block.uncles.len() * size_of::<B256>() +
+                block.header.extra_data.len()
+        });
+
+        size_of::<Self>() + block_size
+    }
+
+    /// Creates a new [`BlockEnvironment`], caching the EIP-4844 blob gas fields of the block
+    pub fn new(block: Option<Block>, gas_price: u128) -> Self {
+        let (excess_blob_gas, blob_gas_used) = block
+            .as_ref()
+            .map(|block| (block.header.excess_blob_gas, block.header.blob_gas_used))
+            .unwrap_or_default();
+        Self { block, gas_price, excess_blob_gas, blob_gas_used, gas_price_estimated: false }
+    }
+
+    /// Creates a new [`BlockEnvironment`] with a gas price estimated from the base fee of the
+    /// block, or zero if the block has no base fee
+    pub fn with_estimated_gas_price(block: Option<Block>) -> Self {
+        let gas_price =
+            block.as_ref().and_then(|block| block.header.base_fee_per_gas).unwrap_or_default();
+        Self { gas_price_estimated: true, ..Self::new(block, gas_price) }
+    }
+
+    /// Whether the two block environments are for the same block header, and their gas prices
+    /// differ by at most `gas_price_tolerance`.
+    ///
+    /// Useful for validating environments fetched from different providers, which may report
+    /// slightly different gas prices for the same block.
+    pub fn approx_eq(&self, other: &Self, gas_price_tolerance: u128) -> bool {
+        self.block.as_ref().map(|block| &block.header) ==
+            other.block.as_ref().map(|block| &block.header) &&
+            self.excess_blob_gas == other.excess_blob_gas &&
+            self.blob_gas_used == other.blob_gas_used &&
+            self.gas_price.abs_diff(other.gas_price) <= gas_price_tolerance
+    }
+
+    /// Returns the fields that differ between the two block environments: the header fields of
+    /// the block, and the gas price if it differs by more than `gas_price_tolerance`.
+    ///
+    /// Empty if and only if the block environments are [`Self::approx_eq`].
+    pub fn discrepancies(&self, other: &Self, gas_price_tolerance: u128) -> Vec<FieldDiscrepancy> {
+        let mut discrepancies = Vec::new();
+        match (&self.block, &other.block) {
+            (Some(left), Some(right)) => {
+                let to_map = |block: &Block| match serde_json::to_value(&block.header) {
+                    Ok(serde_json::Value::Object(map)) => map,
+                    _ => Default::default(),
+                };
+                let (left, right) = (to_map(left), to_map(right));
+                let fields = left.keys().chain(right.keys().filter(|key| !left.contains_key(*key)));
+                for field in fields {
+                    let (left, right) = (left.get(field), right.get(field));
+                    if left != right {
+                        discrepancies.push(FieldDiscrepancy {
+                            field: field.clone(),
+                            left: left.map_or_else(|| "null".to_string(), ToString::to_string),
+                            right: right.map_or_else(|| "null".to_string(), ToString::to_string),
+                        });
+                    }
+                }
+            }
+            (None, None) => {}
+            (left, right) => discrepancies.push(FieldDiscrepancy {
+                field: "block".to_string(),
+                left: if left.is_some() { "found" } else { "missing" }.to_string(),
+                right: if right.is_some() { "found" } else { "missing" }.to_string(),
+            }),
+        }
+        if self.gas_price.abs_diff(other.gas_price) > gas_price_tolerance {
+            discrepancies.push(FieldDiscrepancy {
+                field: "gasPrice".to_string(),
+                left: self.gas_price.to_string(),
+                right: other.gas_price.to_string(),
+            });
+        }
+        discrepancies
+    }
 }
 
 impl EnvironmentCache {
+    /// Creates a new cache holding up to `block_env_capacity` block environments, e.g. to avoid
+    /// refetching blocks on large runs forking at many blocks.
+    ///
+    /// The capacity applies separately to block environments with and without full transactions.
+    pub fn with_capacity(block_env_capacity: usize) -> Self {
+        Self::with_block_env_caches(
+            block_env_capacity,
+            block_env_capacity as u64,
+            BlockEnvironmentWeighter::Count,
+        )
+    }
+
+    /// Creates a new cache that evicts block environments once their estimated size exceeds the
+    /// given byte budget, rather than once the number of entries exceeds the default capacity.
+    ///
+    /// The budget applies separately to block environments with and without full transactions.
+    pub fn with_block_env_byte_budget(max_bytes: u64) -> Self {
+        Self::with_block_env_caches(BLOCK_ENV_CAPACITY, max_bytes, BlockEnvironmentWeighter::Bytes)
+    }
+
+    /// Creates a new cache whose block environment maps are bounded as given
+    fn with_block_env_caches(
+        estimated_items_capacity: usize,
+        weight_capacity: u64,
+        weighter: BlockEnvironmentWeighter,
+    ) -> Self {
+        let cache = Self::default();
+        let block_env_cache = |keys: &BlockEnvKeys| {
+            block_env_cache(
+                estimated_items_capacity,
+                weight_capacity,
+                weighter,
+                &cache.block_env_evictions,
+                keys,
+            )
+        };
+        Self {
+            block_env_map: block_env_cache(&cache.block_env_keys),
+            full_block_env_map: block_env_cache(&cache.full_block_env_keys),
+            ..cache
+        }
+    }
+
+    /// Sets whether the first latest block number fetch for a fork url also resolves its chain id
+    /// concurrently, saving a round-trip when the chain id is needed later, e.g. during fork setup.
+    pub fn with_eager_chain_id(mut self, eager_chain_id: bool) -> Self {
+        self.eager_chain_id = eager_chain_id;
+        self
+    }
+
+    /// Sets a listener invoked with the fork url and the new block number whenever the latest block
+    /// number of a fork url advances, either when set explicitly or when fetched from the provider.
+    ///
+    /// The listener is not invoked for the first latest block number of a fork url, nor when the
+    /// latest block number is set to the same or a lower value.
+    pub fn with_latest_block_listener(
+        mut self,
+        on_latest_block_advance: impl Fn(&str, u64) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_latest_block_advance = LatestBlockListener(Arc::new(on_latest_block_advance));
+        self
+    }
+
+    /// Sets a listener invoked with the fork url and the block number of every block environment
+    /// evicted from the cache because it is at capacity, e.g. to log thrashing and decide to raise
+    /// the capacity, see [`Self::with_capacity`].
+    ///
+    /// Block environments dropped with [`Self::invalidate_block`] are not reported. The listener is
+    /// invoked while the cache is locked, so it must not access the cache.
+    ///
+    /// # Panics
+    ///
+    /// If an eviction listener was already set
+    pub fn with_eviction_listener(
+        self,
+        on_evict: impl Fn(&str, u64) + Send + Sync + 'static,
+    ) -> Self {
+        self.block_env_evictions.set_listener(move |(fork_url, block_number): &(String, u64)| {
+            on_evict(fork_url, *block_number)
+        });
+        self
+    }
+
+    /// Sets how long the latest block number of a fork url is served from the cache before
+    /// [`Self::get_latest_block_number`] fetches it from the provider again, e.g. for long-running
+    /// processes forking the latest block of a live chain repeatedly.
+    ///
+    /// `None`, the default, serves it until it's set explicitly. Pinned latest block numbers never
+    /// expire, see [`Self::pin_latest`].
+    pub fn with_latest_block_ttl(mut self, latest_block_ttl: Option<Duration>) -> Self {
+        self.latest_block_ttl = latest_block_ttl;
+        self
+    }
+
+    /// Sets the clock the age of latest block numbers is measured with
+    #[cfg(test)]
+    fn with_clock(mut self, clock: impl Fn() -> Instant + Send + Sync + 'static) -> Self {
+        self.clock = Clock(Arc::new(clock));
+        self
+    }
+
+    /// Sets the maximum number of background latest block number refreshes in flight at once
+    /// across all fork urls, see [`Self::spawn_latest_block_refresh`].
+    ///
+    /// Refreshes over the limit wait for an in-flight refresh to complete. Only applies to
+    /// refreshes spawned after it is set.
+    ///
+    /// # Panics
+    ///
+    /// If `max_concurrent_refreshes` is zero
+    pub fn with_max_concurrent_refreshes(mut self, max_concurrent_refreshes: usize) -> Self {
+        assert!(max_concurrent_refreshes > 0, "max concurrent refreshes must be positive");
+        self.refresh_permits = Arc::new(Semaphore::new(max_concurrent_refreshes));
+        self
+    }
+
+    /// Sets the priority fee added to the base fee of a block when the gas price reported by the
+    /// provider is below the base fee, e.g. zero or in the wrong unit, and thus bogus.
+    ///
+    /// The validation is disabled by default, using reported gas prices as is: the provider reports
+    /// the current gas price, which can legitimately be below the base fee of a past block.
+    pub fn with_gas_price_fallback_tip(mut self, gas_price_fallback_tip: Option<u128>) -> Self {
+        self.gas_price_fallback_tip = gas_price_fallback_tip;
+        self
+    }
+
+    /// Sets the maximum number of blocks a range of block environments can span to be prefetched
+    /// with [`Self::prefetch_block_range`], e.g. to keep a typo in a range from flooding the
+    /// provider with requests.
+    pub fn with_max_prefetch_span(mut self, max_prefetch_span: u64) -> Self {
+        self.max_prefetch_span = max_prefetch_span;
+        self
+    }
+
     /// Gets the chain id for the given fork url
     async fn get_chain_id<N: Network, T: Transport + Clone, P: Provider<T, N>>(
         &self,
@@ -44,10 +523,41 @@ impl EnvironmentCache {
             return Ok(*chain_id);
         }
         let chain_id = provider.get_chain_id().await?;
+        if let Some(expected) = self.expected_chain_ids.get(fork_url) {
+            if *expected != chain_id {
+                eyre::bail!(
+                    "Chain id mismatch for {fork_url}: expected {}, got {chain_id}",
+                    *expected
+                );
+            }
+        }
         self.chain_ids_by_fork_url.insert(fork_url.to_string(), chain_id);
         Ok(chain_id)
     }
 
+    /// Registers the chain id the provider for the given fork url is expected to be on.
+    ///
+    /// If the provider reports a different chain id, [`Self::get_fork_info`] fails without caching
+    /// anything, so the cache is not poisoned with data from the wrong chain.
+    pub fn set_expected_chain_id(&self, fork_url: &str, chain_id: u64) {
+        self.chain_ids_by_fork_url.remove_if(fork_url, |_, cached| *cached != chain_id);
+        self.expected_chain_ids.insert(fork_url.to_string(), chain_id);
+    }
+
+    /// Returns the hits and misses of block environment lookups so far, the block environments
+    /// inserted and the number of block environments cached, with and without full transactions
+    pub fn stats(&self) -> CacheStats {
+        self.block_env_counters.stats(self.block_env_map.len() + self.full_block_env_map.len())
+    }
+
+    /// Returns the cached block environment for the given fork url and block number, if any,
+    /// without fetching it or updating its recency in the cache.
+    ///
+    /// Cached entries whose block could not be fetched are returned as well.
+    pub fn peek_block_env(&self, fork_url: &str, block_number: u64) -> Option<BlockEnvironment> {
+        self.block_env_map.peek(&(fork_url.to_owned(), block_number))
+    }
+
     /// Fetches the block environment for the given fork url and block number
     async fn get_block_env_by_number<N: Network, T: Transport + Clone, P: Provider<T, N>>(
         &self,
@@ -55,50 +565,491 @@ impl EnvironmentCache {
         fork_url: &str,
         block_number: u64,
     ) -> eyre::Result<BlockEnvironment> {
-        if let Some(block_env) = self.block_env_map.get(&(fork_url.to_owned(), block_number)) {
+        self.get_block_env(provider, fork_url, block_number, false).await
+    }
+
+    /// Fetches the block environment for the given fork url and block number, including the full
+    /// transactions of the block, e.g. to replay them.
+    ///
+    /// Block environments with full transactions are cached separately from the ones with only the
+    /// transaction hashes, which are used by default.
+    pub async fn get_block_env_full<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        fork_url: &str,
+        block_number: u64,
+    ) -> eyre::Result<BlockEnvironment> {
+        self.get_block_env(provider, fork_url, block_number, true).await
+    }
+
+    /// Fetches the block environment for the given fork url and block hash, e.g. a hash taken from
+    /// logs.
+    ///
+    /// The hash is resolved to its block number and the block environment is cached under that
+    /// number, so it's shared with lookups by number. Fails if the block is no longer canonical,
+    /// e.g. because it was reorged out, without caching it.
+    pub async fn get_block_env_by_hash<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        fork_url: &str,
+        block_hash: B256,
+    ) -> eyre::Result<BlockEnvironment> {
+        let block_number = provider
+            .get_block_by_hash(block_hash, BlockTransactionsKind::Hashes)
+            .await?
+            .and_then(|block| block.header.number)
+            .ok_or_else(|| eyre::eyre!("block {block_hash} not found on {fork_url}"))?;
+
+        let block_env = self.get_block_env_by_number(provider, fork_url, block_number).await?;
+        if block_env.block.as_ref().and_then(|block| block.header.hash) == Some(block_hash) {
+            return Ok(block_env);
+        }
+
+        // The cached block may be the stale one, in which case it's dropped and fetched again
+        if let ReorgStatus::Reorged { live, .. } =
+            self.check_reorg(provider, fork_url, block_number).await?
+        {
+            if live == block_hash {
+                return self.get_block_env_by_number(provider, fork_url, block_number).await;
+            }
+        }
+        eyre::bail!("block {block_hash} is not the canonical block {block_number} on {fork_url}, it was likely reorged out")
+    }
+
+    /// Fetches the block environment for the given fork url and block number, with the full
+    /// transactions of the block if `full` is set
+    async fn get_block_env<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        fork_url: &str,
+        block_number: u64,
+        full: bool,
+    ) -> eyre::Result<BlockEnvironment> {
+        let block_env_map = if full { &self.full_block_env_map } else { &self.block_env_map };
+        if let Some(block_env) = block_env_map.get(&(fork_url.to_owned(), block_number)) {
             // If the block is none, try to fetch it from the provider and cache it
             if block_env.block.is_none() {
+                self.block_env_counters.miss();
                 let block = provider
-                    .get_block_by_number(BlockNumberOrTag::Number(block_number), false)
+                    .get_block_by_number(BlockNumberOrTag::Number(block_number), full)
                     .await?;
 
-                let block_env = BlockEnvironment { block, gas_price: block_env.gas_price };
-                self.block_env_map.insert((fork_url.to_owned(), block_number), block_env.clone());
+                let block_env = if block_env.gas_price_estimated {
+                    BlockEnvironment::with_estimated_gas_price(block)
+                } else {
+                    BlockEnvironment::new(block, block_env.gas_price)
+                };
+                self.insert_block_env(full, fork_url, block_number, block_env.clone());
                 Ok(block_env)
             } else {
+                self.block_env_counters.hit();
                 Ok(block_env.clone())
             }
         } else {
-            let (block, gas_price) = tokio::try_join!(
-                provider.get_block_by_number(BlockNumberOrTag::Number(block_number), false),
+            self.block_env_counters.miss();
+            let (block, gas_price) = tokio::join!(
+                provider.get_block_by_number(BlockNumberOrTag::Number(block_number), full),
                 provider.get_gas_price()
-            )?;
+            );
 
-            let block_env = BlockEnvironment { block, gas_price };
-            self.block_env_map.insert((fork_url.to_owned(), block_number), block_env.clone());
+            // Not all providers support `eth_gasPrice`, so don't fail if the block was fetched
+            let block_env = match gas_price {
+                Ok(gas_price) => self.validated_block_env(block?, gas_price, block_number),
+                Err(err) => {
+                    let block = block?;
+                    warn!(target: "backend::environment_cache", %err, block_number, "failed to get gas price, estimating it from the block base fee");
+                    BlockEnvironment::with_estimated_gas_price(block)
+                }
+            };
+            self.insert_block_env(full, fork_url, block_number, block_env.clone());
             Ok(block_env)
         }
     }
 
+    /// Caches the block environment for the given fork url and block number, with the full
+    /// transactions of the block if `full` is set
+    fn insert_block_env(
+        &self,
+        full: bool,
+        fork_url: &str,
+        block_number: u64,
+        block_env: BlockEnvironment,
+    ) {
+        let key = (fork_url.to_owned(), block_number);
+        // Replacing an entry evicts the previous one, which forgets its key, so the key is
+        // recorded after the insertion
+        if full {
+            self.full_block_env_map.insert(key.clone(), block_env);
+            self.full_block_env_keys.insert(key);
+        } else {
+            self.block_env_map.insert(key.clone(), block_env);
+            self.block_env_keys.insert(key);
+        }
+        self.block_env_counters.insertion();
+    }
+
+    /// Creates the block environment with the gas price reported by the provider, replacing it
+    /// with the base fee plus the fallback tip if it's below the base fee of the block
+    fn validated_block_env(
+        &self,
+        block: Option<Block>,
+        gas_price: u128,
+        block_number: u64,
+    ) -> BlockEnvironment {
+        let base_fee = block.as_ref().and_then(|block| block.header.base_fee_per_gas);
+        match (self.gas_price_fallback_tip, base_fee) {
+            (Some(tip), Some(base_fee)) if gas_price < base_fee => {
+                let fallback = base_fee.saturating_add(tip);
+                debug!(target: "backend::environment_cache", gas_price, base_fee, fallback, block_number, "gas price is below the block base fee, using the base fee plus a tip instead");
+                BlockEnvironment {
+                    gas_price_estimated: true,
+                    ..BlockEnvironment::new(block, fallback)
+                }
+            }
+            _ => BlockEnvironment::new(block, gas_price),
+        }
+    }
+
+    /// Fetches the block environments of the blocks from `from` to `to`, inclusive, concurrently
+    /// and caches them, so that later lookups of these blocks are served from the cache, e.g.
+    /// before replaying a sequence of blocks.
+    ///
+    /// Blocks that are already cached are skipped. Returns the number of block environments
+    /// fetched. If any block fails to be fetched, the others are still cached and an error listing
+    /// every failed block is returned.
+    ///
+    /// Errors without fetching anything if `from` is after `to`, or if the range spans more blocks
+    /// than the maximum, see [`Self::with_max_prefetch_span`].
+    pub async fn prefetch_block_range<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        fork_url: &str,
+        from: u64,
+        to: u64,
+    ) -> eyre::Result<usize> {
+        if from > to {
+            eyre::bail!("invalid block range {from}..={to}: the start is after the end");
+        }
+        let span = to - from + 1;
+        if span > self.max_prefetch_span {
+            eyre::bail!(
+                "block range {from}..={to} spans {span} blocks, more than the maximum of {}",
+                self.max_prefetch_span
+            );
+        }
+        let missing = (from..=to)
+            .filter(|block_number| {
+                self.block_env_map
+                    .peek(&(fork_url.to_owned(), *block_number))
+                    .map_or(true, |block_env| block_env.block.is_none())
+            })
+            .collect::<Vec<_>>();
+
+        let results = stream::iter(missing)
+            .map(|block_number| async move {
+                let result = self.get_block_env_by_number(provider, fork_url, block_number).await;
+                (block_number, result)
+            })
+            .buffer_unordered(BLOCK_ENV_PREFETCH_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        let fetched = results.len();
+        let mut failed = results
+            .into_iter()
+            .filter_map(|(block_number, result)| Some((block_number, result.err()?)))
+            .collect::<Vec<_>>();
+        if failed.is_empty() {
+            return Ok(fetched);
+        }
+        failed.sort_by_key(|(block_number, _)| *block_number);
+        let errors = failed
+            .iter()
+            .map(|(block_number, err)| format!("block {block_number}: {err}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        eyre::bail!(
+            "failed to prefetch {} of {fetched} block environments on {fork_url}:\n{errors}",
+            failed.len()
+        )
+    }
+
+    /// Fetches the block environments of the given block numbers from both providers and returns
+    /// the blocks whose environments differ, e.g. to vet a new provider against a trusted one
+    /// before forking from it.
+    ///
+    /// The block environments are cached under the fork url of their provider, gas prices differing
+    /// by at most `gas_price_tolerance` are considered equal, see [`BlockEnvironment::approx_eq`].
+    pub async fn diff_providers<N, T1, T2, P1, P2>(
+        &self,
+        (left, left_url): (&P1, &str),
+        (right, right_url): (&P2, &str),
+        block_numbers: impl IntoIterator<Item = u64>,
+        gas_price_tolerance: u128,
+    ) -> eyre::Result<Vec<BlockEnvDiscrepancy>>
+    where
+        N: Network,
+        T1: Transport + Clone,
+        T2: Transport + Clone,
+        P1: Provider<T1, N>,
+        P2: Provider<T2, N>,
+    {
+        let mut discrepancies = Vec::new();
+        for block_number in block_numbers {
+            let (left_env, right_env) = tokio::try_join!(
+                self.get_block_env_by_number(left, left_url, block_number),
+                self.get_block_env_by_number(right, right_url, block_number)
+            )?;
+            if !left_env.approx_eq(&right_env, gas_price_tolerance) {
+                let fields = left_env.discrepancies(&right_env, gas_price_tolerance);
+                discrepancies.push(BlockEnvDiscrepancy { block_number, fields });
+            }
+        }
+        Ok(discrepancies)
+    }
+
     /// Gets the latest block number for the given fork url
     pub async fn get_latest_block_number<N: Network, T: Transport + Clone, P: Provider<T, N>>(
         &self,
         provider: &P,
         fork_url: &str,
     ) -> eyre::Result<u64> {
-        match self.latest_block_map.get(fork_url) {
-            Some(block_number) => Ok(*block_number),
+        if let Some(block_number) = self.pinned_latest_block_map.get(fork_url) {
+            return Ok(*block_number);
+        }
+        let now = (self.clock.0)();
+        let cached =
+            self.latest_block_map.get(fork_url).map(|entry| *entry).filter(|(_, set_at)| {
+                self.latest_block_ttl
+                    .map_or(true, |ttl| now.saturating_duration_since(*set_at) < ttl)
+            });
+        match cached {
+            Some((block_number, _)) => Ok(block_number),
             None => {
-                let block_number = provider.get_block_number().await?;
+                let block_number = if self.eager_chain_id {
+                    let (block_number, _) = tokio::try_join!(
+                        async { Ok(provider.get_block_number().await?) },
+                        self.get_chain_id(provider, fork_url)
+                    )?;
+                    block_number
+                } else {
+                    provider.get_block_number().await?
+                };
                 self.set_latest_block_number(fork_url, block_number);
                 Ok(block_number)
             }
         }
     }
 
+    /// Gets the earliest block number served by the node at the given fork url.
+    ///
+    /// This is the genesis block on archive nodes, but may be later on pruned nodes, and bounds
+    /// how far back block numbers can be resolved.
+    pub async fn get_earliest_block_number<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        fork_url: &str,
+    ) -> eyre::Result<u64> {
+        if let Some(block_number) = self.earliest_block_map.get(fork_url) {
+            return Ok(*block_number);
+        }
+        let block_number = provider
+            .get_block_by_number(BlockNumberOrTag::Earliest, false)
+            .await?
+            .and_then(|block| block.header.number)
+            .unwrap_or_default();
+        self.earliest_block_map.insert(fork_url.to_string(), block_number);
+        Ok(block_number)
+    }
+
+    /// Finds the latest block whose timestamp is at or before the given timestamp, by binary
+    /// searching the block environments for the given fork url.
+    ///
+    /// Timestamps after the latest block resolve to the latest block, timestamps at or before the
+    /// earliest block served by the node resolve to the earliest block, see
+    /// [`Self::get_earliest_block_number`].
+    pub async fn get_block_number_by_timestamp<
+        N: Network,
+        T: Transport + Clone,
+        P: Provider<T, N>,
+    >(
+        &self,
+        provider: &P,
+        fork_url: &str,
+        timestamp: u64,
+    ) -> eyre::Result<u64> {
+        let latest_block = self.get_latest_block_number(provider, fork_url).await?;
+        if self.get_block_timestamp(provider, fork_url, latest_block).await? <= timestamp {
+            return Ok(latest_block);
+        }
+        let earliest_block = self.get_earliest_block_number(provider, fork_url).await?;
+        if earliest_block >= latest_block ||
+            self.get_block_timestamp(provider, fork_url, earliest_block).await? > timestamp
+        {
+            return Ok(earliest_block.min(latest_block));
+        }
+
+        // invariant: timestamp(low) <= timestamp < timestamp(high)
+        let (mut low, mut high) = (earliest_block, latest_block);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if self.get_block_timestamp(provider, fork_url, mid).await? <= timestamp {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    /// Gets the timestamp of the given block number, using the cached block environment if
+    /// possible
+    async fn get_block_timestamp<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        fork_url: &str,
+        block_number: u64,
+    ) -> eyre::Result<u64> {
+        self.get_block_env_by_number(provider, fork_url, block_number)
+            .await?
+            .block
+            .map(|block| block.header.timestamp)
+            .ok_or_else(|| eyre::eyre!("Failed to get block for block number: {block_number}"))
+    }
+
     /// Sets the latest block number for the given fork url
     pub fn set_latest_block_number(&self, fork_url: &str, block_number: u64) {
-        self.latest_block_map.insert(fork_url.to_string(), block_number);
+        let previous =
+            self.latest_block_map.insert(fork_url.to_string(), (block_number, (self.clock.0)()));
+        if previous.is_some_and(|(previous, _)| block_number > previous) {
+            (self.on_latest_block_advance.0)(fork_url, block_number);
+        }
+    }
+
+    /// Spawns a task refreshing the latest block number of the given fork url from the provider
+    /// every `refresh_interval`, so the cached latest block number is never more than that stale
+    /// while serving, without checking it on every request.
+    ///
+    /// The task runs until the returned handle is canceled or dropped, or the cache is dropped.
+    /// Failed refreshes are logged and retried on the next tick. At most
+    /// [`Self::with_max_concurrent_refreshes`] refreshes of this cache are in flight at once.
+    pub fn spawn_latest_block_refresh<N, T, P>(
+        self: &Arc<Self>,
+        provider: P,
+        fork_url: &str,
+        refresh_interval: Duration,
+    ) -> LatestBlockRefresh
+    where
+        N: Network,
+        T: Transport + Clone,
+        P: Provider<T, N> + 'static,
+    {
+        let cache = Arc::downgrade(self);
+        let refresh_permits = self.refresh_permits.clone();
+        let fork_url = fork_url.to_string();
+        LatestBlockRefresh(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(refresh_interval);
+            loop {
+                interval.tick().await;
+                let Ok(_permit) = refresh_permits.acquire().await else { return };
+                let block_number = provider.get_block_number().await;
+                let Some(cache) = Weak::upgrade(&cache) else { return };
+                match block_number {
+                    Ok(block_number) => cache.set_latest_block_number(&fork_url, block_number),
+                    Err(err) => {
+                        warn!(target: "backend::environment_cache", %err, fork_url, "failed to refresh the latest block number")
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Returns how many blocks the latest block number of the given fork url advanced since the
+    /// given previous block number, e.g. the latest block number of an earlier run.
+    ///
+    /// Negative if the chain is behind the previous block number, `None` if the latest block
+    /// number of the fork url is not known yet. Ignores [`Self::pin_latest`].
+    pub fn latest_block_delta(&self, fork_url: &str, previous: u64) -> Option<i64> {
+        let (latest, _) = *self.latest_block_map.get(fork_url)?;
+        Some(latest as i64 - previous as i64)
+    }
+
+    /// Pins the latest block number for the given fork url, so that
+    /// [`Self::get_latest_block_number`] keeps returning it even if the latest block number
+    /// advances, e.g. to keep a run internally consistent, until [`Self::unpin_latest`] is called.
+    ///
+    /// Returns the pinned block number, or `None` if the latest block number of the fork url is not
+    /// known yet.
+    pub fn pin_latest(&self, fork_url: &str) -> Option<u64> {
+        if let Some(block_number) = self.pinned_latest_block_map.get(fork_url) {
+            return Some(*block_number);
+        }
+        let (block_number, _) = *self.latest_block_map.get(fork_url)?;
+        self.pinned_latest_block_map.insert(fork_url.to_string(), block_number);
+        Some(block_number)
+    }
+
+    /// Unpins the latest block number for the given fork url, see [`Self::pin_latest`]
+    pub fn unpin_latest(&self, fork_url: &str) {
+        self.pinned_latest_block_map.remove(fork_url);
+    }
+
+    /// Drops the cached block environment for the given fork url and block number, so that it is
+    /// fetched from the provider again on the next access, e.g. after learning it was wrong
+    pub fn invalidate_block(&self, fork_url: &str, block_number: u64) {
+        let key = (fork_url.to_owned(), block_number);
+        self.block_env_map.remove(&key);
+        self.block_env_keys.remove(&key);
+        self.full_block_env_map.remove(&key);
+        self.full_block_env_keys.remove(&key);
+    }
+
+    /// Drops the cached block environments for the given fork url from the given block number on,
+    /// e.g. after a reorg at that block
+    pub fn invalidate_from_block(&self, fork_url: &str, block_number: u64) {
+        let invalidated = [&self.block_env_keys, &self.full_block_env_keys]
+            .into_iter()
+            .flat_map(|keys| {
+                keys.iter()
+                    .filter(|key| key.0 == fork_url && key.1 >= block_number)
+                    .map(|key| key.1)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for block_number in invalidated {
+            self.invalidate_block(fork_url, block_number);
+        }
+    }
+
+    /// Compares the hash of the cached block for the given fork url and block number with the hash
+    /// the provider currently serves for that number, to detect reorgs.
+    ///
+    /// If the block was reorged, the cached block environments from that block on are dropped, see
+    /// [`Self::invalidate_from_block`].
+    pub async fn check_reorg<N: Network, T: Transport + Clone, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        fork_url: &str,
+        block_number: u64,
+    ) -> eyre::Result<ReorgStatus> {
+        let key = (fork_url.to_owned(), block_number);
+        let cached = [&self.block_env_map, &self.full_block_env_map].into_iter().find_map(|map| {
+            map.peek(&key).and_then(|block_env| block_env.block.and_then(|block| block.header.hash))
+        });
+        let Some(cached) = cached else { return Ok(ReorgStatus::NotCached) };
+
+        let live = provider
+            .get_block_by_number(BlockNumberOrTag::Number(block_number), false)
+            .await?
+            .and_then(|block| block.header.hash)
+            .ok_or_else(|| eyre::eyre!("block {block_number} not found on {fork_url}"))?;
+
+        if live == cached {
+            return Ok(ReorgStatus::Consistent);
+        }
+        warn!(target: "backend::environment_cache", block_number, %cached, %live, "block was reorged, invalidating the cached block environments");
+        self.invalidate_from_block(fork_url, block_number);
+        Ok(ReorgStatus::Reorged { cached, live })
     }
 
     /// Fetches the chain id and block environment for the given fork url and block number
@@ -108,16 +1059,96 @@ impl EnvironmentCache {
         fork_url: &str,
         block_number: u64,
     ) -> eyre::Result<(u64, BlockEnvironment)> {
+        // Verify the chain id before fetching the block environment, so that a block environment
+        // from the wrong chain is never cached
+        if self.expected_chain_ids.contains_key(fork_url) {
+            let chain_id = self.get_chain_id(provider, fork_url).await?;
+            let block_env = self.get_block_env_by_number(provider, fork_url, block_number).await?;
+            return Ok((chain_id, block_env));
+        }
+
         tokio::try_join!(
             self.get_chain_id(provider, fork_url),
             self.get_block_env_by_number(provider, fork_url, block_number)
         )
     }
+
+    /// Saves the chain ids and block environments of the cache as JSON to the given path, to
+    /// restore them in a later process with [`Self::load_from_path`].
+    ///
+    /// Latest block numbers are not saved, as they are stale by then, and neither are block
+    /// environments with full transactions. The file is gzip compressed if the path has a `.gz`
+    /// extension.
+    pub fn save_to_path(&self, path: &Path) -> eyre::Result<()> {
+        let chain_ids = self
+            .chain_ids_by_fork_url
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        // The keys are collected first, as evictions forget keys while the map is locked
+        let keys = self.block_env_keys.iter().map(|key| key.key().clone()).collect::<Vec<_>>();
+        let mut block_envs = keys
+            .into_iter()
+            .filter_map(|key| {
+                let block_env = self.block_env_map.peek(&key)?;
+                let (fork_url, block_number) = key;
+                Some(PersistedBlockEnvironment { fork_url, block_number, block_env })
+            })
+            .collect::<Vec<_>>();
+        block_envs
+            .sort_by(|a, b| (&a.fork_url, a.block_number).cmp(&(&b.fork_url, b.block_number)));
+
+        let mut writer = compression::create(path, compression::is_gz_path(path))
+            .wrap_err_with(|| format!("failed to create {}", path.display()))?;
+        serde_json::to_writer(&mut writer, &PersistedEnvironmentCache { chain_ids, block_envs })?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Loads the chain ids and block environments saved to the given path with
+    /// [`Self::save_to_path`] into the cache, decompressing the file if it was saved compressed.
+    ///
+    /// The block environments are bounded by the cache's capacity, e.g.
+    /// `EnvironmentCache::with_capacity(100).load_from_path(path)` restores at most 100 of them.
+    pub fn load_from_path(mut self, path: &Path) -> eyre::Result<Self> {
+        let reader = compression::open(path)
+            .wrap_err_with(|| format!("failed to open {}", path.display()))?;
+        let persisted: PersistedEnvironmentCache = serde_json::from_reader(reader)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+
+        self.chain_ids_by_fork_url.extend(persisted.chain_ids);
+        for PersistedBlockEnvironment { fork_url, block_number, block_env } in persisted.block_envs
+        {
+            let key = (fork_url, block_number);
+            self.block_env_map.insert(key.clone(), block_env);
+            self.block_env_keys.insert(key);
+        }
+        Ok(self)
+    }
+}
+
+/// The contents of an [`EnvironmentCache`] saved to disk, see [`EnvironmentCache::save_to_path`]
+#[derive(Serialize, Deserialize)]
+struct PersistedEnvironmentCache {
+    chain_ids: BTreeMap<String, u64>,
+    block_envs: Vec<PersistedBlockEnvironment>,
+}
+
+/// A block environment of an [`EnvironmentCache`] saved to disk
+#[derive(Serialize, Deserialize)]
+struct PersistedBlockEnvironment {
+    fork_url: String,
+    block_number: u64,
+    block_env: BlockEnvironment,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock_transport::{failure, method_not_found, success, MockTransport};
+    use alloy_primitives::U256;
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
     use foundry_common::provider::ProviderBuilder;
     use foundry_test_utils::rpc::next_http_rpc_endpoint as fork_url;
 
@@ -128,7 +1159,7 @@ mod tests {
         let fork_url = fork_url();
         let good_provider = ProviderBuilder::new(&fork_url).build().unwrap();
 
-        let bad_provider = ProviderBuilder::new(&FAKE_FORK_URL).build().unwrap();
+        let bad_provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
 
         let environment_cache = EnvironmentCache::default();
 
@@ -147,7 +1178,7 @@ mod tests {
         let fork_url = fork_url();
         let good_provider = ProviderBuilder::new(&fork_url).build().unwrap();
 
-        let bad_provider = ProviderBuilder::new(&FAKE_FORK_URL).build().unwrap();
+        let bad_provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
 
         let environment_cache = EnvironmentCache::default();
 
@@ -173,6 +1204,537 @@ mod tests {
         );
     }
 
+    /// A transport serving blocks with the given base fee and a single transaction, and the given
+    /// gas price, or not implementing `eth_gasPrice` if it's `None`
+    fn gas_price_transport(
+        base_fee_per_gas: Option<u128>,
+        gas_price: Option<u128>,
+    ) -> MockTransport {
+        MockTransport::new(move |method, params| match (method, gas_price) {
+            ("eth_getBlockByNumber", _) => {
+                let mut block: Block = Block::default();
+                block.header.number = Some(1_000_000);
+                block.header.base_fee_per_gas = base_fee_per_gas;
+                block.transactions = if params[1] == true {
+                    BlockTransactions::Full(vec![Transaction::default()])
+                } else {
+                    BlockTransactions::Hashes(vec![B256::ZERO])
+                };
+                success(&block)
+            }
+            ("eth_gasPrice", Some(gas_price)) => success(&U256::from(gas_price)),
+            (method, _) => method_not_found(method),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_diff_providers() {
+        let provider = |base_fee_per_gas| {
+            RootProvider::<_, Ethereum>::new(RpcClient::new(
+                gas_price_transport(Some(base_fee_per_gas), Some(100)),
+                true,
+            ))
+        };
+        let (trusted, agreeing, diverging) = (provider(10), provider(10), provider(11));
+        let cache = EnvironmentCache::default();
+
+        let discrepancies = cache
+            .diff_providers((&trusted, "http://trusted"), (&agreeing, "http://agreeing"), [1], 0)
+            .await
+            .unwrap();
+        assert!(discrepancies.is_empty());
+
+        let discrepancies = cache
+            .diff_providers((&trusted, "http://trusted"), (&diverging, "http://diverging"), [1], 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            discrepancies,
+            vec![BlockEnvDiscrepancy {
+                block_number: 1,
+                fields: vec![FieldDiscrepancy {
+                    field: "baseFeePerGas".to_string(),
+                    left: "\"0xa\"".to_string(),
+                    right: "\"0xb\"".to_string(),
+                }],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_env_estimated_gas_price() {
+        let cache = EnvironmentCache::default();
+
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(7), None),
+            true,
+        ));
+        let block_env =
+            cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert_eq!(block_env.block.unwrap().header.number, Some(1_000_000));
+        assert_eq!(block_env.gas_price, 7);
+        assert!(block_env.gas_price_estimated);
+
+        // Pre-London blocks have no base fee to estimate from
+        let provider =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(gas_price_transport(None, None), true));
+        let block_env =
+            cache.get_block_env_by_number(&provider, "http://other.com", 1_000_000).await.unwrap();
+        assert_eq!(block_env.gas_price, 0);
+        assert!(block_env.gas_price_estimated);
+    }
+
+    #[tokio::test]
+    async fn test_block_env_bogus_gas_price() {
+        // A zero gas price on a London block is bogus
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(7), Some(0)),
+            true,
+        ));
+
+        // Reported gas prices are used as is by default
+        let cache = EnvironmentCache::default();
+        let block_env =
+            cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert_eq!(block_env.gas_price, 0);
+        assert!(!block_env.gas_price_estimated);
+
+        let cache = EnvironmentCache::default()
+            .with_gas_price_fallback_tip(Some(DEFAULT_GAS_PRICE_FALLBACK_TIP));
+        let block_env =
+            cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert_eq!(block_env.gas_price, 7 + DEFAULT_GAS_PRICE_FALLBACK_TIP);
+        assert!(block_env.gas_price_estimated);
+
+        let cache = EnvironmentCache::default().with_gas_price_fallback_tip(Some(2));
+        let block_env =
+            cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert_eq!(block_env.gas_price, 9);
+
+        // Gas prices at or above the base fee are used as is
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(7), Some(10)),
+            true,
+        ));
+        let cache = EnvironmentCache::default().with_gas_price_fallback_tip(Some(2));
+        let block_env =
+            cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert_eq!(block_env.gas_price, 10);
+        assert!(!block_env.gas_price_estimated);
+    }
+
+    #[tokio::test]
+    async fn test_get_block_env_full() {
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(7), Some(10)),
+            true,
+        ));
+        let bad_provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
+
+        let cache = EnvironmentCache::default();
+        let block_env =
+            cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert!(block_env.block.unwrap().transactions.is_hashes());
+
+        // The light block env is not served for the full one
+        assert!(cache.get_block_env_full(&bad_provider, FAKE_FORK_URL, 1_000_000).await.is_err());
+        let full_block_env =
+            cache.get_block_env_full(&provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert!(full_block_env.block.as_ref().unwrap().transactions.is_full());
+
+        // Both are cached separately
+        assert_eq!(
+            cache.get_block_env_full(&bad_provider, FAKE_FORK_URL, 1_000_000).await.unwrap(),
+            full_block_env
+        );
+        let block_env =
+            cache.get_block_env_by_number(&bad_provider, FAKE_FORK_URL, 1_000_000).await.unwrap();
+        assert!(block_env.block.unwrap().transactions.is_hashes());
+    }
+
+    #[tokio::test]
+    async fn test_block_env_stats() {
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(7), Some(10)),
+            true,
+        ));
+        let cache = EnvironmentCache::default();
+        assert_eq!(cache.stats(), CacheStats::default());
+
+        for block_number in [1, 2, 1, 1] {
+            cache.get_block_env_by_number(&provider, FAKE_FORK_URL, block_number).await.unwrap();
+        }
+        cache.get_block_env_full(&provider, FAKE_FORK_URL, 1).await.unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 3, insertions: 3, entries: 3 });
+    }
+
+    fn block_env_with_hashes(num_hashes: usize) -> BlockEnvironment {
+        let block = Block {
+            transactions: BlockTransactions::Hashes(vec![B256::ZERO; num_hashes]),
+            ..Default::default()
+        };
+        BlockEnvironment::new(Some(block), 1)
+    }
+
+    #[test]
+    fn test_block_env_capacity() {
+        let block_env = block_env_with_hashes(1);
+        let cache = EnvironmentCache::with_capacity(10);
+        for block_number in 0..50 {
+            cache
+                .block_env_map
+                .insert((FAKE_FORK_URL.to_string(), block_number), block_env.clone());
+            cache
+                .full_block_env_map
+                .insert((FAKE_FORK_URL.to_string(), block_number), block_env.clone());
+        }
+        assert!(cache.block_env_map.len() <= 10, "{}", cache.block_env_map.len());
+        assert!(cache.full_block_env_map.len() <= 10, "{}", cache.full_block_env_map.len());
+
+        // The default keeps the default capacity
+        assert_eq!(EnvironmentCache::default().block_env_map.capacity(), BLOCK_ENV_CAPACITY as u64);
+    }
+
+    #[test]
+    fn test_block_env_eviction_listener() {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let cache = EnvironmentCache::with_capacity(2).with_eviction_listener({
+            let evicted = evicted.clone();
+            move |fork_url, block_number| {
+                evicted.lock().unwrap().push((fork_url.to_string(), block_number))
+            }
+        });
+        let block_env = block_env_with_hashes(1);
+        for block_number in 0..10 {
+            cache.insert_block_env(false, FAKE_FORK_URL, block_number, block_env.clone());
+        }
+        for block_number in 0..10 {
+            cache.insert_block_env(true, FAKE_FORK_URL, block_number, block_env.clone());
+        }
+
+        // Both maps report their evictions, every block environment inserted is either still
+        // cached or was reported as evicted
+        let evictions = evicted.lock().unwrap().len();
+        assert!(evictions >= 16, "{evictions}");
+        assert_eq!(evictions + cache.block_env_map.len() + cache.full_block_env_map.len(), 20);
+        assert!(evicted.lock().unwrap().iter().all(|(fork_url, _)| fork_url == FAKE_FORK_URL));
+
+        // Invalidated block environments are not reported
+        let cached = (0..10)
+            .find(|block_number| {
+                cache.block_env_map.peek(&(FAKE_FORK_URL.to_string(), *block_number)).is_some()
+            })
+            .unwrap();
+        cache.invalidate_block(FAKE_FORK_URL, cached);
+        assert_eq!(evicted.lock().unwrap().len(), evictions);
+    }
+
+    #[test]
+    fn test_block_env_byte_budget() {
+        let block_env = block_env_with_hashes(100);
+        let entry_size = (FAKE_FORK_URL.len() + block_env.estimated_size()) as u64;
+        assert!(entry_size > 100 * 32);
+
+        let budget = entry_size * 10;
+        let cache = EnvironmentCache::with_block_env_byte_budget(budget);
+        for block_number in 0..50 {
+            cache
+                .block_env_map
+                .insert((FAKE_FORK_URL.to_string(), block_number), block_env.clone());
+        }
+        assert!(cache.block_env_map.weight() <= budget);
+        assert!(cache.block_env_map.len() < 50);
+
+        // The default cache is count based, so all entries fit
+        let cache = EnvironmentCache::default();
+        for block_number in 0..50 {
+            cache
+                .block_env_map
+                .insert((FAKE_FORK_URL.to_string(), block_number), block_env.clone());
+        }
+        assert_eq!(cache.block_env_map.len(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_block() {
+        let provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
+        let cache = EnvironmentCache::default();
+        for block_number in [1, 2] {
+            cache
+                .block_env_map
+                .insert((FAKE_FORK_URL.to_string(), block_number), block_env_with_hashes(1));
+        }
+
+        cache.invalidate_block(FAKE_FORK_URL, 1);
+
+        // The other block is still served from the cache, the invalidated one hits the provider
+        assert!(cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 2).await.is_ok());
+        assert!(cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 1).await.is_err());
+        assert_eq!(cache.block_env_map.len(), 1);
+    }
+
+    /// A transport serving blocks with the given hash
+    fn block_hash_transport(hash: B256) -> MockTransport {
+        MockTransport::new(move |method, _| {
+            assert_eq!(method, "eth_getBlockByNumber");
+            let mut block: Block = Block::default();
+            block.header.hash = Some(hash);
+            success(&block)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_check_reorg() {
+        let live = B256::repeat_byte(2);
+        let provider =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(block_hash_transport(live), true));
+        let cache = EnvironmentCache::default();
+        cache.set_latest_block_number(FAKE_FORK_URL, 11);
+
+        assert_eq!(
+            cache.check_reorg(&provider, FAKE_FORK_URL, 10).await.unwrap(),
+            ReorgStatus::NotCached
+        );
+
+        let block_env_with_hash = |hash| {
+            let mut block = Block::default();
+            block.header.hash = Some(hash);
+            BlockEnvironment::new(Some(block), 1)
+        };
+        cache.insert_block_env(false, FAKE_FORK_URL, 10, block_env_with_hash(live));
+        assert_eq!(
+            cache.check_reorg(&provider, FAKE_FORK_URL, 10).await.unwrap(),
+            ReorgStatus::Consistent
+        );
+
+        let cached = B256::repeat_byte(1);
+        for block_number in [9, 10, 11] {
+            cache.insert_block_env(false, FAKE_FORK_URL, block_number, block_env_with_hash(cached));
+        }
+        // Blocks past the latest known block number and other fork urls are tracked too
+        cache.insert_block_env(true, FAKE_FORK_URL, 1_000, block_env_with_hash(cached));
+        cache.insert_block_env(false, "http://other.com", 10, block_env_with_hash(cached));
+        assert_eq!(
+            cache.check_reorg(&provider, FAKE_FORK_URL, 10).await.unwrap(),
+            ReorgStatus::Reorged { cached, live }
+        );
+
+        // The reorged block and the ones after it are invalidated
+        assert!(cache.peek_block_env(FAKE_FORK_URL, 9).is_some());
+        assert!(cache.peek_block_env(FAKE_FORK_URL, 10).is_none());
+        assert!(cache.peek_block_env(FAKE_FORK_URL, 11).is_none());
+        assert!(cache.full_block_env_map.is_empty());
+        assert!(cache.peek_block_env("http://other.com", 10).is_some());
+    }
+
+    /// A transport serving any block hash at block 10, with the given hash as the canonical one
+    fn canonical_hash_transport(canonical: B256) -> MockTransport {
+        MockTransport::new(move |method, params| {
+            let mut block: Block = Block::default();
+            block.header.number = Some(10);
+            match method {
+                "eth_getBlockByHash" => {
+                    block.header.hash = Some(serde_json::from_value(params[0].clone()).unwrap());
+                    success(&block)
+                }
+                "eth_getBlockByNumber" => {
+                    block.header.hash = Some(canonical);
+                    success(&block)
+                }
+                "eth_gasPrice" => success(&U256::from(1)),
+                method => unimplemented!("{method}"),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_get_block_env_by_hash() {
+        let canonical = B256::repeat_byte(1);
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            canonical_hash_transport(canonical),
+            true,
+        ));
+        let cache = EnvironmentCache::default();
+
+        let by_hash =
+            cache.get_block_env_by_hash(&provider, FAKE_FORK_URL, canonical).await.unwrap();
+        assert_eq!(by_hash.block.as_ref().unwrap().header.hash, Some(canonical));
+        assert_eq!(cache.stats().misses, 1);
+
+        // Fetching by number afterwards hits the same cache slot
+        let by_number = cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 10).await.unwrap();
+        assert_eq!(by_number, by_hash);
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().entries, 1);
+
+        // A block that was reorged out is not cached
+        let reorged = B256::repeat_byte(2);
+        assert!(cache.get_block_env_by_hash(&provider, FAKE_FORK_URL, reorged).await.is_err());
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(cache.peek_block_env(FAKE_FORK_URL, 10), Some(by_hash));
+    }
+
+    #[test]
+    fn test_peek_block_env() {
+        let cache = EnvironmentCache::default();
+        let block_env = BlockEnvironment::new(None, 1);
+        cache.block_env_map.insert((FAKE_FORK_URL.to_string(), 1), block_env.clone());
+
+        assert_eq!(cache.peek_block_env(FAKE_FORK_URL, 1), Some(block_env));
+        assert_eq!(cache.peek_block_env(FAKE_FORK_URL, 2), None);
+        assert_eq!(cache.peek_block_env("http://other.com", 1), None);
+        assert_eq!(cache.block_env_map.len(), 1);
+    }
+
+    /// A transport serving the given chain id, failing every request if it's `None`
+    fn chain_id_transport(chain_id: Option<u64>) -> MockTransport {
+        MockTransport::new(move |method, _| match (method, chain_id) {
+            ("eth_chainId", Some(chain_id)) => success(&U256::from(chain_id)),
+            (method, _) => failure(-32000, format!("{method} is unavailable")),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("environment-cache.json.gz");
+        let chain_id_provider =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(chain_id_transport(Some(10)), true));
+        let block_provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(10), Some(100)),
+            true,
+        ));
+
+        let cache = EnvironmentCache::default();
+        assert_eq!(cache.get_chain_id(&chain_id_provider, FAKE_FORK_URL).await.unwrap(), 10);
+        let block_env =
+            cache.get_block_env_by_number(&block_provider, FAKE_FORK_URL, 5).await.unwrap();
+        cache.set_latest_block_number(FAKE_FORK_URL, 6);
+        cache.save_to_path(&path).unwrap();
+        drop(cache);
+
+        // The chain id and block environment are served without reaching the provider, the latest
+        // block number is not restored
+        let offline =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(chain_id_transport(None), true));
+        let cache = EnvironmentCache::default().load_from_path(&path).unwrap();
+        assert_eq!(cache.get_chain_id(&offline, FAKE_FORK_URL).await.unwrap(), 10);
+        assert_eq!(cache.get_fork_info(&offline, FAKE_FORK_URL, 5).await.unwrap(), (10, block_env));
+        assert!(cache.latest_block_map.is_empty());
+        assert!(cache.get_chain_id(&offline, "http://other.com").await.is_err());
+    }
+
+    #[test]
+    fn test_load_with_capacity() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("environment-cache.json");
+
+        let cache = EnvironmentCache::default();
+        for block_number in 0..10 {
+            cache.insert_block_env(false, FAKE_FORK_URL, block_number, Default::default());
+        }
+        cache.save_to_path(&path).unwrap();
+
+        // The cache is loaded with the given configuration, evicting what doesn't fit
+        let evictions = Arc::new(AtomicUsize::new(0));
+        let cache = EnvironmentCache::with_capacity(4)
+            .with_eviction_listener({
+                let evictions = Arc::clone(&evictions);
+                move |_, _| {
+                    evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .load_from_path(&path)
+            .unwrap();
+        assert!(cache.block_env_map.len() <= 4);
+        assert_eq!(evictions.load(Ordering::Relaxed) + cache.block_env_map.len(), 10);
+
+        // The keys of evicted block environments are forgotten
+        assert_eq!(cache.block_env_keys.len(), cache.block_env_map.len());
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_block_range() {
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(10), Some(100)),
+            true,
+        ));
+        let offline =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(chain_id_transport(None), true));
+        let cache = EnvironmentCache::default();
+
+        cache.get_block_env_by_number(&provider, FAKE_FORK_URL, 5).await.unwrap();
+        assert_eq!(cache.prefetch_block_range(&provider, FAKE_FORK_URL, 5, 9).await.unwrap(), 4);
+        assert_eq!(cache.stats().entries, 5);
+
+        // Every block of the range is served from the cache
+        for block_number in 5..=9 {
+            cache.get_block_env_by_number(&offline, FAKE_FORK_URL, block_number).await.unwrap();
+        }
+        assert_eq!(cache.stats().hits, 5);
+        assert_eq!(cache.prefetch_block_range(&offline, FAKE_FORK_URL, 5, 9).await.unwrap(), 0);
+
+        // Failed blocks are reported together, the others are still cached
+        let err = cache.prefetch_block_range(&offline, FAKE_FORK_URL, 9, 11).await.unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("failed to prefetch 2 of 2"), "{err}");
+        assert!(err.contains("block 10:") && err.contains("block 11:"), "{err}");
+        assert_eq!(cache.stats().entries, 5);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_block_range_bounds() {
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            gas_price_transport(Some(10), Some(100)),
+            true,
+        ));
+        let cache = EnvironmentCache::default().with_max_prefetch_span(3);
+
+        let err = cache.prefetch_block_range(&provider, FAKE_FORK_URL, 9, 5).await.unwrap_err();
+        assert!(err.to_string().contains("the start is after the end"), "{err}");
+        let err = cache.prefetch_block_range(&provider, FAKE_FORK_URL, 5, 8).await.unwrap_err();
+        assert!(err.to_string().contains("spans 4 blocks, more than the maximum of 3"), "{err}");
+        assert_eq!(cache.stats().entries, 0);
+
+        // The bounds are inclusive, a single block spans one block
+        assert_eq!(cache.prefetch_block_range(&provider, FAKE_FORK_URL, 5, 7).await.unwrap(), 3);
+        assert_eq!(cache.prefetch_block_range(&provider, FAKE_FORK_URL, 9, 9).await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_block_environment_blob_fields() {
+        let mut cancun_block = Block::default();
+        cancun_block.header.excess_blob_gas = Some(0x60000);
+        cancun_block.header.blob_gas_used = Some(0x20000);
+
+        let block_env = BlockEnvironment::new(Some(cancun_block), 1);
+        assert_eq!(block_env.excess_blob_gas, Some(0x60000));
+        assert_eq!(block_env.blob_gas_used, Some(0x20000));
+
+        let block_env = BlockEnvironment::new(Some(Block::default()), 1);
+        assert_eq!(block_env.excess_blob_gas, None);
+        assert_eq!(block_env.blob_gas_used, None);
+    }
+
+    #[test]
+    fn test_block_environment_approx_eq() {
+        let mut block = Block::default();
+        block.header.number = Some(1_000_000);
+
+        let block_env = BlockEnvironment::new(Some(block.clone()), 100);
+        assert!(block_env.approx_eq(&BlockEnvironment::new(Some(block.clone()), 105), 5));
+        assert!(block_env.approx_eq(&BlockEnvironment::new(Some(block.clone()), 95), 5));
+        assert!(!block_env.approx_eq(&BlockEnvironment::new(Some(block.clone()), 106), 5));
+
+        let mut other_block = block;
+        other_block.header.number = Some(1_000_001);
+        assert!(!block_env.approx_eq(&BlockEnvironment::new(Some(other_block), 100), 5));
+        assert!(!block_env.approx_eq(&BlockEnvironment::new(None, 100), 5));
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_latest_block_number() {
         let cache = EnvironmentCache::default();
@@ -189,12 +1751,288 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_latest_block_listener() {
+        let advances = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let cache = EnvironmentCache::default().with_latest_block_listener({
+            let advances = advances.clone();
+            move |fork_url, block_number| {
+                advances.lock().unwrap().push((fork_url.to_string(), block_number))
+            }
+        });
+
+        cache.set_latest_block_number(FAKE_FORK_URL, 100);
+        cache.set_latest_block_number(FAKE_FORK_URL, 101);
+        cache.set_latest_block_number(FAKE_FORK_URL, 101);
+        cache.set_latest_block_number(FAKE_FORK_URL, 99);
+        cache.set_latest_block_number(FAKE_FORK_URL, 105);
+
+        assert_eq!(
+            *advances.lock().unwrap(),
+            vec![(FAKE_FORK_URL.to_string(), 101), (FAKE_FORK_URL.to_string(), 105)]
+        );
+    }
+
+    #[test]
+    fn test_latest_block_delta() {
+        let cache = EnvironmentCache::default();
+        assert_eq!(cache.latest_block_delta(FAKE_FORK_URL, 100), None);
+
+        cache.set_latest_block_number(FAKE_FORK_URL, 142);
+        assert_eq!(cache.latest_block_delta(FAKE_FORK_URL, 100), Some(42));
+        assert_eq!(cache.latest_block_delta(FAKE_FORK_URL, 142), Some(0));
+        assert_eq!(cache.latest_block_delta(FAKE_FORK_URL, 150), Some(-8));
+
+        // The delta is against the actual head, not the pinned one
+        cache.pin_latest(FAKE_FORK_URL);
+        cache.set_latest_block_number(FAKE_FORK_URL, 145);
+        assert_eq!(cache.latest_block_delta(FAKE_FORK_URL, 100), Some(45));
+    }
+
+    /// A transport serving a latest block number that advances by one on every request, starting
+    /// at 100
+    fn advancing_head_transport() -> MockTransport {
+        let head = Arc::new(std::sync::atomic::AtomicU64::new(100));
+        MockTransport::new(move |method, _| {
+            assert_eq!(method, "eth_blockNumber");
+            success(&U256::from(head.fetch_add(1, std::sync::atomic::Ordering::SeqCst)))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_latest_block_refresh() {
+        let provider =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(advancing_head_transport(), true));
+        let cache = Arc::new(EnvironmentCache::default());
+        let latest = || cache.latest_block_map.get(FAKE_FORK_URL).map(|latest| latest.0);
+
+        let refresh =
+            cache.spawn_latest_block_refresh(provider, FAKE_FORK_URL, Duration::from_millis(10));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while latest().map_or(true, |latest| latest < 102) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("the latest block number was not refreshed");
+
+        // No more refreshes once canceled
+        refresh.cancel();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let canceled = latest();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(latest(), canceled);
+    }
+
+    /// A transport serving a slow latest block number
+    fn slow_head_transport() -> MockTransport {
+        MockTransport::new(|method, _| {
+            assert_eq!(method, "eth_blockNumber");
+            success(&U256::from(100))
+        })
+        .with_delay(Duration::from_millis(20))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_max_concurrent_refreshes() {
+        let transport = slow_head_transport();
+        let cache = Arc::new(EnvironmentCache::default().with_max_concurrent_refreshes(2));
+
+        let _refreshes = (0..6)
+            .map(|i| {
+                let provider =
+                    RootProvider::<_, Ethereum>::new(RpcClient::new(transport.clone(), true));
+                cache.spawn_latest_block_refresh(
+                    provider,
+                    &format!("{FAKE_FORK_URL}/{i}"),
+                    Duration::from_millis(5),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while transport.requests() < 20 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("the latest block numbers were not refreshed");
+
+        assert_eq!(transport.peak_in_flight(), 2);
+        assert!(
+            (0..6).all(|i| cache.latest_block_map.contains_key(&format!("{FAKE_FORK_URL}/{i}")))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_latest_block_ttl() {
+        let provider =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(advancing_head_transport(), true));
+        let start = Instant::now();
+        let elapsed = Arc::new(std::sync::Mutex::new(Duration::ZERO));
+        let clock = {
+            let elapsed = elapsed.clone();
+            move || start + *elapsed.lock().unwrap()
+        };
+        let advance = |by: Duration| *elapsed.lock().unwrap() += by;
+
+        let cache = EnvironmentCache::default()
+            .with_latest_block_ttl(Some(Duration::from_secs(10)))
+            .with_clock(clock);
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 100);
+
+        // Served from the cache until the ttl expires
+        advance(Duration::from_secs(9));
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 100);
+        advance(Duration::from_secs(1));
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 101);
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 101);
+
+        // Pinned latest block numbers never expire
+        cache.pin_latest(FAKE_FORK_URL);
+        advance(Duration::from_secs(60));
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 101);
+
+        // Without a ttl, the latest block number is cached forever
+        let cache =
+            EnvironmentCache::default().with_clock(move || start + Duration::from_secs(3600));
+        cache.set_latest_block_number(FAKE_FORK_URL, 50);
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_pin_latest() {
+        let provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
+        let cache = EnvironmentCache::default();
+
+        // Nothing to pin before the latest block number is known
+        assert_eq!(cache.pin_latest(FAKE_FORK_URL), None);
+
+        cache.set_latest_block_number(FAKE_FORK_URL, 100);
+        assert_eq!(cache.pin_latest(FAKE_FORK_URL), Some(100));
+
+        // The pinned block number is returned even though the head advanced
+        cache.set_latest_block_number(FAKE_FORK_URL, 105);
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 100);
+        assert_eq!(cache.pin_latest(FAKE_FORK_URL), Some(100));
+
+        cache.unpin_latest(FAKE_FORK_URL);
+        assert_eq!(cache.get_latest_block_number(&provider, FAKE_FORK_URL).await.unwrap(), 105);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_latest_block_number_eager_chain_id() {
+        let fork_url = fork_url();
+        let good_provider = ProviderBuilder::new(&fork_url).build().unwrap();
+
+        let bad_provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
+
+        let cache = EnvironmentCache::default().with_eager_chain_id(true);
+        cache.get_latest_block_number(&good_provider, &fork_url).await.unwrap();
+
+        // The chain id was resolved along with the latest block number
+        assert_eq!(cache.get_chain_id(&bad_provider, &fork_url).await.unwrap(), 1);
+
+        // Not resolved in lazy mode
+        let cache = EnvironmentCache::default();
+        cache.get_latest_block_number(&good_provider, &fork_url).await.unwrap();
+        assert!(cache.get_chain_id(&bad_provider, &fork_url).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_earliest_block_number() {
+        let fork_url = fork_url();
+        let good_provider = ProviderBuilder::new(&fork_url).build().unwrap();
+
+        let bad_provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
+
+        let cache = EnvironmentCache::default();
+
+        // Fails with bad provider
+        assert!(cache.get_earliest_block_number(&bad_provider, &fork_url).await.is_err());
+
+        // Succeeds with good provider, caches the earliest block
+        let earliest = cache.get_earliest_block_number(&good_provider, &fork_url).await.unwrap();
+
+        // Succeeds with bad provider, returns the cached earliest block without probing
+        assert_eq!(
+            cache.get_earliest_block_number(&bad_provider, &fork_url).await.unwrap(),
+            earliest
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_block_number_by_timestamp() {
+        let fork_url = fork_url();
+        let provider = ProviderBuilder::new(&fork_url).build().unwrap();
+
+        let cache = EnvironmentCache::default();
+        cache.set_latest_block_number(&fork_url, 1_000_100);
+
+        // Timestamp of mainnet block 1_000_000
+        let timestamp = 1_455_404_053;
+        assert_eq!(
+            cache.get_block_number_by_timestamp(&provider, &fork_url, timestamp).await.unwrap(),
+            1_000_000
+        );
+        assert_eq!(
+            cache.get_block_number_by_timestamp(&provider, &fork_url, timestamp + 1).await.unwrap(),
+            1_000_000
+        );
+
+        // Future timestamps clamp to the latest block
+        assert_eq!(
+            cache.get_block_number_by_timestamp(&provider, &fork_url, u64::MAX).await.unwrap(),
+            1_000_100
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_block_number_by_timestamp_bounds() {
+        // A pruned node serving blocks 100 to 200, 12 seconds apart
+        let provider = RootProvider::<_, Ethereum>::new(RpcClient::new(
+            MockTransport::new(|method, params| match method {
+                "eth_blockNumber" => success(&U256::from(200)),
+                "eth_getBlockByNumber" => {
+                    let number = match &params[0] {
+                        serde_json::Value::String(tag) if tag == "earliest" => 100,
+                        number => serde_json::from_value::<U256>(number.clone()).unwrap().to(),
+                    };
+                    if !(100..=200).contains(&number) {
+                        return success(&None::<Block>);
+                    }
+                    let mut block: Block = Block::default();
+                    block.header.number = Some(number);
+                    block.header.timestamp = number * 12;
+                    success(&block)
+                }
+                method => method_not_found(method),
+            }),
+            true,
+        ));
+        let cache = EnvironmentCache::default();
+        let block_at =
+            |timestamp| cache.get_block_number_by_timestamp(&provider, FAKE_FORK_URL, timestamp);
+
+        // The earliest block is part of the range
+        assert_eq!(block_at(1200).await.unwrap(), 100);
+        assert_eq!(block_at(1211).await.unwrap(), 100);
+        assert_eq!(block_at(1212).await.unwrap(), 101);
+        // Timestamps before the earliest block resolve to it
+        assert_eq!(block_at(1199).await.unwrap(), 100);
+        assert_eq!(block_at(0).await.unwrap(), 100);
+        // Timestamps at or after the latest block resolve to it
+        assert_eq!(block_at(2399).await.unwrap(), 199);
+        assert_eq!(block_at(2400).await.unwrap(), 200);
+        assert_eq!(block_at(u64::MAX).await.unwrap(), 200);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_get_fork_info() {
         let fork_url = fork_url();
         let good_provider = ProviderBuilder::new(&fork_url).build().unwrap();
 
-        let bad_provider = ProviderBuilder::new(&FAKE_FORK_URL).build().unwrap();
+        let bad_provider = ProviderBuilder::new(FAKE_FORK_URL).build().unwrap();
 
         let cache = EnvironmentCache::default();
 
@@ -212,4 +2050,24 @@ mod tests {
         assert_eq!(chain_id, 1);
         assert_eq!(block_env_0, block_env_1);
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_fork_info_expected_chain_id() {
+        let fork_url = fork_url();
+        let provider = ProviderBuilder::new(&fork_url).build().unwrap();
+
+        let cache = EnvironmentCache::default();
+        cache.set_expected_chain_id(&fork_url, 10);
+
+        // Fails with a provider on another chain, caching nothing
+        assert!(cache.get_fork_info(&provider, &fork_url, 1_000_000).await.is_err());
+        assert!(cache.chain_ids_by_fork_url.is_empty());
+        assert!(cache.block_env_map.is_empty());
+
+        // Succeeds once the expectation matches
+        cache.set_expected_chain_id(&fork_url, 1);
+        let (chain_id, _) = cache.get_fork_info(&provider, &fork_url, 1_000_000).await.unwrap();
+        assert_eq!(chain_id, 1);
+        assert_eq!(cache.block_env_map.len(), 1);
+    }
 }
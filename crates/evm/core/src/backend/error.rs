@@ -1,5 +1,6 @@
 use alloy_primitives::{Address, B256, U256};
 use alloy_rpc_types::BlockId;
+use alloy_transport::{TransportError, TransportErrorKind};
 use futures::channel::mpsc::{SendError, TrySendError};
 use revm::primitives::EVMError;
 use std::{
@@ -83,6 +84,34 @@ impl DatabaseError {
         }
     }
 
+    /// Whether the error is a transient failure of a remote read that may succeed when retried,
+    /// i.e. a transport failure, e.g. a dropped connection or a server error, or a rate limit.
+    ///
+    /// JSON-RPC error responses other than rate limits, e.g. `header not found` or an execution
+    /// revert, as well as not-found, decoding and non-archive node errors are considered terminal.
+    pub fn is_retryable(&self) -> bool {
+        if self.is_possibly_non_archive_node_error() {
+            return false;
+        }
+        self.get_rpc_error()
+            .and_then(|err| err.chain().find_map(|err| err.downcast_ref::<TransportError>()))
+            .is_some_and(|err| match err {
+                TransportError::Transport(kind) => match kind {
+                    TransportErrorKind::HttpError(err) => {
+                        err.status == 429 || err.status >= 500 || is_rate_limit(&err.body)
+                    }
+                    TransportErrorKind::MissingBatchResponse(_) |
+                    TransportErrorKind::BackendGone |
+                    TransportErrorKind::Custom(_) => true,
+                    _ => false,
+                },
+                TransportError::ErrorResp(payload) => {
+                    payload.code == 429 || payload.code == -32005 || is_rate_limit(&payload.message)
+                }
+                _ => false,
+            })
+    }
+
     /// Whether the error is potentially caused by the user forking from an older block in a
     /// non-archive node.
     pub fn is_possibly_non_archive_node_error(&self) -> bool {
@@ -94,6 +123,12 @@ impl DatabaseError {
     }
 }
 
+/// Whether the given error message of a provider says the request was rate limited
+fn is_rate_limit(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("rate limit") || message.contains("too many requests")
+}
+
 impl From<tokio::task::JoinError> for DatabaseError {
     fn from(value: tokio::task::JoinError) -> Self {
         Self::display(value)
@@ -26,10 +26,13 @@ use revm::{
     Database, DatabaseCommit, JournaledState,
 };
 
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::Instant,
 };
 
@@ -48,14 +51,39 @@ pub use in_memory_db::{EmptyDBWrapper, FoundryEvmInMemoryDB, MemDb};
 mod snapshot;
 pub use snapshot::{BackendSnapshot, RevertSnapshotAction, StateSnapshot};
 
+mod access_log;
+pub use access_log::{AccessLog, AccessLogEntry, AccessValue};
+
 mod data_access;
-pub use data_access::{Access, AccessType, RevmDbAccess, StateLookup};
+pub use data_access::{
+    access_table, dedup_by_location, intersect, merge_accesses, minimize_accesses,
+    minimize_accesses_at, read_accesses, retag_chain, write_accesses, Access, AccessPlan,
+    AccessRecorder, AccessSink, AccessSummary, AccessType, AccessTypeMask, ChainEnvAdjustments,
+    DataAccesses, DefaultAccessRecorder, EnvironmentAccess, EnvironmentRecorder,
+    LoadAccessesOptions, LoadAccessesOutcome, RequestUnitWeights, RevmDbAccess, StateLookup,
+    TransientStorageAccess, TransientStorageRecorder,
+};
 
 mod environment_cache;
-pub use environment_cache::{BlockEnvironment, EnvironmentCache};
+pub use environment_cache::{
+    BlockEnvDiscrepancy, BlockEnvironment, BlockEnvironmentWeighter, EnvironmentCache,
+    FieldDiscrepancy, LatestBlockRefresh, ReorgStatus, DEFAULT_GAS_PRICE_FALLBACK_TIP,
+    DEFAULT_MAX_CONCURRENT_REFRESHES,
+};
 
 mod code_cache;
-pub use code_cache::CodeCache;
+pub use code_cache::{CacheHit, CodeCache, InconsistentCodeError};
+
+mod cache_stats;
+pub use cache_stats::CacheStats;
+
+mod compression;
+
+mod reproducer;
+pub use reproducer::ReproducerBundle;
+
+mod retry;
+pub use retry::RetryPolicy;
 
 // A `revm::Database` that is used in forking mode
 type ForkDB = CacheDB<SharedBackend>;
@@ -429,12 +457,27 @@ pub struct Backend {
     /// holds additional Backend data
     inner: BackendInner,
     /// The data accesses made by this backend instance.
-    pub data_accesses: Arc<dashmap::DashSet<Access>>,
+    pub data_accesses: Arc<DataAccesses>,
+    /// The block hashes returned for the block hash accesses made by this backend instance.
+    pub block_hash_values: Arc<dashmap::DashMap<Access, B256>>,
 
     pub environment_cache: Arc<EnvironmentCache>,
 
     pub code_cache: Arc<CodeCache>,
 }
+
+/// The caches of a [`Backend`], which can be shared across backends for higher hit rates, see
+/// [`Backend::spawn_with_caches`].
+///
+/// The default context holds fresh caches.
+#[derive(Clone, Debug, Default)]
+pub struct CacheContext {
+    /// The cache of account code
+    pub code: Arc<CodeCache>,
+    /// The cache of block environments and block numbers
+    pub env: Arc<EnvironmentCache>,
+}
+
 // === impl Backend ===
 
 impl Backend {
@@ -446,6 +489,12 @@ impl Backend {
         Self::new(MultiFork::spawn(), fork)
     }
 
+    /// Creates a new Backend with a spawned multi fork thread, like [`Backend::spawn`], using the
+    /// caches of the given context, e.g. to share them across many backends
+    pub fn spawn_with_caches(fork: Option<CreateFork>, caches: CacheContext) -> Self {
+        Self::new_with_caches(MultiFork::spawn(), fork, caches)
+    }
+
     /// Creates a new instance of `Backend`
     ///
     /// If `fork` is `Some` this will use a `fork` database, otherwise with an in-memory
@@ -453,6 +502,17 @@ impl Backend {
     ///
     /// Prefer using [`spawn`](Self::spawn) instead.
     pub fn new(forks: MultiFork, fork: Option<CreateFork>) -> Self {
+        Self::new_with_caches(forks, fork, CacheContext::default())
+    }
+
+    /// Creates a new instance of `Backend` using the caches of the given context
+    ///
+    /// Prefer using [`spawn_with_caches`](Self::spawn_with_caches) instead.
+    pub fn new_with_caches(
+        forks: MultiFork,
+        fork: Option<CreateFork>,
+        caches: CacheContext,
+    ) -> Self {
         trace!(target: "backend", forking_mode=?fork.is_some(), "creating executor backend");
         // Note: this will take of registering the `fork`
         let inner = BackendInner {
@@ -467,8 +527,9 @@ impl Backend {
             active_fork_ids: None,
             inner,
             data_accesses: Default::default(),
-            environment_cache: Arc::new(EnvironmentCache::default()),
-            code_cache: Arc::new(CodeCache::default()),
+            block_hash_values: Default::default(),
+            environment_cache: caches.env,
+            code_cache: caches.code,
         };
 
         if let Some(fork) = fork {
@@ -478,6 +539,7 @@ impl Backend {
                     fork,
                     backend.environment_cache.clone(),
                     backend.data_accesses.clone(),
+                    backend.block_hash_values.clone(),
                     backend.code_cache.clone(),
                 )
                 .expect("Unable to create fork");
@@ -516,6 +578,7 @@ impl Backend {
             active_fork_ids: None,
             inner: Default::default(),
             data_accesses: Default::default(),
+            block_hash_values: Default::default(),
             environment_cache: self.environment_cache.clone(),
             code_cache: self.code_cache.clone(),
         }
@@ -699,6 +762,128 @@ impl Backend {
         )
     }
 
+    /// Selects the given fork like [DatabaseExt::select_fork], but outside of a test, e.g. when
+    /// tracing, where no test contract is set.
+    ///
+    /// Only the state of the persistent accounts and the caller is carried over to the selected
+    /// fork, without making the caller a persistent account.
+    pub fn select_fork_outside_test(
+        &mut self,
+        id: LocalForkId,
+        env: &mut Env,
+        journaled_state: &mut JournaledState,
+    ) -> eyre::Result<()> {
+        self.select_fork_with(id, env, journaled_state, false)
+    }
+
+    /// Selects the given fork, see [DatabaseExt::select_fork].
+    ///
+    /// Within a test, the state of the persistent accounts, including the test contract, is
+    /// carried over to the selected fork. Outside of a test there's no test contract, so only the
+    /// persistent accounts are.
+    fn select_fork_with(
+        &mut self,
+        id: LocalForkId,
+        env: &mut Env,
+        active_journaled_state: &mut JournaledState,
+        in_test: bool,
+    ) -> eyre::Result<()> {
+        trace!(?id, "select fork");
+        if self.is_active_fork(id) {
+            // nothing to do
+            return Ok(());
+        }
+
+        let fork_id = self.ensure_fork_id(id).cloned()?;
+        let idx = self.inner.ensure_fork_index(&fork_id)?;
+        let fork_env = self
+            .forks
+            .get_env(fork_id)?
+            .ok_or_else(|| eyre::eyre!("Requested fork `{}` does not exit", id))?;
+
+        // If we're currently in forking mode we need to update the journaled_state to this point,
+        // this ensures the changes performed while the fork was active are recorded
+        if let Some(active) = self.active_fork_mut() {
+            active.journaled_state = active_journaled_state.clone();
+
+            let caller = env.tx.caller;
+            let caller_account = active.journaled_state.state.get(&env.tx.caller).cloned();
+            let target_fork = self.inner.get_fork_mut(idx);
+
+            // depth 0 will be the default value when the fork was created
+            if target_fork.journaled_state.depth == 0 {
+                // Initialize caller with its fork info
+                if let Some(mut acc) = caller_account {
+                    let fork_account = Database::basic(&mut target_fork.db, caller)?
+                        .ok_or(DatabaseError::MissingAccount(caller))?;
+
+                    acc.info = fork_account;
+                    target_fork.journaled_state.state.insert(caller, acc);
+                }
+            }
+        } else {
+            // this is the first time a fork is selected. This means up to this point all changes
+            // are made in a single `JournaledState`, for example after a `setup` that only created
+            // different forks. Since the `JournaledState` is valid for all forks until the
+            // first fork is selected, we need to update it for all forks and use it as init state
+            // for all future forks
+
+            self.set_init_journaled_state(active_journaled_state.clone());
+            self.prepare_init_journal_state()?;
+
+            // Make sure that the next created fork has a depth of 0.
+            self.fork_init_journaled_state.depth = 0;
+        }
+
+        {
+            // update the shared state and track
+            let mut fork = self.inner.take_fork(idx);
+
+            // since all forks handle their state separately, the depth can drift
+            // this is a handover where the target fork starts at the same depth where it was
+            // selected. This ensures that there are no gaps in depth which would
+            // otherwise cause issues with the tracer
+            fork.journaled_state.depth = active_journaled_state.depth;
+
+            // another edge case where a fork is created and selected during setup with not
+            // necessarily the same caller as for the test, however we must always
+            // ensure that fork's state contains the current sender
+            let caller = env.tx.caller;
+            fork.journaled_state.state.entry(caller).or_insert_with(|| {
+                let caller_account = active_journaled_state
+                    .state
+                    .get(&env.tx.caller)
+                    .map(|acc| acc.info.clone())
+                    .unwrap_or_default();
+
+                if !fork.db.accounts.contains_key(&caller) {
+                    // update the caller account which is required by the evm
+                    fork.db.insert_account_info(caller, caller_account.clone());
+                }
+                caller_account.into()
+            });
+
+            if in_test {
+                self.update_fork_db(active_journaled_state, &mut fork);
+            } else {
+                self.update_fork_db_contracts(
+                    self.inner.persistent_accounts.iter().copied(),
+                    active_journaled_state,
+                    &mut fork,
+                );
+            }
+
+            // insert the fork back
+            self.inner.set_fork(idx, fork);
+        }
+
+        self.active_fork_ids = Some((id, idx));
+        // update the environment accordingly
+        update_current_env_with_fork_env(env, fork_env);
+
+        Ok(())
+    }
+
     /// Merges the state of all `accounts` from the currently active db into the given `fork`
     pub(crate) fn update_fork_db_contracts(
         &self,
@@ -1069,17 +1254,25 @@ impl DatabaseExt for Backend {
             create_fork.clone(),
             Arc::clone(&self.environment_cache),
             Arc::clone(&self.data_accesses),
+            Arc::clone(&self.block_hash_values),
             Arc::clone(&self.code_cache),
         )?;
 
         // All Create Forks roll to specific blocks as currently implemented
         let state_lookup: StateLookup = (&create_fork).into();
 
-        self.data_accesses.insert(Access {
-            chain: env.cfg.chain_id.into(),
-            state_lookup,
-            access_type: AccessType::CreateFork(create_fork.url.to_owned()),
-        });
+        self.data_accesses.record_at(
+            Access {
+                chain: env.cfg.chain_id.into(),
+                state_lookup,
+                access_type: AccessType::CreateFork {
+                    url: create_fork.url,
+                    env_adjustments: Some(Box::new(ChainEnvAdjustments::from_env(&env))),
+                    block_number: Some(fork.block_number()),
+                },
+            },
+            fork.block_number(),
+        );
 
         let fork_db = ForkDB::new(fork);
         let (id, _) =
@@ -1120,92 +1313,7 @@ impl DatabaseExt for Backend {
         env: &mut Env,
         active_journaled_state: &mut JournaledState,
     ) -> eyre::Result<()> {
-        trace!(?id, "select fork");
-        if self.is_active_fork(id) {
-            // nothing to do
-            return Ok(());
-        }
-
-        let fork_id = self.ensure_fork_id(id).cloned()?;
-        let idx = self.inner.ensure_fork_index(&fork_id)?;
-        let fork_env = self
-            .forks
-            .get_env(fork_id)?
-            .ok_or_else(|| eyre::eyre!("Requested fork `{}` does not exit", id))?;
-
-        // If we're currently in forking mode we need to update the journaled_state to this point,
-        // this ensures the changes performed while the fork was active are recorded
-        if let Some(active) = self.active_fork_mut() {
-            active.journaled_state = active_journaled_state.clone();
-
-            let caller = env.tx.caller;
-            let caller_account = active.journaled_state.state.get(&env.tx.caller).cloned();
-            let target_fork = self.inner.get_fork_mut(idx);
-
-            // depth 0 will be the default value when the fork was created
-            if target_fork.journaled_state.depth == 0 {
-                // Initialize caller with its fork info
-                if let Some(mut acc) = caller_account {
-                    let fork_account = Database::basic(&mut target_fork.db, caller)?
-                        .ok_or(DatabaseError::MissingAccount(caller))?;
-
-                    acc.info = fork_account;
-                    target_fork.journaled_state.state.insert(caller, acc);
-                }
-            }
-        } else {
-            // this is the first time a fork is selected. This means up to this point all changes
-            // are made in a single `JournaledState`, for example after a `setup` that only created
-            // different forks. Since the `JournaledState` is valid for all forks until the
-            // first fork is selected, we need to update it for all forks and use it as init state
-            // for all future forks
-
-            self.set_init_journaled_state(active_journaled_state.clone());
-            self.prepare_init_journal_state()?;
-
-            // Make sure that the next created fork has a depth of 0.
-            self.fork_init_journaled_state.depth = 0;
-        }
-
-        {
-            // update the shared state and track
-            let mut fork = self.inner.take_fork(idx);
-
-            // since all forks handle their state separately, the depth can drift
-            // this is a handover where the target fork starts at the same depth where it was
-            // selected. This ensures that there are no gaps in depth which would
-            // otherwise cause issues with the tracer
-            fork.journaled_state.depth = active_journaled_state.depth;
-
-            // another edge case where a fork is created and selected during setup with not
-            // necessarily the same caller as for the test, however we must always
-            // ensure that fork's state contains the current sender
-            let caller = env.tx.caller;
-            fork.journaled_state.state.entry(caller).or_insert_with(|| {
-                let caller_account = active_journaled_state
-                    .state
-                    .get(&env.tx.caller)
-                    .map(|acc| acc.info.clone())
-                    .unwrap_or_default();
-
-                if !fork.db.accounts.contains_key(&caller) {
-                    // update the caller account which is required by the evm
-                    fork.db.insert_account_info(caller, caller_account.clone());
-                }
-                caller_account.into()
-            });
-
-            self.update_fork_db(active_journaled_state, &mut fork);
-
-            // insert the fork back
-            self.inner.set_fork(idx, fork);
-        }
-
-        self.active_fork_ids = Some((id, idx));
-        // update the environment accordingly
-        update_current_env_with_fork_env(env, fork_env);
-
-        Ok(())
+        self.select_fork_with(id, env, active_journaled_state, true)
     }
 
     /// This is effectively the same as [`Self::create_select_fork()`] but updating an existing
@@ -1224,6 +1332,7 @@ impl DatabaseExt for Backend {
             block_number,
             Arc::clone(&self.environment_cache),
             Arc::clone(&self.data_accesses),
+            Arc::clone(&self.block_hash_values),
             Arc::clone(&self.code_cache),
         )?;
         // this will update the local mapping
@@ -2017,6 +2126,7 @@ impl Clone for Backend {
             mem_db: self.mem_db.clone(),
             forks: self.forks.clone(),
             data_accesses: Default::default(),
+            block_hash_values: Default::default(),
             fork_init_journaled_state: self.fork_init_journaled_state.clone(),
             active_fork_ids: self.active_fork_ids,
             environment_cache: Arc::clone(&self.environment_cache),
@@ -2034,34 +2144,391 @@ impl Backend {
         accesses
     }
 
+    /// Returns the block hash that was returned for the given block hash access, if recorded.
+    pub fn recorded_block_hash(&self, access: &Access) -> Option<B256> {
+        self.block_hash_values.get(access).map(|hash| *hash)
+    }
+
+    /// Returns an iterator over the recorded accesses, without cloning them.
+    ///
+    /// Unlike [Self::get_accesses], this does not clear the recorded accesses. Each item holds a
+    /// read lock on its shard of the access set, so accesses must not be recorded, e.g. by
+    /// executing against a fork of this backend, while an item is alive, which would deadlock.
+    pub fn accesses_iter(
+        &self,
+    ) -> impl Iterator<Item = dashmap::setref::multiple::RefMulti<'_, Access>> + '_ {
+        self.data_accesses.iter()
+    }
+
+    /// Returns the recorded accesses whose [AccessType] matches the given predicate.
+    /// Unlike [Self::get_accesses], this does not clear the recorded accesses.
+    pub fn accesses_of<F: Fn(&AccessType) -> bool>(&self, pred: F) -> Vec<Access> {
+        self.data_accesses
+            .iter()
+            .filter(|access| pred(&access.access_type))
+            .map(|access| access.key().clone())
+            .collect()
+    }
+
+    /// Returns the recorded storage slot accesses
+    pub fn storage_accesses(&self) -> Vec<Access> {
+        self.accesses_of(|access_type| {
+            matches!(access_type, AccessType::RevmDbAccess(RevmDbAccess::Storage(..)))
+        })
+    }
+
+    /// Returns the recorded code by hash accesses
+    pub fn code_accesses(&self) -> Vec<Access> {
+        self.accesses_of(|access_type| {
+            matches!(access_type, AccessType::RevmDbAccess(RevmDbAccess::CodeByHash(_)))
+        })
+    }
+
+    /// Returns the distinct contracts touched by the recorded basic account, storage and account
+    /// existence accesses, e.g. for reports or to decide which codes to prefetch.
+    ///
+    /// Code by hash accesses are not associated with an address, so they are not included.
+    pub fn touched_contracts(&self) -> HashSet<Address> {
+        self.accesses_iter()
+            .filter_map(|access| match access.access_type {
+                AccessType::RevmDbAccess(
+                    RevmDbAccess::Basic(address) |
+                    RevmDbAccess::Storage(address, _) |
+                    RevmDbAccess::Exists(address),
+                ) => Some(address),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the recorded accesses whose values can change with the block the fork is pinned at,
+    /// see [`AccessType::is_block_sensitive`]
+    pub fn block_sensitive_accesses(&self) -> Vec<Access> {
+        self.accesses_of(AccessType::is_block_sensitive)
+    }
+
+    /// Returns the recorded fork creations
+    pub fn fork_accesses(&self) -> Vec<Access> {
+        self.accesses_of(|access_type| matches!(access_type, AccessType::CreateFork { .. }))
+    }
+
+    /// Returns the statistics of the recorded accesses
+    pub fn access_summary(&self) -> AccessSummary {
+        AccessSummary::new(self.accesses_iter())
+    }
+
+    /// Returns the recorded transient storage accesses
+    pub fn transient_storage_accesses(&self) -> Vec<Access> {
+        self.accesses_of(|access_type| matches!(access_type, AccessType::TransientStorage(_)))
+    }
+
+    /// Returns the recorded environment reads
+    pub fn environment_accesses(&self) -> Vec<Access> {
+        self.accesses_of(|access_type| matches!(access_type, AccessType::Environment(_)))
+    }
+
+    /// Sets a sink that receives every access recorded by this backend and its forks from now on,
+    /// e.g. to stream them to a channel, see [`DataAccesses::set_sink`].
+    ///
+    /// If `accumulate` is false, accesses are no longer accumulated in the recorded accesses of
+    /// the backend, so memory use doesn't grow with the number of accesses.
+    pub fn set_access_sink(&self, sink: Option<AccessSink>, accumulate: bool) {
+        self.data_accesses.set_sink(sink, accumulate);
+    }
+
+    /// Sets whether the values read by the accesses of the forks of this backend are logged in
+    /// order from now on, to be exported with [`Backend::take_access_log`] and replayed offline.
+    pub fn set_log_access_values(&self, enabled: bool) {
+        self.data_accesses.set_log_values(enabled);
+    }
+
+    /// Takes the accesses logged along with their values so far, see
+    /// [`Backend::set_log_access_values`].
+    pub fn take_access_log(&self) -> AccessLog {
+        self.data_accesses.take_value_log()
+    }
+
+    /// Sets the recorder adding the accesses of this backend and its forks to the recorded
+    /// accesses from now on, e.g. to only keep storage accesses, see
+    /// [`DataAccesses::set_recorder`].
+    pub fn set_access_recorder(&self, recorder: Box<dyn AccessRecorder>) {
+        self.data_accesses.set_recorder(recorder);
+    }
+
+    /// Sets the block from which on data accesses are recorded, accesses whose state lookup
+    /// resolves to an earlier block are ignored, see [`DataAccesses::set_record_from_block`].
+    pub fn set_record_from_block(&self, record_from_block: Option<u64>) {
+        self.data_accesses.set_record_from_block(record_from_block);
+    }
+
+    /// Returns an inspector that records the transient storage accesses of the inspected calls
+    /// into the accesses of this backend, tagged with the given chain and state lookup.
+    pub fn transient_storage_recorder(
+        &self,
+        chain: Chain,
+        state_lookup: StateLookup,
+    ) -> TransientStorageRecorder {
+        TransientStorageRecorder::new(Arc::clone(&self.data_accesses), chain, state_lookup)
+    }
+
+    /// Returns an inspector that records the environment values read by the inspected calls, e.g.
+    /// `block.basefee`, into the accesses of this backend, tagged with the given chain and state
+    /// lookup.
+    pub fn environment_recorder(
+        &self,
+        chain: Chain,
+        state_lookup: StateLookup,
+    ) -> EnvironmentRecorder {
+        EnvironmentRecorder::new(Arc::clone(&self.data_accesses), chain, state_lookup)
+    }
+
     /// sets the latest block number for the given url
     pub fn set_latest_block_number(&self, url: &str, block_number: u64) {
         self.environment_cache.set_latest_block_number(url, block_number);
     }
 
     /// Loads the given acceses on the given chain at the given block number, using the given url
+    ///
+    /// Accesses of other chains are loaded from the forks recorded for their chain, each fork from
+    /// its own url, relative to the block it resolved to. Accesses of chains without a recorded
+    /// fork are skipped, while a fork recorded without its block is an error, see
+    /// [fork_partitions].
+    ///
+    /// If [LoadAccessesOptions::max_fetches] is set, at most that many accesses are fetched from
+    /// the provider, while accesses that are already cached are always loaded. The accesses that
+    /// were not loaded once the budget is exhausted are returned in
+    /// [LoadAccessesOutcome::BudgetExhausted].
+    ///
+    /// Accesses whose type is not allowed by the mask of the options are skipped, see
+    /// [AccessTypeMask].
+    ///
+    /// Reads failing with a transient error, e.g. a dropped connection, are retried with backoff
+    /// according to the retry policy of the options, while deterministic errors fail right away,
+    /// see [RetryPolicy].
+    ///
+    /// Accesses are bucketed by the fork they resolve to and loaded concurrently, with at most
+    /// [LoadAccessesOptions::max_concurrency] accesses in flight at once if set, or one per thread
+    /// of the global thread pool otherwise. Errors of any access are returned.
+    ///
+    /// Unless a fetch budget is set, the storage slots of each fork are first fetched in a single
+    /// batch request, see [SharedBackend::prefetch_storage]. If the provider rejects the batch, the
+    /// slots are read one by one instead.
+    ///
+    /// [StateLookup::RollTransaction] accesses are loaded from the state right before their
+    /// transaction, like [DatabaseExt::roll_fork_to_transaction]: the transactions of its block
+    /// that precede it are replayed on top of the fork of the parent block first. These replays are
+    /// not counted against the fetch budget. Errors if such a transaction can't be found.
     pub fn load_accesses(
         &self,
-        accesses: &Vec<Access>,
+        accesses: &[Access],
         chain: Chain,
         current_block: u64,
         url: String,
-    ) -> Result<(), <Self as DatabaseRef>::Error> {
-        self.set_latest_block_number(&url, current_block);
+        options: LoadAccessesOptions,
+    ) -> Result<LoadAccessesOutcome, <Self as DatabaseRef>::Error> {
+        let LoadAccessesOptions { max_fetches, max_concurrency, mask, retry } = options;
+        let partitions = fork_partitions(accesses, chain, current_block, &url)?;
+
+        // Accesses resolving to the same url and block share a fork, those of a transaction also
+        // share the state its preceding transactions are replayed into
+        let mut buckets = BTreeMap::<(&str, u64, Option<B256>), Vec<&Access>>::new();
+        let mut prestates = HashMap::new();
+        for ((_, url), (current_block, partition)) in &partitions {
+            self.set_latest_block_number(url, *current_block);
+
+            let loaded =
+                partition.iter().copied().filter(|access| mask.allows(&access.access_type));
+            let transactions =
+                self.replay_transactions(loaded.clone(), *current_block, url, retry)?;
+            let transaction_blocks = transaction_blocks(&transactions);
+            for access in loaded {
+                let block_num = resolve_access_block(access, *current_block, &transaction_blocks)?;
+                let tx_hash = match access.state_lookup {
+                    StateLookup::RollTransaction(tx_hash) => Some(tx_hash),
+                    _ => None,
+                };
+                buckets.entry((url, block_num, tx_hash)).or_default().push(access);
+            }
+            prestates.extend(transactions);
+        }
+
+        let fetches = AtomicUsize::new(0);
+        let load = || {
+            if max_fetches.is_none() {
+                buckets.par_iter().for_each(|((url, block_num, _), accesses)| {
+                    self.prefetch_storage(accesses, *block_num, url)
+                });
+            }
+            buckets
+                .par_iter()
+                .flat_map(|(&(url, block_num, tx_hash), accesses)| {
+                    let prestate = tx_hash.and_then(|tx_hash| prestates.get(&tx_hash));
+                    accesses.par_iter().map(move |access| (url, block_num, prestate, *access))
+                })
+                .map(|(url, block_num, prestate, access)| {
+                    if let Some(max_fetches) = max_fetches {
+                        if !self.is_access_cached(access, block_num, url) &&
+                            fetches.fetch_add(1, Ordering::Relaxed) >= max_fetches
+                        {
+                            return Ok(Some(access.clone()));
+                        }
+                    }
+                    self.execute_access_at(access, block_num, url, prestate, retry)
+                        .map(|_| None)
+                        .map_err(|err| DatabaseError::msg(err.to_string()))
+                })
+                .collect::<Result<Vec<_>, DatabaseError>>()
+        };
 
-        let chain_accesses = accesses.into_par_iter().filter(|access| access.chain == chain);
+        let remaining = match max_concurrency {
+            Some(max_concurrency) => rayon::ThreadPoolBuilder::new()
+                .num_threads(max_concurrency.max(1))
+                .build()
+                .map_err(|err| DatabaseError::msg(err.to_string()))?
+                .install(load),
+            None => load(),
+        }?;
+        let remaining = remaining.into_iter().flatten().collect::<Vec<_>>();
+        if remaining.is_empty() {
+            Ok(LoadAccessesOutcome::Loaded)
+        } else {
+            Ok(LoadAccessesOutcome::BudgetExhausted { remaining })
+        }
+    }
 
-        let results = chain_accesses.map(|access| {
-            self.clone()
-                .execute_access(access, current_block, &url)
-                .map_err(|err| DatabaseError::msg(err.to_string()))
-        });
+    /// Fetches the storage slots read by the given accesses at the given block in a single batch
+    /// request, falling back to reading them one by one when they are executed if that fails
+    fn prefetch_storage(&self, accesses: &[&Access], block_num: u64, url: &str) {
+        let slots = accesses
+            .iter()
+            .filter_map(|access| match access.access_type {
+                AccessType::RevmDbAccess(RevmDbAccess::Storage(address, idx)) => {
+                    Some((address, idx))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // A single slot is not worth a batch
+        if slots.len() < 2 {
+            return;
+        }
+        if let Err(err) =
+            self.get_or_create_fork(url, block_num).and_then(|fork| fork.prefetch_storage(&slots))
+        {
+            debug!(target: "backend", %err, block_num, "failed to batch storage reads, reading them one by one");
+        }
+    }
 
-        if let Some(err) = results.find_any(|v| v.is_err()) {
-            return err;
+    /// Replays each transaction of the given [StateLookup::RollTransaction] accesses up to the
+    /// transaction, once per transaction, returning the state right before it
+    fn replay_transactions<'a>(
+        &self,
+        accesses: impl IntoIterator<Item = &'a Access>,
+        current_block: u64,
+        url: &str,
+        retry: RetryPolicy,
+    ) -> Result<HashMap<B256, TransactionPrestate>, DatabaseError> {
+        let mut prestates = HashMap::new();
+        for access in accesses {
+            let StateLookup::RollTransaction(tx_hash) = access.state_lookup else { continue };
+            if prestates.contains_key(&tx_hash) {
+                continue;
+            }
+            let tx = self.get_or_create_fork(url, current_block)?.get_transaction(tx_hash)?;
+            trace!(target: "backend", ?tx_hash, block = ?tx.block_number, index = ?tx.transaction_index, "resolved transaction");
+            prestates
+                .insert(tx_hash, self.replay_until_transaction(&tx, current_block, url, retry)?);
         }
+        Ok(prestates)
+    }
 
-        Ok(())
+    /// Replays the transactions of the given transaction's block that precede it on top of the
+    /// fork of the parent block, like [Backend::replay_until].
+    ///
+    /// Pending transactions have no preceding transactions, their state is the current block's.
+    fn replay_until_transaction(
+        &self,
+        tx: &Transaction,
+        current_block: u64,
+        url: &str,
+        retry: RetryPolicy,
+    ) -> Result<TransactionPrestate, DatabaseError> {
+        let fork_block = transaction_fork_block(tx, current_block);
+        let fork = self.get_or_create_fork(url, fork_block)?.with_retry_policy(retry);
+        let mut db = CacheDB::new(fork.clone());
+        let Some(block_number) = tx.block_number else {
+            return Ok(TransactionPrestate { fork_block, db });
+        };
+
+        let mut env = self
+            .forks
+            .get_env(ForkId::new(url, fork_block))
+            .map_err(|err| DatabaseError::msg(err.to_string()))?
+            .unwrap_or_default();
+        let block = fork.get_full_block(block_number)?;
+        update_env_block(&mut env, block_number, &block);
+
+        if let BlockTransactions::Full(txs) = block.transactions {
+            for preceding in txs.into_iter().take_while(|preceding| preceding.hash != tx.hash) {
+                // System transactions such as on L2s don't contain any pricing info so we skip them
+                // otherwise this would cause reverts
+                if is_known_system_sender(preceding.from) ||
+                    preceding.transaction_type == Some(SYSTEM_TRANSACTION_TYPE)
+                {
+                    trace!(tx=?preceding.hash, "skipping system transaction");
+                    continue;
+                }
+                trace!(tx=?preceding.hash, "replaying transaction");
+
+                let mut env = self.env_with_handler_cfg(env.clone());
+                configure_tx_env(&mut env.env, &preceding);
+                let res = crate::utils::new_evm_with_inspector(&mut db, env, NoOpInspector)
+                    .transact()
+                    .map_err(|err| {
+                        DatabaseError::msg(format!("failed replaying transaction: {err}"))
+                    })?;
+                db.commit(res.state);
+            }
+        }
+
+        Ok(TransactionPrestate { fork_block, db })
+    }
+
+    /// Returns the fork of the given url at the given block, creating it if it doesn't exist
+    fn get_or_create_fork(
+        &self,
+        url: &str,
+        block_num: u64,
+    ) -> Result<SharedBackend, DatabaseError> {
+        match self.forks.get_fork(ForkId::new(url, block_num)) {
+            Ok(Some(fork)) => Ok(fork),
+            Ok(None) => self
+                .forks
+                .create_fork(
+                    get_create_fork(url, block_num),
+                    Arc::clone(&self.environment_cache),
+                    Arc::clone(&self.data_accesses),
+                    Arc::clone(&self.block_hash_values),
+                    Arc::clone(&self.code_cache),
+                )
+                .map(|(_, fork, _)| fork),
+            Err(err) => Err(err),
+        }
+        .map_err(|err| DatabaseError::msg(err.to_string()))
+    }
+
+    /// Whether executing the given access at the given block would not fetch from the provider,
+    /// i.e. its fork exists and its data is cached
+    fn is_access_cached(&self, access: &Access, block_num: u64, url: &str) -> bool {
+        let fork_id = ForkId::new(url, block_num);
+        let fork = self.forks.get_fork(fork_id).ok().flatten();
+        match &access.access_type {
+            AccessType::RevmDbAccess(revm_db_access) => {
+                fork.is_some_and(|fork| fork.is_cached(revm_db_access))
+            }
+            AccessType::CreateFork { .. } => fork.is_some(),
+            AccessType::TransientStorage(_) | AccessType::Environment(_) => true,
+        }
     }
 
     /// Executes the given access on the database
@@ -2071,51 +2538,197 @@ impl Backend {
         current_block: u64,
         url: &str,
     ) -> Result<(), DatabaseError> {
-        let block_num = match &access.state_lookup {
-            StateLookup::RollN(n) => ((current_block as i64) + n).max(0) as u64,
-            StateLookup::RollAt(n) => *n,
-        };
-
+        let retry = RetryPolicy::default();
+        let transactions =
+            self.replay_transactions(std::iter::once(access), current_block, url, retry)?;
+        let block_num =
+            resolve_access_block(access, current_block, &transaction_blocks(&transactions))?;
+        let prestate = transactions.values().next();
+        self.execute_access_at(access, block_num, url, prestate, retry)
+    }
+
+    /// Executes the given access on the database, at the block its state lookup resolved to, or on
+    /// the state right before its transaction if given
+    fn execute_access_at(
+        &self,
+        access: &Access,
+        block_num: u64,
+        url: &str,
+        prestate: Option<&TransactionPrestate>,
+        retry: RetryPolicy,
+    ) -> Result<(), DatabaseError> {
         let fork_id = ForkId::new(url, block_num);
 
         match &access.access_type {
-            AccessType::RevmDbAccess(revm_db_access) => {
-                let mut fork = match self.forks.get_fork(fork_id) {
-                    Ok(Some(fork)) => Ok(fork),
-                    Ok(None) => self
-                        .forks
-                        .create_fork(
-                            get_create_fork(url, block_num),
-                            Arc::clone(&self.environment_cache),
-                            Arc::clone(&self.data_accesses),
-                            Arc::clone(&self.code_cache),
-                        )
-                        .map(|(_, fork, _)| fork),
-                    Err(err) => Err(err),
+            AccessType::RevmDbAccess(revm_db_access) => match prestate {
+                Some(prestate) => revm_db_access.execute(&prestate.db)?,
+                None => {
+                    let fork = self.get_or_create_fork(url, block_num)?.with_retry_policy(retry);
+                    revm_db_access.execute(&fork)?;
                 }
-                .map_err(|err| DatabaseError::msg(err.to_string()))?;
-
-                revm_db_access.execute(&mut fork)?;
-            }
-            AccessType::CreateFork(url) => {
+            },
+            AccessType::CreateFork { url, env_adjustments, .. } => {
                 if let Ok(Some(_)) = self.forks.get_fork(fork_id) {
                     return Ok(());
                 }
-                self.forks
+                let (id, _, mut env) = self
+                    .forks
                     .create_fork(
                         get_create_fork(url, block_num),
                         Arc::clone(&self.environment_cache),
                         Arc::clone(&self.data_accesses),
+                        Arc::clone(&self.block_hash_values),
                         Arc::clone(&self.code_cache),
                     )
                     .map_err(|err| DatabaseError::msg(err.to_string()))?;
+
+                // The provider may resolve a different env than the one recorded, e.g. another L1
+                // block number, so the recorded env takes precedence
+                if let Some(adjustments) = env_adjustments {
+                    if ChainEnvAdjustments::from_env(&env) != **adjustments {
+                        debug!(target: "backend", ?adjustments, "replayed fork env differs from the recorded env");
+                        adjustments.apply(&mut env);
+                        self.forks
+                            .set_env(id, env)
+                            .map_err(|err| DatabaseError::msg(err.to_string()))?;
+                    }
+                }
             }
+            // Transient storage is not persisted and the environment is part of the fork's env, so
+            // there is nothing to fetch
+            AccessType::TransientStorage(_) | AccessType::Environment(_) => {}
         };
 
         Ok(())
     }
 }
 
+/// The state right before a transaction, see [Backend::replay_until_transaction]
+struct TransactionPrestate {
+    /// The block of the fork the transaction's block is built on
+    fork_block: u64,
+    /// The fork with the preceding transactions of the block replayed on top
+    db: CacheDB<SharedBackend>,
+}
+
+/// Returns the fork block of each of the given replayed transactions
+fn transaction_blocks(transactions: &HashMap<B256, TransactionPrestate>) -> HashMap<B256, u64> {
+    transactions.iter().map(|(tx_hash, prestate)| (*tx_hash, prestate.fork_block)).collect()
+}
+
+/// Resolves the block number the state lookup points to, relative to the current block
+///
+/// The blocks of [StateLookup::RollTransaction] lookups are looked up in the given transaction
+/// blocks, see [Backend::replay_transactions].
+fn resolve_state_lookup(
+    state_lookup: &StateLookup,
+    current_block: u64,
+    transaction_blocks: &HashMap<B256, u64>,
+) -> Result<u64, DatabaseError> {
+    match state_lookup {
+        StateLookup::RollN(n) => Ok(((current_block as i64) + n).max(0) as u64),
+        StateLookup::RollAt(n) => Ok(*n),
+        StateLookup::RollTransaction(tx_hash) => transaction_blocks
+            .get(tx_hash)
+            .copied()
+            .ok_or(DatabaseError::TransactionNotFound(*tx_hash)),
+    }
+}
+
+/// The accesses loaded from each fork, keyed by the fork's chain and url, along with the current
+/// block they are loaded relative to
+type ForkPartitions<'a> = HashMap<(Chain, String), (u64, Vec<&'a Access>)>;
+
+/// Partitions the given accesses by the fork they are loaded from, keyed by the fork's chain and
+/// url, along with the current block the fork's accesses are loaded relative to, see
+/// [Backend::load_accesses].
+///
+/// The accesses of the given chain are loaded from the given url, relative to the given current
+/// block, even if forks of the chain with other urls were recorded. Every fork recorded on any
+/// other chain is loaded from its own url, relative to the block it resolved to, while the other
+/// accesses of the chain are loaded from the first fork recorded on it. Chains without a recorded
+/// fork have no url to load their accesses from and are left out.
+///
+/// Errors if a fork of another chain was recorded without its block, as there's no block to
+/// load its accesses at.
+fn fork_partitions<'a>(
+    accesses: &'a [Access],
+    chain: Chain,
+    current_block: u64,
+    url: &'a str,
+) -> Result<ForkPartitions<'a>, DatabaseError> {
+    let mut chain_urls = HashMap::from([(chain, url)]);
+    let mut partitions = HashMap::from([((chain, url.to_string()), (current_block, Vec::new()))]);
+    for access in accesses {
+        let AccessType::CreateFork { url: fork_url, block_number, .. } = &access.access_type else {
+            continue;
+        };
+        if access.chain == chain {
+            continue;
+        }
+        chain_urls.entry(access.chain).or_insert(fork_url);
+        let key = (access.chain, fork_url.clone());
+        if partitions.contains_key(&key) {
+            continue;
+        }
+        let block_number = block_number
+            .or(match access.state_lookup {
+                StateLookup::RollAt(block_number) => Some(block_number),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                DatabaseError::msg(format!(
+                    "the fork {} on chain {} was recorded without its block",
+                    reproducer::redact_url(fork_url),
+                    access.chain
+                ))
+            })?;
+        partitions.insert(key, (block_number, Vec::new()));
+    }
+
+    for access in accesses {
+        let fork_url = match &access.access_type {
+            AccessType::CreateFork { url: fork_url, .. } if access.chain != chain => {
+                fork_url.as_str()
+            }
+            _ => match chain_urls.get(&access.chain) {
+                Some(url) => url,
+                None => continue,
+            },
+        };
+        if let Some((_, partition)) = partitions.get_mut(&(access.chain, fork_url.to_string())) {
+            partition.push(access);
+        }
+    }
+    Ok(partitions)
+}
+
+/// Resolves the block the given access is executed at.
+///
+/// Fork creations that recorded the block they resolved to are pinned to it, so replaying them is
+/// deterministic even if the head their state lookup is relative to has moved on. Every other
+/// access resolves its state lookup, see [resolve_state_lookup].
+fn resolve_access_block(
+    access: &Access,
+    current_block: u64,
+    transaction_blocks: &HashMap<B256, u64>,
+) -> Result<u64, DatabaseError> {
+    match access.access_type {
+        AccessType::CreateFork { block_number: Some(block_number), .. } => Ok(block_number),
+        _ => resolve_state_lookup(&access.state_lookup, current_block, transaction_blocks),
+    }
+}
+
+/// Returns the block of the fork the state right before the given transaction is built on: the
+/// parent of the transaction's block, or the current block if the transaction is pending.
+///
+/// This is the whole state right before the first transaction of a block only, the preceding
+/// transactions of the block have to be replayed on top of it for later transactions, see
+/// [Backend::replay_until_transaction].
+fn transaction_fork_block(tx: &Transaction, current_block: u64) -> u64 {
+    tx.block_number.map_or(current_block, |block_number| block_number.saturating_sub(1))
+}
+
 fn get_create_fork(url: &str, block_num: u64) -> CreateFork {
     CreateFork {
         enable_caching: false,
@@ -0,0 +1,120 @@
+use super::DatabaseResult;
+use std::time::Duration;
+
+/// Policy for retrying remote reads that failed with a transient error, with exponential backoff.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the initial attempt.
+    pub max_retries: u32,
+    /// The backoff before the first retry, doubled for every following retry.
+    pub initial_backoff: Duration,
+    /// The upper bound for the backoff between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub const NONE: Self =
+        Self { max_retries: 0, initial_backoff: Duration::ZERO, max_backoff: Duration::ZERO };
+
+    /// Returns the backoff to wait before the given retry, starting at `0`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff)
+    }
+
+    /// Runs `f`, retrying it with backoff as long as it fails with a retryable error and retries
+    /// are left.
+    ///
+    /// See [`DatabaseError::is_retryable`](super::DatabaseError::is_retryable).
+    pub fn retry<T>(&self, mut f: impl FnMut() -> DatabaseResult<T>) -> DatabaseResult<T> {
+        let mut retry = 0;
+        loop {
+            match f() {
+                Err(err) if retry < self.max_retries && err.is_retryable() => {
+                    let backoff = self.backoff(retry);
+                    trace!(target: "backend::retry", %err, ?backoff, retry, "retrying remote read");
+                    std::thread::sleep(backoff);
+                    retry += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::DatabaseError;
+    use alloy_primitives::Address;
+    use alloy_transport::TransportErrorKind;
+    use std::sync::Arc;
+
+    fn transport_error() -> DatabaseError {
+        let err = TransportErrorKind::custom_str("connection reset");
+        DatabaseError::GetAccount(Address::ZERO, Arc::new(eyre::Report::new(err)))
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let policy = RetryPolicy { initial_backoff: Duration::ZERO, ..Default::default() };
+
+        let mut attempts = 0;
+        let res = policy.retry(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(transport_error())
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(res.unwrap(), 2);
+    }
+
+    #[test]
+    fn does_not_retry_terminal_errors() {
+        let policy = RetryPolicy { initial_backoff: Duration::ZERO, ..Default::default() };
+
+        let mut attempts = 0;
+        let res: DatabaseResult<()> = policy.retry(|| {
+            attempts += 1;
+            Err(DatabaseError::MissingAccount(Address::ZERO))
+        });
+
+        assert!(res.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let policy = RetryPolicy { initial_backoff: Duration::ZERO, ..Default::default() };
+
+        let mut attempts = 0;
+        let res: DatabaseResult<()> = policy.retry(|| {
+            attempts += 1;
+            Err(transport_error())
+        });
+
+        assert!(res.is_err());
+        assert_eq!(attempts, policy.max_retries + 1);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(10), policy.max_backoff);
+    }
+}
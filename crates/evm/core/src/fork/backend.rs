@@ -1,13 +1,18 @@
 //! Smart caching and deduplication of requests when using a forking provider
 use crate::{
     backend::{
-        Access, AccessType, CodeCache, DatabaseError, DatabaseResult, RevmDbAccess, StateLookup,
+        Access, AccessLog, AccessLogEntry, AccessType, AccessValue, CodeCache, DataAccesses,
+        DatabaseError, DatabaseResult, RetryPolicy, RevmDbAccess, StateLookup,
     },
     fork::{cache::FlushJsonBlockCacheDB, BlockchainDb},
 };
 use alloy_chains::Chain;
 use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
-use alloy_provider::{network::AnyNetwork, Provider};
+use alloy_provider::{
+    network::{AnyNetwork, Network},
+    Provider,
+};
+use alloy_rpc_client::BatchRequest;
 use alloy_rpc_types::{Block, BlockId, Transaction};
 use alloy_serde::WithOtherFields;
 use alloy_transport::Transport;
@@ -15,7 +20,7 @@ use eyre::WrapErr;
 use foundry_common::NON_ARCHIVE_NODE_WARNING;
 use futures::{
     channel::mpsc::{channel, Receiver, Sender},
-    stream::Stream,
+    stream::{self, Stream, StreamExt, TryStreamExt},
     task::{Context, Poll},
     Future, FutureExt,
 };
@@ -35,23 +40,41 @@ use std::{
     },
 };
 
+/// The maximum number of concurrent block requests when prefetching block hashes
+const BLOCK_HASH_PREFETCH_CONCURRENCY: usize = 16;
+
+/// The maximum number of blocks a block hash prefetch may span by default, see
+/// [`SharedBackend::with_max_prefetch_span`]
+pub const DEFAULT_MAX_PREFETCH_SPAN: u64 = 4096;
+
 // Various future/request type aliases
 
 type AccountFuture<Err> =
     Pin<Box<dyn Future<Output = (Result<(U256, u64, Bytes), Err>, Address)> + Send>>;
 type StorageFuture<Err> = Pin<Box<dyn Future<Output = (Result<U256, Err>, Address, U256)> + Send>>;
+type StorageBatchFuture<Err> = Pin<
+    Box<
+        dyn Future<Output = (StorageBatchSender, Result<Vec<((Address, U256), U256)>, Err>)> + Send,
+    >,
+>;
 type BlockHashFuture<Err> = Pin<Box<dyn Future<Output = (Result<B256, Err>, u64)> + Send>>;
 type FullBlockFuture<Err> =
     Pin<Box<dyn Future<Output = (FullBlockSender, Result<Option<Block>, Err>, BlockId)> + Send>>;
 type TransactionFuture<Err> = Pin<
     Box<
-        dyn Future<Output = (TransactionSender, Result<WithOtherFields<Transaction>, Err>, B256)>
-            + Send,
+        dyn Future<
+                Output = (
+                    TransactionSender,
+                    Result<Option<WithOtherFields<Transaction>>, Err>,
+                    B256,
+                ),
+            > + Send,
     >,
 >;
 
 type AccountInfoSender = OneshotSender<DatabaseResult<AccountInfo>>;
 type StorageSender = OneshotSender<DatabaseResult<U256>>;
+type StorageBatchSender = OneshotSender<DatabaseResult<usize>>;
 type BlockHashSender = OneshotSender<DatabaseResult<B256>>;
 type FullBlockSender = OneshotSender<DatabaseResult<Block>>;
 type TransactionSender = OneshotSender<DatabaseResult<WithOtherFields<Transaction>>>;
@@ -60,6 +83,7 @@ type TransactionSender = OneshotSender<DatabaseResult<WithOtherFields<Transactio
 enum ProviderRequest<Err> {
     Account(AccountFuture<Err>),
     Storage(StorageFuture<Err>),
+    StorageBatch(StorageBatchFuture<Err>),
     BlockHash(BlockHashFuture<Err>),
     FullBlock(FullBlockFuture<Err>),
     Transaction(TransactionFuture<Err>),
@@ -72,6 +96,8 @@ enum BackendRequest {
     Basic(Address, Chain, Arc<CodeCache>, AccountInfoSender),
     /// Fetch a storage slot
     Storage(Address, U256, StorageSender),
+    /// Fetch storage slots in a single batch request
+    StorageBatch(Vec<(Address, U256)>, StorageBatchSender),
     /// Fetch a block hash
     BlockHash(u64, BlockHashSender),
     /// Fetch an entire block with transactions
@@ -170,6 +196,9 @@ where
                     self.request_account_storage(addr, idx, sender);
                 }
             }
+            BackendRequest::StorageBatch(slots, sender) => {
+                self.request_storage_batch(slots, sender);
+            }
             BackendRequest::SetPinnedBlock(block_number) => {
                 self.block_number = block_number;
             }
@@ -200,6 +229,30 @@ where
         }
     }
 
+    /// process a request for storage slots, fetched in a single JSON-RPC batch request
+    fn request_storage_batch(&mut self, slots: Vec<(Address, U256)>, sender: StorageBatchSender) {
+        trace!(target: "backendhandler", slots = slots.len(), "preparing storage batch request");
+        let provider = self.provider.clone();
+        let block_id: BlockId = self.block_number.into();
+        let fut = Box::pin(async move {
+            let values = async {
+                let mut batch = BatchRequest::new(provider.client());
+                let waiters = slots
+                    .iter()
+                    .map(|&(address, idx)| {
+                        batch.add_call::<_, U256>("eth_getStorageAt", &(address, idx, block_id))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                batch.send().await?;
+                let values = futures::future::try_join_all(waiters).await?;
+                Ok(slots.into_iter().zip(values).collect())
+            }
+            .await;
+            (sender, values)
+        });
+        self.pending_requests.push(ProviderRequest::StorageBatch(fut));
+    }
+
     /// returns the future that fetches the account data
     fn get_account_req(
         &self,
@@ -214,7 +267,6 @@ where
         let block_number = self.block_number;
 
         let fut = Box::pin(async move {
-         
             let code = code_cache.get_code(&provider, address, chain, block_number);
             let balance = provider.get_balance(address).block_id(block_id).into_future();
             let nonce = provider.get_transaction_count(address).block_id(block_id).into_future();
@@ -264,10 +316,7 @@ where
             let block = provider
                 .get_transaction_by_hash(tx)
                 .await
-                .wrap_err_with(|| format!("could not get transaction {tx}"))
-                .and_then(|maybe| {
-                    maybe.ok_or_else(|| eyre::eyre!("could not get transaction {tx}"))
-                });
+                .wrap_err_with(|| format!("could not get transaction {tx}"));
             (sender, block, tx)
         });
 
@@ -425,6 +474,25 @@ where
                             continue;
                         }
                     }
+                    ProviderRequest::StorageBatch(fut) => {
+                        if let Poll::Ready((sender, resp)) = fut.poll_unpin(cx) {
+                            let msg = match resp {
+                                Ok(values) => {
+                                    let count = values.len();
+                                    let mut storage = pin.db.storage().write();
+                                    for ((addr, idx), value) in values {
+                                        storage.entry(addr).or_default().insert(idx, value);
+                                    }
+                                    Ok(count)
+                                }
+                                Err(err) => Err(DatabaseError::msg(format!(
+                                    "failed to get storage batch: {err}"
+                                ))),
+                            };
+                            let _ = sender.send(msg);
+                            continue;
+                        }
+                    }
                     ProviderRequest::BlockHash(fut) => {
                         if let Poll::Ready((block_hash, number)) = fut.poll_unpin(cx) {
                             let value = match block_hash {
@@ -473,7 +541,8 @@ where
                     ProviderRequest::Transaction(fut) => {
                         if let Poll::Ready((sender, tx, tx_hash)) = fut.poll_unpin(cx) {
                             let msg = match tx {
-                                Ok(tx) => Ok(tx),
+                                Ok(Some(tx)) => Ok(tx),
+                                Ok(None) => Err(DatabaseError::TransactionNotFound(tx_hash)),
                                 Err(err) => {
                                     let err = Arc::new(err);
                                     Err(DatabaseError::GetTransaction(tx_hash, err))
@@ -541,11 +610,43 @@ pub struct SharedBackend {
     /// The StateLookup to use
     state_lookup: StateLookup,
 
+    /// The block number the backend was created at, which its state lookup resolves to
+    block_number: u64,
+
     /// Shared set of data accesses that have been made
-    data_accesses: Arc<dashmap::DashSet<Access>>,
+    data_accesses: Arc<DataAccesses>,
+
+    /// Shared map of block hash accesses to the block hash that was returned
+    block_hash_values: Arc<dashmap::DashMap<Access, B256>>,
 
     /// The code cache
     code_cache: Arc<CodeCache>,
+
+    /// The policy for retrying remote reads that failed with a transient error
+    retry_policy: RetryPolicy,
+
+    /// The maximum number of blocks a block hash prefetch may span
+    max_prefetch_span: u64,
+}
+
+/// The configuration of a [`SharedBackend`], see [`SharedBackend::new`].
+///
+/// The default configuration is pinned to block `0` and records into fresh data accesses, block
+/// hash values and code cache.
+#[derive(Clone, Debug, Default)]
+pub struct SharedBackendConfig {
+    /// The block number to fetch data from, which the state lookup resolves to
+    pub pin_block: u64,
+    /// Shared set of data accesses that have been made
+    pub data_accesses: Arc<DataAccesses>,
+    /// Shared map of block hash accesses to the block hash that was returned
+    pub block_hash_values: Arc<dashmap::DashMap<Access, B256>>,
+    /// The Chain the backend is operating on
+    pub chain: Chain,
+    /// The StateLookup to use
+    pub state_lookup: StateLookup,
+    /// The code cache
+    pub code_cache: Arc<CodeCache>,
 }
 
 impl SharedBackend {
@@ -559,18 +660,13 @@ impl SharedBackend {
     pub async fn spawn_backend<T, P>(
         provider: P,
         db: BlockchainDb,
-        pin_block: u64,
-        data_accesses: Arc<dashmap::DashSet<Access>>,
-        chain: Chain,
-        state_lookup: StateLookup,
-        code_cache: Arc<CodeCache>,
+        config: SharedBackendConfig,
     ) -> Self
     where
         T: Transport + Clone + Unpin,
         P: Provider<T, AnyNetwork> + Unpin + 'static + Clone,
     {
-        let (shared, handler) =
-            Self::new(provider, db, pin_block, data_accesses, chain, state_lookup, code_cache);
+        let (shared, handler) = Self::new(provider, db, config);
         // spawn the provider handler to a task
         trace!(target: "backendhandler", "spawning Backendhandler task");
         tokio::spawn(handler);
@@ -582,18 +678,13 @@ impl SharedBackend {
     pub fn spawn_backend_thread<T, P>(
         provider: P,
         db: BlockchainDb,
-        pin_block: u64,
-        data_accesses: Arc<dashmap::DashSet<Access>>,
-        chain: Chain,
-        state_lookup: StateLookup,
-        code_cache: Arc<CodeCache>,
+        config: SharedBackendConfig,
     ) -> Self
     where
         T: Transport + Clone + Unpin,
         P: Provider<T, AnyNetwork> + Unpin + 'static + Clone,
     {
-        let (shared, handler) =
-            Self::new(provider, db, pin_block, data_accesses, chain, state_lookup, code_cache);
+        let (shared, handler) = Self::new(provider, db, config);
 
         // spawn a light-weight thread with a thread-local async runtime just for
         // sending and receiving data from the remote client
@@ -617,20 +708,51 @@ impl SharedBackend {
     pub fn new<T, P>(
         provider: P,
         db: BlockchainDb,
-        pin_block: u64,
-        data_accesses: Arc<dashmap::DashSet<Access>>,
-        chain: Chain,
-        state_lookup: StateLookup,
-        code_cache: Arc<CodeCache>,
+        config: SharedBackendConfig,
     ) -> (Self, BackendHandler<T, P>)
     where
         T: Transport + Clone + Unpin,
         P: Provider<T, AnyNetwork> + Unpin + 'static + Clone,
     {
+        let SharedBackendConfig {
+            pin_block,
+            data_accesses,
+            block_hash_values,
+            chain,
+            state_lookup,
+            code_cache,
+        } = config;
         let (backend, backend_rx) = channel(1);
         let cache = Arc::new(FlushJsonBlockCacheDB(Arc::clone(db.cache())));
         let handler = BackendHandler::new(provider, db, backend_rx, pin_block);
-        (Self { backend, cache, data_accesses, chain, state_lookup, code_cache }, handler)
+        (
+            Self {
+                backend,
+                cache,
+                data_accesses,
+                block_hash_values,
+                chain,
+                state_lookup,
+                block_number: pin_block,
+                code_cache,
+                retry_policy: Default::default(),
+                max_prefetch_span: DEFAULT_MAX_PREFETCH_SPAN,
+            },
+            handler,
+        )
+    }
+
+    /// Sets the policy for retrying remote reads that failed with a transient error
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the maximum number of blocks a block hash prefetch may span, see
+    /// [`Self::prefetch_block_hashes`]
+    pub fn with_max_prefetch_span(mut self, max_prefetch_span: u64) -> Self {
+        self.max_prefetch_span = max_prefetch_span;
+        self
     }
 
     /// Updates the pinned block to fetch data from
@@ -686,18 +808,201 @@ impl SharedBackend {
         })
     }
 
+    /// Concurrently fetches and caches the hashes of the blocks in the inclusive range `from..=to`,
+    /// so that subsequent [`DatabaseRef::block_hash_ref`] calls for them don't hit the provider,
+    /// e.g. for contracts reading the last 256 block hashes with `BLOCKHASH`.
+    ///
+    /// Errors if the given chain is not the chain of the backend, or if the range spans more blocks
+    /// than the maximum, see [`Self::with_max_prefetch_span`], before fetching anything. Returns
+    /// the number of block hashes fetched, which excludes the ones already cached.
+    pub async fn prefetch_block_hashes<T: Transport + Clone, N: Network, P: Provider<T, N>>(
+        &self,
+        provider: &P,
+        from: u64,
+        to: u64,
+        chain: Chain,
+    ) -> DatabaseResult<usize> {
+        if chain != self.chain {
+            return Err(DatabaseError::msg(format!(
+                "cannot prefetch block hashes of chain {chain} into a backend of chain {}",
+                self.chain
+            )));
+        }
+        let span = to.saturating_sub(from);
+        if span > self.max_prefetch_span {
+            return Err(DatabaseError::msg(format!(
+                "cannot prefetch the block hashes of {from}..={to}: the range spans {span} blocks, \
+                 more than the maximum of {}; raise the maximum with \
+                 `SharedBackend::with_max_prefetch_span` if this is intended",
+                self.max_prefetch_span
+            )));
+        }
+
+        let block_hashes = &self.cache.0.db().block_hashes;
+        let missing = {
+            let cached = block_hashes.read();
+            (from..=to)
+                .filter(|number| !cached.contains_key(&U256::from(*number)))
+                .collect::<Vec<_>>()
+        };
+
+        let fetched = stream::iter(missing)
+            .map(|number| async move {
+                let block = provider
+                    .get_block_by_number(number.into(), false)
+                    .await
+                    .map_err(|err| DatabaseError::GetBlockHash(number, Arc::new(err.into())))?;
+                // Like the backend handler, a block that doesn't exist has an empty hash
+                let hash = match block {
+                    Some(block) => block.header.hash.ok_or_else(|| {
+                        DatabaseError::msg(format!("empty block hash for block {number}"))
+                    })?,
+                    None => KECCAK_EMPTY,
+                };
+                Ok::<_, DatabaseError>((U256::from(number), hash))
+            })
+            .buffer_unordered(BLOCK_HASH_PREFETCH_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let count = fetched.len();
+        block_hashes.write().extend(fetched);
+        Ok(count)
+    }
+
+    /// Fetches and caches the given storage slots in a single JSON-RPC batch request, so that
+    /// subsequent [`DatabaseRef::storage_ref`] calls for them don't hit the provider, e.g. to warm
+    /// many slots at once.
+    ///
+    /// Errors if the provider rejects the batch, in which case nothing is cached and the slots can
+    /// be read one by one instead. Returns the number of slots fetched, which excludes the ones
+    /// already cached.
+    pub fn prefetch_storage(&self, slots: &[(Address, U256)]) -> DatabaseResult<usize> {
+        let missing = {
+            let storage = self.cache.0.db().storage.read();
+            slots
+                .iter()
+                .filter(|(address, idx)| {
+                    !storage.get(address).is_some_and(|storage| storage.contains_key(idx))
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        if missing.is_empty() {
+            return Ok(0);
+        }
+
+        tokio::task::block_in_place(|| {
+            let (sender, rx) = oneshot_channel();
+            let req = BackendRequest::StorageBatch(missing, sender);
+            self.backend.clone().try_send(req)?;
+            rx.recv()?
+        })
+    }
+
     /// Flushes the DB to disk if caching is enabled
     pub(crate) fn flush_cache(&self) {
         self.cache.0.flush();
     }
 }
 impl SharedBackend {
+    /// Whether the data of the given access is already cached, so executing the access doesn't
+    /// fetch from the provider
+    pub fn is_cached(&self, access: &RevmDbAccess) -> bool {
+        let db = self.cache.0.db();
+        match access {
+            RevmDbAccess::Basic(address) | RevmDbAccess::Exists(address) => {
+                db.accounts.read().contains_key(address)
+            }
+            RevmDbAccess::Storage(address, index) => {
+                db.storage.read().get(address).is_some_and(|storage| storage.contains_key(index))
+            }
+            RevmDbAccess::BlockHash(number) => db.block_hashes.read().contains_key(number),
+            // Code is never fetched by hash
+            RevmDbAccess::CodeByHash(_) => true,
+        }
+    }
+
+    /// Returns the block number the backend was created at
+    pub fn block_number(&self) -> u64 {
+        self.block_number
+    }
+
     fn record_revm_data_access(&self, revm_access_type: RevmDbAccess) {
-        self.data_accesses.insert(Access {
+        self.data_accesses.record_at(self.to_access(revm_access_type), self.block_number);
+    }
+
+    /// Logs the value read by the given access, if the values of accesses are logged
+    fn log_value(&self, revm_access_type: RevmDbAccess, value: impl FnOnce() -> AccessValue) {
+        if self.data_accesses.logs_values() {
+            self.data_accesses.log_value(AccessLogEntry {
+                access: self.to_access(revm_access_type),
+                value: value(),
+            });
+        }
+    }
+
+    /// Seeds the cache with the values of the logged accesses of this backend's chain and state
+    /// lookup, so reading them again is served without hitting the provider, e.g. to rerun
+    /// offline. Other entries are skipped.
+    ///
+    /// Errors if a logged value differs from the value already cached for the same access, e.g.
+    /// because the log was recorded against another block. Returns the number of entries
+    /// replayed.
+    pub fn replay_access_log(&self, log: &AccessLog) -> DatabaseResult<usize> {
+        let db = self.cache.0.db();
+        let mut replayed = 0;
+        for AccessLogEntry { access, value } in &log.entries {
+            if access.chain != self.chain || access.state_lookup != self.state_lookup {
+                continue
+            }
+            let AccessType::RevmDbAccess(revm_access) = &access.access_type else { continue };
+            let conflict = match (revm_access, value) {
+                (RevmDbAccess::Basic(address), AccessValue::Basic(info)) => {
+                    let mut accounts = db.accounts.write();
+                    match accounts.get(address) {
+                        Some(cached) => cached != info,
+                        None => {
+                            accounts.insert(*address, info.clone());
+                            false
+                        }
+                    }
+                }
+                (RevmDbAccess::Storage(address, index), AccessValue::Storage(value)) => {
+                    let mut storage = db.storage.write();
+                    let slots = storage.entry(*address).or_default();
+                    *slots.entry(*index).or_insert(*value) != *value
+                }
+                (RevmDbAccess::BlockHash(number), AccessValue::BlockHash(hash)) => {
+                    *db.block_hashes.write().entry(*number).or_insert(*hash) != *hash
+                }
+                _ => {
+                    return Err(DatabaseError::msg(format!(
+                        "logged value {value:?} does not match access {access:?}"
+                    )))
+                }
+            };
+            if conflict {
+                return Err(DatabaseError::msg(format!(
+                    "logged value of {access:?} differs from the cached value"
+                )));
+            }
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+
+    /// Records the block hash returned for a block hash access, so a replay can verify it
+    fn record_block_hash_value(&self, number: U256, hash: B256) {
+        self.block_hash_values.insert(self.to_access(RevmDbAccess::BlockHash(number)), hash);
+    }
+
+    fn to_access(&self, revm_access_type: RevmDbAccess) -> Access {
+        Access {
             chain: self.chain,
             state_lookup: self.state_lookup.clone(),
             access_type: AccessType::RevmDbAccess(revm_access_type),
-        });
+        }
     }
 }
 
@@ -707,13 +1012,17 @@ impl DatabaseRef for SharedBackend {
     fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
         trace!(target: "sharedbackend", %address, "request basic");
         self.record_revm_data_access(RevmDbAccess::Basic(address));
-        self.do_get_basic(address).map_err(|err| {
+        let info = self.retry_policy.retry(|| self.do_get_basic(address)).map_err(|err| {
             error!(target: "sharedbackend", %err, %address, "Failed to send/recv `basic`");
             if err.is_possibly_non_archive_node_error() {
                 error!(target: "sharedbackend", "{NON_ARCHIVE_NODE_WARNING}");
             }
             err
-        })
+        })?;
+        if let Some(info) = &info {
+            self.log_value(RevmDbAccess::Basic(address), || AccessValue::Basic(info.clone()));
+        }
+        Ok(info)
     }
 
     fn code_by_hash_ref(&self, hash: B256) -> Result<Bytecode, Self::Error> {
@@ -723,13 +1032,16 @@ impl DatabaseRef for SharedBackend {
     fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
         trace!(target: "sharedbackend", "request storage {:?} at {:?}", address, index);
         self.record_revm_data_access(RevmDbAccess::Storage(address, index));
-        self.do_get_storage(address, index).map_err(|err| {
-            error!(target: "sharedbackend", %err, %address, %index, "Failed to send/recv `storage`");
-            if err.is_possibly_non_archive_node_error() {
-                error!(target: "sharedbackend", "{NON_ARCHIVE_NODE_WARNING}");
-            }
-          err
-        })
+        let value =
+            self.retry_policy.retry(|| self.do_get_storage(address, index)).map_err(|err| {
+                error!(target: "sharedbackend", %err, %address, %index, "Failed to send/recv `storage`");
+                if err.is_possibly_non_archive_node_error() {
+                    error!(target: "sharedbackend", "{NON_ARCHIVE_NODE_WARNING}");
+                }
+                err
+            })?;
+        self.log_value(RevmDbAccess::Storage(address, index), || AccessValue::Storage(value));
+        Ok(value)
     }
 
     fn block_hash_ref(&self, number: U256) -> Result<B256, Self::Error> {
@@ -737,16 +1049,18 @@ impl DatabaseRef for SharedBackend {
             return Ok(KECCAK_EMPTY);
         }
         self.record_revm_data_access(RevmDbAccess::BlockHash(number));
-        let number: U256 = number;
-        let number = number.to();
-        trace!(target: "sharedbackend", "request block hash for number {:?}", number);
-        self.do_get_block_hash(number).map_err(|err| {
-            error!(target: "sharedbackend", %err, %number, "Failed to send/recv `block_hash`");
+        let block_number: u64 = number.to();
+        trace!(target: "sharedbackend", "request block hash for number {:?}", block_number);
+        let hash = self.retry_policy.retry(|| self.do_get_block_hash(block_number)).map_err(|err| {
+            error!(target: "sharedbackend", %err, %block_number, "Failed to send/recv `block_hash`");
             if err.is_possibly_non_archive_node_error() {
                 error!(target: "sharedbackend", "{NON_ARCHIVE_NODE_WARNING}");
             }
             err
-        })
+        })?;
+        self.record_block_hash_value(number, hash);
+        self.log_value(RevmDbAccess::BlockHash(number), || AccessValue::BlockHash(hash));
+        Ok(hash)
     }
 }
 
@@ -756,11 +1070,18 @@ mod tests {
     use crate::{
         backend::Backend,
         fork::{BlockchainDbMeta, CreateFork, JsonBlockCacheDB},
+        mock_transport::{failure, method_not_found, success, MockTransport},
         opts::EvmOpts,
     };
+    use alloy_provider::RootProvider;
+    use alloy_rpc_client::RpcClient;
     use foundry_common::provider::get_http_provider;
     use foundry_config::{Config, NamedChain};
-    use std::{collections::BTreeSet, path::PathBuf};
+    use std::{
+        collections::{BTreeSet, HashSet},
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
 
     const ENDPOINT: Option<&str> = option_env!("ETH_RPC_URL");
 
@@ -776,16 +1097,8 @@ mod tests {
         };
 
         let db = BlockchainDb::new(meta, None);
-        let backend = SharedBackend::spawn_backend(
-            Arc::new(provider),
-            db.clone(),
-            0,
-            Default::default(),
-            Default::default(),
-            Default::default(),
-            Default::default(),
-        )
-        .await;
+        let backend =
+            SharedBackend::spawn_backend(Arc::new(provider), db.clone(), Default::default()).await;
 
         // some rng contract from etherscan
         let address: Address = "63091244180ae240c87d1f528f5f269134cb07b3".parse().unwrap();
@@ -818,6 +1131,338 @@ mod tests {
         assert_eq!(slots.len() as u64, max_slots);
     }
 
+    /// Returns the block of the given number, whose hash is its number
+    fn numbered_block(number: &serde_json::Value) -> Block {
+        let number: U256 = serde_json::from_value(number.clone()).unwrap();
+        let mut block: Block = Block::default();
+        block.header.number = Some(number.to());
+        block.header.hash = Some(B256::from(number));
+        block
+    }
+
+    /// A transport serving blocks whose hash is their number
+    fn block_transport() -> MockTransport {
+        MockTransport::new(|method, params| match method {
+            "eth_getBlockByNumber" => success(&numbered_block(&params[0])),
+            method => method_not_found(method),
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn can_prefetch_block_hashes() {
+        let meta = BlockchainDbMeta::new(Default::default(), String::new());
+        let db = BlockchainDb::new(meta, None);
+        // The backend itself can't reach any provider, so it can only serve cached block hashes
+        let backend = SharedBackend::spawn_backend(
+            Arc::new(get_http_provider("http://fake.com")),
+            db.clone(),
+            Default::default(),
+        )
+        .await;
+
+        let transport = block_transport();
+        let provider = RootProvider::<_, AnyNetwork>::new(RpcClient::new(transport.clone(), true));
+        let chain = Chain::default();
+
+        assert_eq!(backend.prefetch_block_hashes(&provider, 10, 13, chain).await.unwrap(), 4);
+        assert_eq!(transport.requests(), 4);
+
+        // Cached block hashes are not fetched again
+        assert_eq!(backend.prefetch_block_hashes(&provider, 12, 14, chain).await.unwrap(), 1);
+        assert_eq!(transport.requests(), 5);
+
+        for number in 10..=14 {
+            let number = U256::from(number);
+            assert_eq!(backend.block_hash_ref(number).unwrap(), B256::from(number));
+        }
+        assert_eq!(db.block_hashes().read().len(), 5);
+
+        // Block hashes of another chain are rejected
+        assert!(backend
+            .prefetch_block_hashes(&provider, 0, 1, Chain::optimism_mainnet())
+            .await
+            .is_err());
+    }
+
+    /// A transport serving accounts with a balance of 100 and some code, storage slots whose value
+    /// is their index, and blocks whose hash is their number, but no transactions
+    fn state_transport() -> MockTransport {
+        MockTransport::new(|method, params| match method {
+            "eth_getBalance" => success(&U256::from(100)),
+            "eth_getTransactionCount" => success(&U256::from(1)),
+            "eth_getCode" => success(&Bytes::from_static(&[0x60, 0x00])),
+            "eth_getStorageAt" => {
+                success(&serde_json::from_value::<U256>(params[1].clone()).unwrap())
+            }
+            "eth_getBlockByNumber" => success(&numbered_block(&params[0])),
+            "eth_getTransactionByHash" => success(&None::<Transaction>),
+            method => unimplemented!("{method}"),
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn can_prefetch_storage_in_batch() {
+        let address = Address::repeat_byte(1);
+        let slots = (0..50u64).map(|idx| (address, U256::from(idx))).collect::<Vec<_>>();
+        let spawn = |transport: MockTransport| {
+            SharedBackend::spawn_backend(
+                RootProvider::<_, AnyNetwork>::new(RpcClient::new(transport, true)),
+                BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+                Default::default(),
+            )
+        };
+        let read_all = |backend: &SharedBackend| {
+            for &(address, idx) in &slots {
+                assert_eq!(backend.storage_ref(address, idx).unwrap(), idx);
+            }
+        };
+
+        // Reading the slots one by one makes a request per slot
+        let transport = state_transport();
+        read_all(&spawn(transport.clone()).await);
+        assert_eq!(transport.requests(), 50);
+
+        // Prefetching them makes a single batch request, after which reads are served from the
+        // cache
+        let transport = state_transport();
+        let backend = spawn(transport.clone()).await;
+        assert_eq!(backend.prefetch_storage(&slots).unwrap(), 50);
+        read_all(&backend);
+        assert_eq!(transport.requests(), 1);
+
+        // Cached slots are not fetched again
+        assert_eq!(backend.prefetch_storage(&slots).unwrap(), 0);
+        assert_eq!(transport.requests(), 1);
+
+        // A rejected batch caches nothing, and the slots can still be read one by one
+        let transport = state_transport().rejecting_batches();
+        let backend = spawn(transport.clone()).await;
+        assert!(backend.prefetch_storage(&slots).is_err());
+        assert!(!backend.is_cached(&RevmDbAccess::Storage(address, U256::ZERO)));
+        read_all(&backend);
+        assert_eq!(transport.requests(), 51);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retries_transient_access_errors() {
+        let access = RevmDbAccess::Storage(Address::repeat_byte(1), U256::from(7));
+        let spawn = |transport: MockTransport| {
+            SharedBackend::spawn_backend(
+                RootProvider::<_, AnyNetwork>::new(RpcClient::new(transport, true)),
+                BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+                Default::default(),
+            )
+        };
+        let retry = RetryPolicy {
+            initial_backoff: std::time::Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+
+        // Fails twice, then loads
+        let transport = state_transport().failing_first(2);
+        let backend = spawn(transport.clone()).await.with_retry_policy(retry);
+        access.execute(&backend).unwrap();
+        assert!(backend.is_cached(&access));
+        assert_eq!(transport.requests(), 3);
+
+        // Gives up once the retries are exhausted
+        let transport = state_transport().failing_first(10);
+        let backend = spawn(transport.clone()).await.with_retry_policy(retry);
+        assert!(access.execute(&backend).is_err());
+        assert_eq!(transport.requests(), 4);
+
+        // Without retries the first error is returned
+        let transport = state_transport().failing_first(2);
+        let backend = spawn(transport.clone()).await.with_retry_policy(RetryPolicy::NONE);
+        assert!(access.execute(&backend).is_err());
+        assert_eq!(transport.requests(), 1);
+    }
+
+    /// A transport answering storage reads with the given error response `failures` times, then
+    /// with the value of the slot
+    fn failing_storage_transport(
+        code: i64,
+        message: &'static str,
+        failures: usize,
+    ) -> MockTransport {
+        let attempts = AtomicUsize::new(0);
+        MockTransport::new(move |method, params| match method {
+            "eth_getStorageAt" if attempts.fetch_add(1, Ordering::SeqCst) < failures => {
+                failure(code, message)
+            }
+            "eth_getStorageAt" => {
+                success(&serde_json::from_value::<U256>(params[1].clone()).unwrap())
+            }
+            method => method_not_found(method),
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn classifies_rpc_error_responses() {
+        let access = RevmDbAccess::Storage(Address::repeat_byte(1), U256::from(7));
+        let retry = RetryPolicy {
+            initial_backoff: std::time::Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let spawn = |transport: MockTransport| {
+            SharedBackend::spawn_backend(
+                RootProvider::<_, AnyNetwork>::new(RpcClient::new(transport, true)),
+                BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+                Default::default(),
+            )
+        };
+
+        // Rate limits are transient, the read is retried until it loads
+        for (code, message) in
+            [(429, "too many requests"), (-32005, "daily request count exceeded")]
+        {
+            let transport = failing_storage_transport(code, message, 2);
+            let backend = spawn(transport.clone()).await.with_retry_policy(retry);
+            access.execute(&backend).unwrap();
+            assert!(backend.is_cached(&access));
+            assert_eq!(transport.requests(), 3);
+        }
+
+        // Other error responses are terminal, the read fails right away
+        for (code, message) in
+            [(-32000, "header not found"), (3, "execution reverted"), (-32602, "invalid params")]
+        {
+            let transport = failing_storage_transport(code, message, 1);
+            let backend = spawn(transport.clone()).await.with_retry_policy(retry);
+            let err = access.execute(&backend).unwrap_err();
+            assert!(!err.is_retryable(), "{err}");
+            assert_eq!(transport.requests(), 1);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unknown_transaction_is_not_found() {
+        let backend = SharedBackend::spawn_backend(
+            RootProvider::<_, AnyNetwork>::new(RpcClient::new(state_transport(), true)),
+            BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+            Default::default(),
+        )
+        .await;
+
+        let tx_hash = B256::repeat_byte(1);
+        assert!(matches!(
+            backend.get_transaction(tx_hash),
+            Err(DatabaseError::TransactionNotFound(hash)) if hash == tx_hash
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn can_replay_access_log() {
+        let address = Address::repeat_byte(1);
+        let read_all = |backend: &SharedBackend| {
+            (
+                backend.basic_ref(address).unwrap(),
+                backend.storage_ref(address, U256::from(1)).unwrap(),
+                backend.storage_ref(address, U256::from(2)).unwrap(),
+                backend.block_hash_ref(U256::from(10)).unwrap(),
+            )
+        };
+
+        // Record the values read against the provider
+        let data_accesses = Arc::new(DataAccesses::default());
+        data_accesses.set_log_values(true);
+        let backend = SharedBackend::spawn_backend(
+            RootProvider::<_, AnyNetwork>::new(RpcClient::new(state_transport(), true)),
+            BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+            SharedBackendConfig { data_accesses: data_accesses.clone(), ..Default::default() },
+        )
+        .await;
+        let recorded = read_all(&backend);
+        assert_eq!(recorded.0.as_ref().unwrap().balance, U256::from(100));
+
+        let log = data_accesses.take_value_log();
+        assert_eq!(log.entries.len(), 4);
+        assert_eq!(
+            log.entries[0].access.access_type,
+            AccessType::RevmDbAccess(RevmDbAccess::Basic(address))
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access-log.ndjson");
+        log.save(&path).unwrap();
+        let log = AccessLog::load(&path).unwrap();
+
+        // Replay the values into a backend that can't reach any provider
+        let transport = state_transport();
+        let backend = SharedBackend::spawn_backend(
+            RootProvider::<_, AnyNetwork>::new(RpcClient::new(transport.clone(), true)),
+            BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+            Default::default(),
+        )
+        .await;
+        assert_eq!(backend.replay_access_log(&log).unwrap(), 4);
+        assert_eq!(read_all(&backend), recorded);
+        assert_eq!(transport.requests(), 0);
+
+        // A differing value for an access that was already replayed is detected
+        let mut changed = log.clone();
+        changed.entries[1].value = AccessValue::Storage(U256::from(42));
+        assert!(backend.replay_access_log(&changed).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn can_execute_exists_access() {
+        let address = Address::repeat_byte(1);
+        let exists = RevmDbAccess::Exists(address);
+        let basic = RevmDbAccess::Basic(address);
+        assert_ne!(exists, basic);
+        let accesses = HashSet::from([
+            exists.clone().to_access(Chain::default(), StateLookup::RollN(0)),
+            basic.clone().to_access(Chain::default(), StateLookup::RollN(0)),
+        ]);
+        assert_eq!(accesses.len(), 2);
+
+        let transport = state_transport();
+        let backend = SharedBackend::spawn_backend(
+            RootProvider::<_, AnyNetwork>::new(RpcClient::new(transport.clone(), true)),
+            BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+            Default::default(),
+        )
+        .await;
+        assert!(!backend.is_cached(&exists));
+        exists.execute(&backend).unwrap();
+        assert!(backend.is_cached(&exists));
+
+        // The account was loaded, so the basic access is served from the cache
+        let requests = transport.requests();
+        basic.execute(&backend).unwrap();
+        assert_eq!(transport.requests(), requests);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn prefetch_block_hashes_limits_span() {
+        let backend = SharedBackend::spawn_backend(
+            Arc::new(get_http_provider("http://fake.com")),
+            BlockchainDb::new(BlockchainDbMeta::new(Default::default(), String::new()), None),
+            Default::default(),
+        )
+        .await;
+
+        let transport = block_transport();
+        let provider = RootProvider::<_, AnyNetwork>::new(RpcClient::new(transport.clone(), true));
+        let chain = Chain::default();
+
+        // An over-large range errors before any request
+        assert!(backend.prefetch_block_hashes(&provider, 0, u64::MAX, chain).await.is_err());
+        assert!(backend
+            .prefetch_block_hashes(&provider, 0, DEFAULT_MAX_PREFETCH_SPAN + 1, chain)
+            .await
+            .is_err());
+        assert_eq!(transport.requests(), 0);
+
+        assert_eq!(backend.prefetch_block_hashes(&provider, 100, 103, chain).await.unwrap(), 4);
+        assert_eq!(transport.requests(), 4);
+
+        // The maximum can be lowered or raised explicitly
+        let backend = backend.with_max_prefetch_span(2);
+        assert!(backend.prefetch_block_hashes(&provider, 200, 203, chain).await.is_err());
+        assert_eq!(transport.requests(), 4);
+    }
+
     #[test]
     fn can_read_cache() {
         let cache_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-data/storage.json");
@@ -276,16 +276,8 @@ mod tests {
         };
         let db = BlockchainDb::new(meta, None);
 
-        let backend = SharedBackend::spawn_backend(
-            Arc::new(provider),
-            db.clone(),
-            0,
-            Default::default(),
-            Default::default(),
-            Default::default(),
-            Default::default(),
-        )
-        .await;
+        let backend =
+            SharedBackend::spawn_backend(Arc::new(provider), db.clone(), Default::default()).await;
 
         let mut db = ForkedDatabase::new(backend, db);
         let address = Address::random();
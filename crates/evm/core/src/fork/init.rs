@@ -22,6 +22,13 @@ pub struct EnvironmentArgs<P> {
     pub pin_block: Option<u64>,
     pub origin: Address,
     pub disable_block_gas_limit: bool,
+    /// Replaces the timestamp of the block env derived from the fork's block, e.g. to test time
+    /// dependent logic against the fork's state
+    pub override_timestamp: Option<u64>,
+    /// Replaces the number of the block env derived from the fork's block.
+    ///
+    /// This only changes what `block.number` reads, state is still read from the pinned block.
+    pub override_block_number: Option<u64>,
 }
 
 /// Initializes a REVM block environment based on a forked
@@ -37,18 +44,17 @@ pub async fn environment<N: Network, T: Transport + Clone, P: Provider<T, N>>(
         pin_block,
         origin,
         disable_block_gas_limit,
+        override_timestamp,
+        override_block_number,
     }: EnvironmentArgs<P>,
 ) -> eyre::Result<(Env, Block)> {
-    let block_number = if let Some(pin_block) = pin_block {
-        pin_block
-    } else {
-        env_cache
-            .get_latest_block_number(&provider, &fork_url)
-            .await
-            .expect("latest block for url not set")
+    // Fetches and caches the latest block number if it wasn't primed for the fork url
+    let block_number = match pin_block {
+        Some(pin_block) => pin_block,
+        None => env_cache.get_latest_block_number(&provider, &fork_url).await?,
     };
 
-    let (rpc_chain_id, BlockEnvironment { gas_price: fork_gas_price, block }) =
+    let (rpc_chain_id, BlockEnvironment { gas_price: fork_gas_price, block, excess_blob_gas, .. }) =
         env_cache.get_fork_info(&provider, &fork_url, block_number).await?;
 
     let block = if let Some(block) = block {
@@ -69,8 +75,11 @@ pub async fn environment<N: Network, T: Transport + Clone, P: Provider<T, N>>(
         eyre::bail!("Failed to get block for block number: {}", block_number)
     };
 
+    // The overridden chain id is authoritative, including for the chain specific env changes
+    let chain_id = override_chain_id.unwrap_or(rpc_chain_id);
+
     let mut cfg = CfgEnv::default();
-    cfg.chain_id = override_chain_id.unwrap_or(rpc_chain_id);
+    cfg.chain_id = chain_id;
     cfg.memory_limit = memory_limit;
     cfg.limit_contract_code_size = Some(usize::MAX);
     // EIP-3607 rejects transactions from senders with deployed code.
@@ -94,13 +103,249 @@ pub async fn environment<N: Network, T: Transport + Clone, P: Provider<T, N>>(
         tx: TxEnv {
             caller: origin,
             gas_price: U256::from(gas_price.unwrap_or(fork_gas_price)),
-            chain_id: Some(override_chain_id.unwrap_or(rpc_chain_id)),
+            chain_id: Some(chain_id),
             gas_limit: block.header.gas_limit as u64,
             ..Default::default()
         },
     };
 
+    // Reconstruct the blob gas schedule from the cached env, falling back to the block header.
+    // Pre-Cancun blocks have no excess blob gas, leaving the default of the block env. The blob gas
+    // used by the block has no counterpart in the block env.
+    if let Some(excess_blob_gas) = excess_blob_gas.or(block.header.excess_blob_gas) {
+        env.block.set_blob_excess_gas_and_price(excess_blob_gas as u64);
+    }
+
+    // Keyed off `env.cfg.chain_id`, i.e. the overridden chain id if any
     apply_chain_and_block_specific_env_changes(&mut env, &block);
 
+    if let Some(timestamp) = override_timestamp {
+        env.block.timestamp = U256::from(timestamp);
+    }
+    if let Some(number) = override_block_number {
+        env.block.number = U256::from(number);
+    }
+
     Ok((env, block))
 }
+
+/// Renders the fields of the given env relevant to a fork setup as JSON, e.g. to inspect the env
+/// resolved by [`environment`].
+///
+/// The schema is stable and independent of the serialization of the revm types: `cfg` holds the
+/// chain id and the flags affecting execution, `block` the block fields and `tx` the transaction
+/// fields. 256-bit quantities are hex encoded, other numbers are plain JSON numbers.
+pub fn env_to_json(env: &Env) -> serde_json::Value {
+    let Env { cfg, block, tx } = env;
+    serde_json::json!({
+        "cfg": {
+            "chainId": cfg.chain_id,
+            "memoryLimit": cfg.memory_limit,
+            "limitContractCodeSize": cfg.limit_contract_code_size,
+            "disableEip3607": cfg.disable_eip3607,
+            "disableBlockGasLimit": cfg.disable_block_gas_limit,
+        },
+        "block": {
+            "number": block.number,
+            "timestamp": block.timestamp,
+            "coinbase": block.coinbase,
+            "basefee": block.basefee,
+            "gasLimit": block.gas_limit,
+            "prevrandao": block.prevrandao,
+            "blobExcessGas": block.blob_excess_gas_and_price.as_ref().map(|blob| blob.excess_blob_gas),
+        },
+        "tx": {
+            "caller": tx.caller,
+            "gasLimit": tx.gas_limit,
+            "gasPrice": tx.gas_price,
+            "chainId": tx.chain_id,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_transport::{success, MockTransport};
+    use alloy_provider::{network::Ethereum, RootProvider};
+    use alloy_rpc_client::RpcClient;
+
+    /// The first Cancun block on mainnet
+    const CANCUN_BLOCK: u64 = 19_426_587;
+
+    /// A mainnet transport serving blocks with the given excess blob gas, pre-Cancun blocks if
+    /// it's `None`
+    fn blob_transport(excess_blob_gas: Option<u128>) -> MockTransport {
+        MockTransport::new(move |method, _| match method {
+            "eth_chainId" => success(&U256::from(1)),
+            "eth_blockNumber" => success(&U256::from(CANCUN_BLOCK)),
+            "eth_gasPrice" => success(&U256::from(20_000_000_000u64)),
+            "eth_getBlockByNumber" => {
+                let mut block: Block = Block::default();
+                block.header.number = Some(CANCUN_BLOCK);
+                block.header.base_fee_per_gas = Some(10_000_000_000);
+                block.header.gas_limit = 30_000_000;
+                block.header.excess_blob_gas = excess_blob_gas;
+                block.header.blob_gas_used = excess_blob_gas.map(|_| 0x60000);
+                success(&block)
+            }
+            method => unimplemented!("{method}"),
+        })
+    }
+
+    fn environment_args(
+        excess_blob_gas: Option<u128>,
+    ) -> EnvironmentArgs<RootProvider<MockTransport, Ethereum>> {
+        let provider =
+            RootProvider::<_, Ethereum>::new(RpcClient::new(blob_transport(excess_blob_gas), true));
+        EnvironmentArgs {
+            provider: Arc::new(provider),
+            fork_url: "http://fake.com".to_string(),
+            env_cache: Default::default(),
+            memory_limit: 0,
+            gas_price: None,
+            override_chain_id: None,
+            pin_block: Some(CANCUN_BLOCK),
+            origin: Address::ZERO,
+            disable_block_gas_limit: false,
+            override_timestamp: None,
+            override_block_number: None,
+        }
+    }
+
+    /// A transport serving the given block as the block of the given chain
+    fn chain_transport(chain_id: u64, block: Block) -> MockTransport {
+        MockTransport::new(move |method, _| match method {
+            "eth_chainId" => success(&U256::from(chain_id)),
+            "eth_gasPrice" => success(&U256::from(1)),
+            "eth_getBlockByNumber" => success(&block),
+            method => unimplemented!("{method}"),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_override_chain_id_env_changes() {
+        // An optimism block carrying an L1 block number, which is only applied on arbitrum
+        let mut block: Block = Block::default();
+        block.header.number = Some(120_000_000);
+        block.header.gas_limit = 30_000_000;
+        block.other.insert("l1BlockNumber".to_string(), serde_json::json!("0x121eac0"));
+
+        let environment_args = |override_chain_id| {
+            let transport = chain_transport(10, block.clone());
+            EnvironmentArgs {
+                provider: Arc::new(RootProvider::<_, Ethereum>::new(RpcClient::new(
+                    transport, true,
+                ))),
+                fork_url: "http://fake.com".to_string(),
+                env_cache: Default::default(),
+                memory_limit: 0,
+                gas_price: None,
+                override_chain_id,
+                pin_block: Some(120_000_000),
+                origin: Address::ZERO,
+                disable_block_gas_limit: false,
+                override_timestamp: None,
+                override_block_number: None,
+            }
+        };
+
+        let (env, _) = environment(environment_args(Some(42_161))).await.unwrap();
+        assert_eq!(env.cfg.chain_id, 42_161);
+        assert_eq!(env.tx.chain_id, Some(42_161));
+        assert_eq!(env.block.number, U256::from(19_000_000));
+
+        // Without the override the env changes of the forked chain apply
+        let (env, _) = environment(environment_args(None)).await.unwrap();
+        assert_eq!(env.cfg.chain_id, 10);
+        assert_eq!(env.block.number, U256::from(120_000_000));
+    }
+
+    async fn blob_environment(excess_blob_gas: Option<u128>) -> Env {
+        let (env, _) = environment(environment_args(excess_blob_gas)).await.unwrap();
+        env
+    }
+
+    #[tokio::test]
+    async fn test_environment_overrides() {
+        let (env, block) = environment(EnvironmentArgs {
+            override_timestamp: Some(2_000_000_000),
+            override_block_number: Some(CANCUN_BLOCK + 1000),
+            ..environment_args(Some(0))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(env.block.timestamp, U256::from(2_000_000_000));
+        assert_eq!(env.block.number, U256::from(CANCUN_BLOCK + 1000));
+        // The state is still that of the pinned block
+        assert_eq!(block.header.number, Some(CANCUN_BLOCK));
+        assert_eq!(env.block.basefee, U256::from(10_000_000_000u64));
+
+        let (env, _) = environment(environment_args(Some(0))).await.unwrap();
+        assert_eq!(env.block.number, U256::from(CANCUN_BLOCK));
+    }
+
+    #[tokio::test]
+    async fn test_environment_unprimed_latest_block() {
+        let args = EnvironmentArgs { pin_block: None, ..environment_args(Some(0)) };
+        let env_cache = args.env_cache.clone();
+        assert_eq!(env_cache.latest_block_delta("http://fake.com", 0), None);
+
+        let (env, block) = environment(args).await.unwrap();
+        assert_eq!(block.header.number, Some(CANCUN_BLOCK));
+        assert_eq!(env.block.number, U256::from(CANCUN_BLOCK));
+        assert_eq!(env.cfg.chain_id, 1);
+
+        // The fetched latest block number is cached
+        assert_eq!(env_cache.latest_block_delta("http://fake.com", 0), Some(CANCUN_BLOCK as i64));
+    }
+
+    #[tokio::test]
+    async fn test_environment_blob_base_fee() {
+        // The blob base fee is e^(excess blob gas / update fraction), here e^10
+        let env = blob_environment(Some(10 * 3_338_477)).await;
+        assert_eq!(
+            env.block.blob_excess_gas_and_price.as_ref().unwrap().excess_blob_gas,
+            33_384_770
+        );
+        assert_eq!(env.block.get_blob_gasprice(), Some(22_026));
+
+        // No excess blob gas is the minimum blob base fee
+        let env = blob_environment(Some(0)).await;
+        assert_eq!(env.block.get_blob_gasprice(), Some(1));
+
+        // Pre-Cancun blocks keep the default of the block env
+        let env = blob_environment(None).await;
+        assert_eq!(
+            env.block.blob_excess_gas_and_price,
+            BlockEnv::default().blob_excess_gas_and_price
+        );
+    }
+
+    #[test]
+    fn test_env_to_json() {
+        let caller = Address::repeat_byte(1);
+        let mut env = Env::default();
+        env.cfg.chain_id = 10;
+        env.cfg.disable_eip3607 = true;
+        env.block.number = U256::from(20_000_000);
+        env.block.timestamp = U256::from(1_700_000_000);
+        env.block.basefee = U256::from(7);
+        env.block.gas_limit = U256::from(30_000_000);
+        env.tx.caller = caller;
+        env.tx.gas_limit = 1_000_000;
+        env.tx.gas_price = U256::from(8);
+
+        let json = env_to_json(&env);
+        assert_eq!(json["cfg"]["chainId"], 10);
+        assert_eq!(json["cfg"]["disableEip3607"], true);
+        assert_eq!(json["block"]["number"], "0x1312d00");
+        assert_eq!(json["block"]["timestamp"], "0x6553f100");
+        assert_eq!(json["block"]["basefee"], "0x7");
+        assert_eq!(json["block"]["gasLimit"], "0x1c9c380");
+        assert_eq!(json["tx"]["caller"], caller.to_string());
+        assert_eq!(json["tx"]["gasLimit"], 1_000_000);
+        assert_eq!(json["tx"]["gasPrice"], "0x8");
+    }
+}
@@ -1,11 +1,12 @@
 use super::opts::EvmOpts;
 use revm::primitives::Env;
+use serde::{Deserialize, Serialize};
 
 mod backend;
-pub use backend::{BackendHandler, SharedBackend};
+pub use backend::{BackendHandler, SharedBackend, SharedBackendConfig, DEFAULT_MAX_PREFETCH_SPAN};
 
 mod init;
-pub use init::{environment, EnvironmentArgs};
+pub use init::{env_to_json, environment, EnvironmentArgs};
 
 mod cache;
 pub use cache::{
@@ -19,7 +20,7 @@ mod multi;
 pub use multi::{ForkId, MultiFork, MultiForkHandler};
 
 /// Represents a _fork_ of a remote chain whose data is available only via the `url` endpoint.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CreateFork {
     /// Whether to enable rpc storage caching for this fork
     pub enable_caching: bool,
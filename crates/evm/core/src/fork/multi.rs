@@ -4,9 +4,13 @@
 //! concurrently active pairs at once.
 
 use crate::{
-    backend::{Access, CodeCache, EnvironmentCache},
-    fork::{BackendHandler, BlockchainDb, BlockchainDbMeta, CreateFork, SharedBackend},
+    backend::{Access, CodeCache, DataAccesses, EnvironmentCache},
+    fork::{
+        BackendHandler, BlockchainDb, BlockchainDbMeta, CreateFork, SharedBackend,
+        SharedBackendConfig,
+    },
 };
+use alloy_primitives::B256;
 use foundry_common::provider::{
     runtime_transport::RuntimeTransport, tower::RetryBackoffService, ProviderBuilder, RetryProvider,
 };
@@ -110,12 +114,20 @@ impl MultiFork {
         &self,
         fork: CreateFork,
         env_cache: Arc<EnvironmentCache>,
-        data_accesses: Arc<dashmap::DashSet<Access>>,
+        data_accesses: Arc<DataAccesses>,
+        block_hash_values: Arc<dashmap::DashMap<Access, B256>>,
         code_cache: Arc<CodeCache>,
     ) -> eyre::Result<(ForkId, SharedBackend, Env)> {
         trace!("Creating new fork, url={}, block={:?}", fork.url, fork.evm_opts.fork_block_number);
         let (sender, rx) = oneshot_channel();
-        let req = Request::CreateFork(Box::new(fork), sender, env_cache, data_accesses, code_cache);
+        let req = Request::CreateFork(
+            Box::new(fork),
+            sender,
+            env_cache,
+            data_accesses,
+            block_hash_values,
+            code_cache,
+        );
         self.handler.clone().try_send(req).map_err(|e| eyre::eyre!("{:?}", e))?;
         rx.recv()?
     }
@@ -128,12 +140,21 @@ impl MultiFork {
         fork: ForkId,
         block: u64,
         env_cache: Arc<EnvironmentCache>,
-        data_accesses: Arc<dashmap::DashSet<Access>>,
+        data_accesses: Arc<DataAccesses>,
+        block_hash_values: Arc<dashmap::DashMap<Access, B256>>,
         code_cache: Arc<CodeCache>,
     ) -> eyre::Result<(ForkId, SharedBackend, Env)> {
         trace!(?fork, ?block, "rolling fork");
         let (sender, rx) = oneshot_channel();
-        let req = Request::RollFork(fork, block, sender, env_cache, data_accesses, code_cache);
+        let req = Request::RollFork(
+            fork,
+            block,
+            sender,
+            env_cache,
+            data_accesses,
+            block_hash_values,
+            code_cache,
+        );
         self.handler.clone().try_send(req).map_err(|e| eyre::eyre!("{:?}", e))?;
         rx.recv()?
     }
@@ -147,6 +168,15 @@ impl MultiFork {
         Ok(rx.recv()?)
     }
 
+    /// Replaces the `Env` of the given fork, e.g. with the env recorded when it was first created
+    pub fn set_env(&self, fork: ForkId, env: Env) -> eyre::Result<()> {
+        trace!(?fork, "setting env config");
+        let (sender, rx) = oneshot_channel();
+        let req = Request::SetEnv(fork, Box::new(env), sender);
+        self.handler.clone().try_send(req).map_err(|e| eyre::eyre!("{:?}", e))?;
+        rx.recv()?
+    }
+
     /// Returns the corresponding fork if it exists
     ///
     /// Returns `None` if no matching fork backend is available.
@@ -185,7 +215,8 @@ enum Request {
         Box<CreateFork>,
         CreateSender,
         Arc<EnvironmentCache>,
-        Arc<dashmap::DashSet<Access>>,
+        Arc<DataAccesses>,
+        Arc<dashmap::DashMap<Access, B256>>,
         Arc<CodeCache>,
     ),
     /// Returns the Fork backend for the `ForkId` if it exists
@@ -196,11 +227,14 @@ enum Request {
         u64,
         CreateSender,
         Arc<EnvironmentCache>,
-        Arc<dashmap::DashSet<Access>>,
+        Arc<DataAccesses>,
+        Arc<dashmap::DashMap<Access, B256>>,
         Arc<CodeCache>,
     ),
     /// Returns the environment of the fork
     GetEnv(ForkId, GetEnvSender),
+    /// Replaces the environment of the fork
+    SetEnv(ForkId, Box<Env>, OneshotSender<eyre::Result<()>>),
     /// Shutdowns the entire `MultiForkHandler`, see `ShutDownMultiFork`
     ShutDown(OneshotSender<()>),
     /// Returns the Fork Url for the `ForkId` if it exists
@@ -276,7 +310,8 @@ impl MultiForkHandler {
         fork: CreateFork,
         sender: CreateSender,
         env_cache: Arc<EnvironmentCache>,
-        data_accesses: Arc<dashmap::DashSet<Access>>,
+        data_accesses: Arc<DataAccesses>,
+        block_hash_values: Arc<dashmap::DashMap<Access, B256>>,
         code_cache: Arc<CodeCache>,
     ) {
         let block_number_opt = fork.evm_opts.fork_block_number;
@@ -291,7 +326,8 @@ impl MultiForkHandler {
         }
 
         // need to create a new fork
-        let task = Box::pin(create_fork(fork, env_cache, data_accesses, code_cache));
+        let task =
+            Box::pin(create_fork(fork, env_cache, data_accesses, block_hash_values, code_cache));
         self.pending_tasks.push(ForkTask::Create(
             task,
             fork_url,
@@ -321,19 +357,46 @@ impl MultiForkHandler {
 
     fn on_request(&mut self, req: Request) {
         match req {
-            Request::CreateFork(fork, sender, env_cache, data_accesses, code_cache) => {
-                self.create_fork(*fork, sender, env_cache, data_accesses, code_cache)
-            }
+            Request::CreateFork(
+                fork,
+                sender,
+                env_cache,
+                data_accesses,
+                block_hash_values,
+                code_cache,
+            ) => self.create_fork(
+                *fork,
+                sender,
+                env_cache,
+                data_accesses,
+                block_hash_values,
+                code_cache,
+            ),
             Request::GetFork(fork_id, sender) => {
                 let fork = self.forks.get(&fork_id).map(|f| f.backend.clone());
                 let _ = sender.send(fork);
             }
-            Request::RollFork(fork_id, block, sender, env_cache, data_accesses, code_cache) => {
+            Request::RollFork(
+                fork_id,
+                block,
+                sender,
+                env_cache,
+                data_accesses,
+                block_hash_values,
+                code_cache,
+            ) => {
                 if let Some(fork) = self.forks.get(&fork_id) {
                     trace!(target: "fork::multi", "rolling {} to {}", fork_id, block);
                     let mut opts = fork.opts.clone();
                     opts.evm_opts.fork_block_number = Some(block);
-                    self.create_fork(opts, sender, env_cache, data_accesses, code_cache)
+                    self.create_fork(
+                        opts,
+                        sender,
+                        env_cache,
+                        data_accesses,
+                        block_hash_values,
+                        code_cache,
+                    )
                 } else {
                     let _ = sender.send(Err(eyre::eyre!("No matching fork exits for {}", fork_id)));
                 }
@@ -341,6 +404,16 @@ impl MultiForkHandler {
             Request::GetEnv(fork_id, sender) => {
                 let _ = sender.send(self.forks.get(&fork_id).map(|fork| fork.opts.env.clone()));
             }
+            Request::SetEnv(fork_id, env, sender) => {
+                let res = match self.forks.get_mut(&fork_id) {
+                    Some(fork) => {
+                        fork.opts.env = *env;
+                        Ok(())
+                    }
+                    None => Err(eyre::eyre!("No matching fork exits for {}", fork_id)),
+                };
+                let _ = sender.send(res);
+            }
             Request::ShutDown(sender) => {
                 trace!(target: "fork::multi", "received shutdown signal");
                 // we're emptying all fork backends, this way we ensure all caches get flushed
@@ -519,7 +592,8 @@ impl Drop for ShutDownMultiFork {
 async fn create_fork(
     mut fork: CreateFork,
     env_cache: Arc<EnvironmentCache>,
-    data_accesses: Arc<dashmap::DashSet<Access>>,
+    data_accesses: Arc<DataAccesses>,
+    block_hash_values: Arc<dashmap::DashMap<Access, B256>>,
     code_cache: Arc<CodeCache>,
 ) -> eyre::Result<(ForkId, CreatedFork, Handler)> {
     let provider = Arc::new(
@@ -551,11 +625,14 @@ async fn create_fork(
     let (backend, handler) = SharedBackend::new(
         provider,
         db,
-        number,
-        data_accesses,
-        fork.env.cfg.chain_id.into(),
-        (&(fork)).into(),
-        code_cache,
+        SharedBackendConfig {
+            pin_block: number,
+            data_accesses,
+            block_hash_values,
+            chain: fork.env.cfg.chain_id.into(),
+            state_lookup: (&(fork)).into(),
+            code_cache,
+        },
     );
     let fork = CreatedFork::new(fork, backend);
     let fork_id = ForkId::new(&fork.opts.url, number);
@@ -14,6 +14,9 @@ extern crate tracing;
 
 mod ic;
 
+#[cfg(test)]
+mod mock_transport;
+
 pub mod abi;
 pub mod backend;
 pub mod constants;
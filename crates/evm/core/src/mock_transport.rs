@@ -0,0 +1,268 @@
+//! A mock RPC transport for tests.
+
+use alloy_json_rpc::{
+    ErrorPayload, Id, RequestPacket, Response, ResponsePacket, ResponsePayload, SerializedRequest,
+};
+use alloy_primitives::{Bytes, B256, U256};
+use alloy_rpc_types::Block;
+use alloy_transport::{TransportError, TransportErrorKind, TransportFut};
+use serde::Serialize;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Answers a request given its method and params, `null` if it has none
+type Handler = dyn Fn(&str, &serde_json::Value) -> ResponsePayload + Send + Sync;
+
+/// A transport answering every request with a closure, counting the requests.
+///
+/// Batch requests are answered request by request, unless [`Self::rejecting_batches`] is set.
+#[derive(Clone)]
+pub(crate) struct MockTransport {
+    handler: Arc<Handler>,
+    /// The number of requests received, a batch counting as one
+    requests: Arc<AtomicUsize>,
+    /// The number of requests currently being answered
+    in_flight: Arc<AtomicUsize>,
+    /// The highest number of requests that were answered at once
+    peak_in_flight: Arc<AtomicUsize>,
+    delay: Option<Duration>,
+    reject_batches: bool,
+    fail_first: usize,
+}
+
+impl MockTransport {
+    /// Creates a transport answering every request with the given closure, which is passed the
+    /// method and params of the request
+    pub(crate) fn new(
+        handler: impl Fn(&str, &serde_json::Value) -> ResponsePayload + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            requests: Default::default(),
+            in_flight: Default::default(),
+            peak_in_flight: Default::default(),
+            delay: None,
+            reject_batches: false,
+            fail_first: 0,
+        }
+    }
+
+    /// Delays every response by the given duration
+    pub(crate) fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Fails batch requests with a transport error
+    pub(crate) fn rejecting_batches(mut self) -> Self {
+        self.reject_batches = true;
+        self
+    }
+
+    /// Fails the first `fail_first` requests with a transport error, e.g. a dropped connection
+    pub(crate) fn failing_first(mut self, fail_first: usize) -> Self {
+        self.fail_first = fail_first;
+        self
+    }
+
+    /// Returns the number of requests received so far, a batch counting as one
+    pub(crate) fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the highest number of requests that were answered at once so far
+    pub(crate) fn peak_in_flight(&self) -> usize {
+        self.peak_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Serves the transport over HTTP on a local port and returns its url, e.g. for a
+    /// [`Backend`](crate::backend::Backend) that connects to its forks by url.
+    ///
+    /// Requests failed by [`Self::failing_first`] are answered with a `503 Service Unavailable`,
+    /// rejected batches with a `400 Bad Request`.
+    pub(crate) fn serve(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let this = self.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let this = this.clone();
+                std::thread::spawn(move || this.serve_connection(stream));
+            }
+        });
+        url
+    }
+
+    /// Answers the HTTP requests of the given connection until it is closed
+    fn serve_connection(&self, stream: TcpStream) {
+        let mut stream = BufReader::new(stream);
+        loop {
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).unwrap_or(0) == 0 {
+                    return;
+                }
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            if stream.read_exact(&mut body).is_err() {
+                return;
+            }
+
+            let (status, body) = self.respond_http(&serde_json::from_slice(&body).unwrap());
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            if stream.get_mut().write_all(response.as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Answers the given JSON-RPC request or batch with an HTTP status and body
+    fn respond_http(&self, req: &serde_json::Value) -> (&'static str, String) {
+        let previous_requests = self.requests.fetch_add(1, Ordering::SeqCst);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        if let Some(delay) = self.delay {
+            std::thread::sleep(delay);
+        }
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let respond = |req: &serde_json::Value| {
+            let id: Id = serde_json::from_value(req["id"].clone()).unwrap();
+            let payload = (self.handler)(req["method"].as_str().unwrap(), &req["params"]);
+            Response { id, payload }
+        };
+        match req {
+            _ if previous_requests < self.fail_first => {
+                ("503 Service Unavailable", "connection reset".to_string())
+            }
+            serde_json::Value::Array(_) if self.reject_batches => {
+                ("400 Bad Request", "batch requests are not supported".to_string())
+            }
+            serde_json::Value::Array(reqs) => {
+                let responses = reqs.iter().map(respond).collect::<Vec<_>>();
+                ("200 OK", serde_json::to_string(&responses).unwrap())
+            }
+            req => ("200 OK", serde_json::to_string(&respond(req)).unwrap()),
+        }
+    }
+
+    fn respond(&self, req: &SerializedRequest) -> Response {
+        let params = req
+            .params()
+            .map(|params| serde_json::from_str(params.get()).unwrap())
+            .unwrap_or_default();
+        Response { id: req.id().clone(), payload: (self.handler)(req.method(), &params) }
+    }
+}
+
+impl tower::Service<RequestPacket> for MockTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let previous_requests = self.requests.fetch_add(1, Ordering::SeqCst);
+        let response = match req {
+            _ if previous_requests < self.fail_first => {
+                Err(TransportErrorKind::custom_str("connection reset"))
+            }
+            RequestPacket::Single(req) => Ok(ResponsePacket::Single(self.respond(&req))),
+            RequestPacket::Batch(_) if self.reject_batches => {
+                Err(TransportErrorKind::custom_str("batch requests are not supported"))
+            }
+            RequestPacket::Batch(reqs) => {
+                Ok(ResponsePacket::Batch(reqs.iter().map(|req| self.respond(req)).collect()))
+            }
+        };
+        let this = self.clone();
+        Box::pin(async move {
+            let in_flight = this.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            this.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            if let Some(delay) = this.delay {
+                tokio::time::sleep(delay).await;
+            }
+            this.in_flight.fetch_sub(1, Ordering::SeqCst);
+            response
+        })
+    }
+}
+
+/// Returns a successful response with the given result
+pub(crate) fn success(result: &impl Serialize) -> ResponsePayload {
+    ResponsePayload::Success(serde_json::value::to_raw_value(result).unwrap())
+}
+
+/// Returns an error response with the given code and message
+pub(crate) fn failure(code: i64, message: impl Into<String>) -> ResponsePayload {
+    ResponsePayload::Failure(ErrorPayload { code, message: message.into(), data: None })
+}
+
+/// Returns the error response of a node that doesn't implement the given method
+pub(crate) fn method_not_found(method: &str) -> ResponsePayload {
+    failure(-32601, format!("the method {method} does not exist"))
+}
+
+/// Returns a transport mocking a node of the given chain whose head is the given block.
+///
+/// Blocks have a hash equal to their number and are 12 seconds apart, accounts have a balance of
+/// 100, a nonce of 1 and some code, storage slots hold their index, and there are no transactions.
+pub(crate) fn node_transport(chain_id: u64, head: u64) -> MockTransport {
+    MockTransport::new(move |method, params| match method {
+        "eth_chainId" => success(&U256::from(chain_id)),
+        "eth_blockNumber" => success(&U256::from(head)),
+        "eth_gasPrice" => success(&U256::from(1)),
+        "eth_getBlockByNumber" => {
+            let number = match &params[0] {
+                serde_json::Value::String(tag) if tag.starts_with("0x") => {
+                    serde_json::from_value::<U256>(params[0].clone()).unwrap().to()
+                }
+                _ => head,
+            };
+            if number > head {
+                return success(&None::<Block>);
+            }
+            success(&node_block(number))
+        }
+        "eth_getBalance" => success(&U256::from(100)),
+        "eth_getTransactionCount" => success(&U256::from(1)),
+        "eth_getCode" => success(&Bytes::from_static(&[0x60, 0x00])),
+        "eth_getStorageAt" => success(&serde_json::from_value::<U256>(params[1].clone()).unwrap()),
+        "eth_getTransactionByHash" => success(&None::<()>),
+        method => method_not_found(method),
+    })
+}
+
+/// Returns the block of the given number served by [`node_transport`]
+pub(crate) fn node_block(number: u64) -> Block {
+    let mut block: Block = Block::default();
+    block.header.number = Some(number);
+    block.header.hash = Some(B256::from(U256::from(number)));
+    block.header.timestamp = number * 12;
+    block.header.gas_limit = 30_000_000;
+    block.header.base_fee_per_gas = Some(1);
+    block
+}
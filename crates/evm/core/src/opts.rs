@@ -1,5 +1,8 @@
 use super::fork::{environment, EnvironmentArgs};
-use crate::{backend::EnvironmentCache, fork::CreateFork};
+use crate::{
+    backend::{CacheContext, CodeCache, EnvironmentCache},
+    fork::CreateFork,
+};
 use alloy_primitives::{Address, B256, U256};
 use alloy_provider::Provider;
 use alloy_rpc_types::Block;
@@ -64,6 +67,12 @@ pub struct EvmOpts {
 
     /// Whether to disable block gas limit checks.
     pub disable_block_gas_limit: bool,
+
+    /// The number of block environments to cache, the default capacity if not set.
+    pub block_env_cache_capacity: Option<usize>,
+
+    /// The number of accounts to cache the code of, the default capacity if not set.
+    pub code_cache_capacity: Option<usize>,
 }
 
 impl EvmOpts {
@@ -104,6 +113,8 @@ impl EvmOpts {
             pin_block: self.fork_block_number,
             origin: self.sender,
             disable_block_gas_limit: self.disable_block_gas_limit,
+            override_timestamp: None,
+            override_block_number: None,
         })
         .await
         .wrap_err_with(|| {
@@ -163,6 +174,21 @@ impl EvmOpts {
         Some(CreateFork { url, enable_caching, env, evm_opts: self.clone() })
     }
 
+    /// Returns the caches to spawn a [`Backend`](crate::backend::Backend) with, sized according to
+    /// the configured capacities
+    pub fn cache_context(&self) -> CacheContext {
+        CacheContext {
+            code: Arc::new(
+                self.code_cache_capacity.map(CodeCache::with_capacity).unwrap_or_default(),
+            ),
+            env: Arc::new(
+                self.block_env_cache_capacity
+                    .map(EnvironmentCache::with_capacity)
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+
     /// Returns the gas limit to use
     pub fn gas_limit(&self) -> u64 {
         self.env.block_gas_limit.unwrap_or(self.env.gas_limit)
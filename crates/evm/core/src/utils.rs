@@ -23,7 +23,9 @@ pub use revm::primitives::EvmState as StateChangeset;
 /// - checks for prevrandao mixhash after merge
 /// - applies chain specifics: on Arbitrum `block.number` is the L1 block
 ///
-/// Should be called with proper chain id (retrieved from provider if not provided).
+/// Should be called with the effective chain id set on the env, i.e. the overridden chain id if
+/// any, otherwise the chain id retrieved from the provider. The changes are keyed off that chain id
+/// only, regardless of the network the block was fetched from.
 pub fn apply_chain_and_block_specific_env_changes(env: &mut revm::primitives::Env, block: &Block) {
     if let Ok(chain) = NamedChain::try_from(env.cfg.chain_id) {
         let block_number = block.header.number.unwrap_or_default();
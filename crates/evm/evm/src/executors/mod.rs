@@ -45,7 +45,10 @@ pub mod invariant;
 pub use invariant::InvariantExecutor;
 
 mod trace;
-pub use trace::TracingExecutor;
+pub use trace::{
+    chain_memory_limit, AccountDiff, Change, DecodedTrace, DecodedTraceCall, StateDiff,
+    TracedOutcome, TracingExecutor, TracingExecutorBuilder, DEFAULT_FORK,
+};
 
 sol! {
     interface ITest {
@@ -1,13 +1,63 @@
-use crate::executors::{Executor, ExecutorBuilder};
+use crate::{
+    executors::{Executor, ExecutorBuilder, RawCallResult},
+    inspectors::InspectorStackBuilder,
+};
+use alloy_dyn_abi::EventExt;
+use alloy_primitives::{hex, Address, Bytes, LogData, B256, U256};
+use foundry_common::provider::ProviderBuilder;
 use foundry_compilers::artifacts::EvmVersion;
-use foundry_config::{utils::evm_spec_id, Chain, Config};
-use foundry_evm_core::{backend::Backend, fork::CreateFork, opts::EvmOpts};
-use revm::primitives::{Env, SpecId};
-use std::ops::{Deref, DerefMut};
+use foundry_config::{utils::evm_spec_id, Chain, Config, NamedChain};
+use foundry_evm_core::{
+    backend::{Access, Backend, DatabaseExt, EnvironmentCache, LocalForkId},
+    decode::RevertDecoder,
+    fork::CreateFork,
+    opts::EvmOpts,
+    utils::StateChangeset,
+};
+use foundry_evm_traces::{CallTraceArena, CallTraceDecoder};
+use revm::{
+    interpreter::{gas::validate_initial_tx_gas, InstructionResult},
+    primitives::{Env, EnvWithHandlerCfg, SpecId, TxEnv, KECCAK_EMPTY},
+    DatabaseRef, JournaledState,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::{Deref, DerefMut},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The name the fork an executor is built with is registered under, see
+/// [`TracingExecutor::select_fork`]
+pub const DEFAULT_FORK: &str = "default";
+
+/// The memory limits per EVM execution in bytes of the chains whose contracts need more memory
+/// than the default limit when traced, e.g. because they allow contracts above the EIP-170 size
+/// limit.
+const CHAIN_MEMORY_LIMITS: &[(NamedChain, u64)] = &[
+    (NamedChain::Arbitrum, 1 << 29),
+    (NamedChain::ArbitrumNova, 1 << 29),
+    (NamedChain::ArbitrumSepolia, 1 << 29),
+    (NamedChain::ZkSync, 1 << 29),
+    (NamedChain::ZkSyncTestnet, 1 << 29),
+];
+
+/// Returns the default memory limit per EVM execution in bytes for the given chain, if it needs
+/// more than the default limit, see [`TracingExecutorBuilder::build`].
+pub fn chain_memory_limit(chain_id: u64) -> Option<u64> {
+    let chain = NamedChain::try_from(chain_id).ok()?;
+    CHAIN_MEMORY_LIMITS.iter().find(|(named, _)| *named == chain).map(|(_, limit)| *limit)
+}
 
 /// A default executor with tracing enabled
 pub struct TracingExecutor {
     executor: Executor,
+    /// The registered forks by name
+    forks: HashMap<String, LocalForkId>,
+    /// The wall-clock time a traced transaction may run for, if bounded
+    timeout: Option<Duration>,
+    /// The gas limit of traced transactions is capped at, if any
+    gas_cap: Option<u64>,
 }
 
 impl TracingExecutor {
@@ -17,15 +67,32 @@ impl TracingExecutor {
         version: Option<EvmVersion>,
         debug: bool,
     ) -> Self {
-        let db = Backend::spawn(fork);
-        Self {
-            // configures a bare version of the evm executor: no cheatcode inspector is enabled,
-            // tracing will be enabled only for the targeted transaction
-            executor: ExecutorBuilder::new()
-                .inspectors(|stack| stack.trace(true).debug(debug))
-                .spec(evm_spec_id(&version.unwrap_or_default()))
-                .build(env, db),
-        }
+        Self::with_inspectors(env, fork, version, debug, |stack| stack)
+    }
+
+    /// Creates a new executor like [`TracingExecutor::new`], with the inspector stack modified by
+    /// the given function, e.g. to add a [`CustomInspector`](crate::inspectors::CustomInspector).
+    ///
+    /// The function is applied after tracing and the debugger are configured, so it can override
+    /// them.
+    pub fn with_inspectors(
+        env: revm::primitives::Env,
+        fork: Option<CreateFork>,
+        version: Option<EvmVersion>,
+        debug: bool,
+        f: impl FnOnce(InspectorStackBuilder) -> InspectorStackBuilder,
+    ) -> Self {
+        Self::builder()
+            .fork(fork)
+            .spec(evm_spec_id(&version.unwrap_or_default()))
+            .debug(debug)
+            .inspectors(f)
+            .build(env)
+    }
+
+    /// Returns a new [`TracingExecutorBuilder`]
+    pub fn builder() -> TracingExecutorBuilder {
+        TracingExecutorBuilder::default()
     }
 
     /// Returns the spec id of the executor
@@ -33,6 +100,267 @@ impl TracingExecutor {
         self.executor.spec_id()
     }
 
+    /// Registers an additional fork under the given name, e.g. of another chain, without selecting
+    /// it.
+    ///
+    /// Data accesses of the fork are recorded with its chain. Errors if a fork with the same name
+    /// is already registered.
+    pub fn add_fork(
+        &mut self,
+        name: impl Into<String>,
+        fork: CreateFork,
+    ) -> eyre::Result<LocalForkId> {
+        let name = name.into();
+        if self.forks.contains_key(&name) {
+            eyre::bail!("fork `{name}` is already registered");
+        }
+        let id = self.executor.backend_mut().create_fork(fork)?;
+        self.forks.insert(name, id);
+        Ok(id)
+    }
+
+    /// Selects the registered fork with the given name, so subsequent executions read its state,
+    /// and updates the env to the fork's env, e.g. its chain id and block.
+    ///
+    /// The fork the executor was built with is registered as [`DEFAULT_FORK`].
+    pub fn select_fork(&mut self, name: &str) -> eyre::Result<()> {
+        let Some(&id) = self.forks.get(name) else { eyre::bail!("unknown fork `{name}`") };
+        let mut env = self.env().clone();
+        let mut journaled_state = JournaledState::new(self.spec_id(), Default::default());
+        self.executor.backend_mut().select_fork_outside_test(id, &mut env, &mut journaled_state)?;
+        *self.env_mut() = env;
+        Ok(())
+    }
+
+    /// Estimates the minimum gas limit the given transaction succeeds with on the current state,
+    /// like `eth_estimateGas`, without committing it.
+    ///
+    /// Binary searches between the intrinsic gas of the transaction and the lower of its gas limit
+    /// and the block gas limit. Errors if the transaction fails even with the highest gas limit,
+    /// e.g. if it reverts regardless of the gas.
+    pub fn estimate_gas(&mut self, tx: TxEnv) -> eyre::Result<u64> {
+        let spec_id = self.spec_id();
+        let mut env = self.env().clone();
+        let cap = tx
+            .gas_limit
+            .min(env.block.gas_limit.saturating_to())
+            .min(self.gas_cap.unwrap_or(u64::MAX));
+        let intrinsic_gas =
+            validate_initial_tx_gas(spec_id, &tx.data, tx.transact_to.is_create(), &tx.access_list);
+        env.tx = tx;
+
+        let mut call = |gas_limit: u64| {
+            let mut env = env.clone();
+            env.tx.gas_limit = gas_limit;
+            self.call_bounded(EnvWithHandlerCfg::new_with_spec_id(Box::new(env), spec_id))
+        };
+
+        if intrinsic_gas > cap {
+            eyre::bail!("intrinsic gas {intrinsic_gas} exceeds the gas limit {cap}");
+        }
+        let result = call(cap)?;
+        if result.reverted {
+            let reason = RevertDecoder::new().decode(&result.result, Some(result.exit_reason));
+            eyre::bail!("execution failed with a gas limit of {cap}: {reason}");
+        }
+
+        // invariant: the transaction fails with `low` and succeeds with `high`
+        let (mut low, mut high) = (intrinsic_gas - 1, cap);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if call(mid)?.reverted {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(high)
+    }
+
+    /// Warms the cache of the executor's fork with the data of the given accesses, e.g. the
+    /// recorded accesses of a transaction, so that tracing it needs as few RPC requests as
+    /// possible.
+    ///
+    /// Accesses are resolved relative to the block of the executor's env. Errors if the executor is
+    /// not forked.
+    pub fn warm_from_accesses(&mut self, accesses: &[Access]) -> eyre::Result<()> {
+        let Some(fork_url) = self.backend().active_fork_url() else {
+            eyre::bail!("cannot warm an executor that is not forked");
+        };
+        let chain = Chain::from_id(self.env().cfg.chain_id);
+        let block_number = self.env().block.number.saturating_to();
+        self.backend().load_accesses(
+            accesses,
+            chain,
+            block_number,
+            fork_url,
+            Default::default(),
+        )?;
+        Ok(())
+    }
+
+    /// Executes the given call without committing it, with its gas limit capped and its execution
+    /// time bounded as configured, see [`TracingExecutorBuilder::gas_cap`] and
+    /// [`TracingExecutorBuilder::timeout`].
+    ///
+    /// Errors if the call was halted because it ran for longer than the timeout.
+    fn call_bounded(&mut self, mut env: EnvWithHandlerCfg) -> eyre::Result<RawCallResult> {
+        if let Some(gas_cap) = self.gas_cap {
+            env.tx.gas_limit = env.tx.gas_limit.min(gas_cap);
+        }
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.executor.inspector_mut().deadline = deadline;
+        let result = self.executor.call_with_env(env);
+        self.executor.inspector_mut().deadline = None;
+
+        let result = result?;
+        if result.exit_reason == InstructionResult::OutOfGas &&
+            deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            eyre::bail!("execution timed out after {:?}", self.timeout.unwrap_or_default());
+        }
+        Ok(result)
+    }
+
+    /// Executes the given transaction on the current state without committing it and returns its
+    /// trace along with either the return data or the decoded revert reason.
+    ///
+    /// If the transaction reverts, the trace includes the reverting call. If it can't be executed
+    /// at all, e.g. due to a database error or because it ran for longer than the timeout, it's
+    /// returned as reverted with an empty trace.
+    pub fn trace_outcome(&mut self, tx: TxEnv) -> TracedOutcome {
+        let mut env = self.env().clone();
+        env.tx = tx;
+        let env = EnvWithHandlerCfg::new_with_spec_id(Box::new(env), self.spec_id());
+        TracedOutcome::new(self.call_bounded(env))
+    }
+
+    /// Executes the given transaction like [`Self::trace_outcome`] and additionally returns the
+    /// accounts and storage slots it changed, see [`StateDiff`].
+    ///
+    /// The values before the transaction are read from the current state, i.e. from the fork for
+    /// accounts that weren't modified locally. Since this reads every changed account once more,
+    /// it's opt-in rather than part of [`Self::trace_outcome`]. If the transaction can't be
+    /// executed at all, the diff is empty.
+    pub fn trace_state_diff(&mut self, tx: TxEnv) -> eyre::Result<(TracedOutcome, StateDiff)> {
+        let mut env = self.env().clone();
+        env.tx = tx;
+        let env = EnvWithHandlerCfg::new_with_spec_id(Box::new(env), self.spec_id());
+
+        let mut result = match self.call_bounded(env) {
+            Ok(result) => result,
+            Err(err) => return Ok((TracedOutcome::new(Err(err)), StateDiff::default())),
+        };
+        let diff = self.state_diff(&std::mem::take(&mut result.state_changeset))?;
+        Ok((TracedOutcome::new(Ok(result)), diff))
+    }
+
+    /// Diffs the given uncommitted state changes against the current state
+    fn state_diff(&self, changeset: &StateChangeset) -> eyre::Result<StateDiff> {
+        let code_hash = |hash: B256| if hash.is_zero() { KECCAK_EMPTY } else { hash };
+
+        let mut accounts = BTreeMap::new();
+        for (address, account) in changeset {
+            if !account.is_touched() {
+                continue;
+            }
+            let before = self.backend().basic_ref(*address)?.unwrap_or_default();
+            let after =
+                if account.is_selfdestructed() { Default::default() } else { account.info.clone() };
+
+            let diff = AccountDiff {
+                balance: Change::new(before.balance, after.balance),
+                nonce: Change::new(before.nonce, after.nonce),
+                code_hash: Change::new(code_hash(before.code_hash), code_hash(after.code_hash)),
+                storage: account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| slot.is_changed())
+                    .map(|(key, slot)| {
+                        (*key, Change { before: slot.original_value(), after: slot.present_value })
+                    })
+                    .collect(),
+            };
+            if !diff.is_empty() {
+                accounts.insert(*address, diff);
+            }
+        }
+        Ok(StateDiff { accounts })
+    }
+
+    /// Returns the current state of the given accounts as JSON, e.g. to inspect the result of a
+    /// traced sequence or to compare it against a golden file.
+    ///
+    /// Every account maps to its balance, nonce and code hash, along with the values of the given
+    /// storage slots, if any, e.g.
+    /// `{"0x…": {"balance": "0x0", "nonce": 0, "codeHash": "0x…", "storage": {"0x0": "0x1"}}}`.
+    pub fn dump_state(
+        &self,
+        addresses: &[Address],
+        slots: &[U256],
+    ) -> eyre::Result<serde_json::Value> {
+        let mut state = serde_json::Map::new();
+        for address in addresses {
+            let info = self.backend().basic_ref(*address)?.unwrap_or_default();
+            let mut account = serde_json::json!({
+                "balance": info.balance,
+                "nonce": info.nonce,
+                "codeHash": if info.code_hash.is_zero() { KECCAK_EMPTY } else { info.code_hash },
+            });
+            if !slots.is_empty() {
+                let storage = slots
+                    .iter()
+                    .map(|slot| {
+                        let value = self.backend().storage_ref(*address, *slot)?;
+                        Ok((format!("{slot:#x}"), serde_json::to_value(value)?))
+                    })
+                    .collect::<eyre::Result<serde_json::Map<_, _>>>()?;
+                account["storage"] = storage.into();
+            }
+            state.insert(address.to_string(), account);
+        }
+        Ok(state.into())
+    }
+
+    /// Decodes the given call trace, annotating each call with its function signature and each
+    /// log with its event signature, e.g. `Transfer(address,address,uint256)`.
+    ///
+    /// Signatures are resolved from the ABIs and signature identifier of the `decoder`, e.g. a
+    /// [`SignaturesIdentifier`](foundry_evm_traces::identifier::SignaturesIdentifier) querying
+    /// the 4byte directory. Unknown selectors are kept as raw hex.
+    ///
+    /// This takes the trace to decode rather than reading it from the executor, since the executor
+    /// doesn't retain the traces of its calls, e.g. the trace of [`TracedOutcome::trace`].
+    pub async fn decoded_trace(arena: &CallTraceArena, decoder: &CallTraceDecoder) -> DecodedTrace {
+        decoder.prefetch_signatures(arena.nodes()).await;
+
+        let mut calls = Vec::with_capacity(arena.nodes().len());
+        for node in arena.nodes() {
+            let trace = &node.trace;
+            let signature = match decoder.decode_function(trace).await.func {
+                Some(func) => func.signature,
+                None => hex::encode_prefixed(&trace.data[..trace.data.len().min(4)]),
+            };
+
+            let mut events = Vec::with_capacity(node.logs.len());
+            for log in &node.logs {
+                events.push(match event_signature(decoder, log).await {
+                    Some(signature) => signature,
+                    None => log.topics().first().map(hex::encode_prefixed).unwrap_or_default(),
+                });
+            }
+
+            calls.push(DecodedTraceCall {
+                depth: trace.depth,
+                address: trace.address,
+                signature,
+                events,
+            });
+        }
+
+        DecodedTrace { calls }
+    }
+
     /// uses the fork block number from the config
     pub async fn get_fork_material(
         config: &Config,
@@ -47,6 +375,354 @@ impl TracingExecutor {
 
         Ok((env, fork, evm_opts.get_remote_chain_id().await))
     }
+
+    /// Same as [`Self::get_fork_material`], but forks at the latest block whose timestamp is at or
+    /// before the given `timestamp`.
+    pub async fn get_fork_material_at_timestamp(
+        config: &Config,
+        evm_opts: EvmOpts,
+        timestamp: u64,
+    ) -> eyre::Result<(Env, Option<CreateFork>, Option<Chain>)> {
+        let fork_url = config.get_rpc_url_or_localhost_http()?.into_owned();
+        let provider = ProviderBuilder::new(&fork_url)
+            .compute_units_per_second(evm_opts.get_compute_units_per_second())
+            .build()?;
+
+        let env_cache = Arc::new(EnvironmentCache::default());
+        let block_number =
+            env_cache.get_block_number_by_timestamp(&provider, &fork_url, timestamp).await?;
+
+        Self::get_fork_material_at_block(config, evm_opts, fork_url, env_cache, block_number).await
+    }
+
+    /// Forks at the given block of the given url, resolving the env with the given cache
+    async fn get_fork_material_at_block(
+        config: &Config,
+        mut evm_opts: EvmOpts,
+        fork_url: String,
+        env_cache: Arc<EnvironmentCache>,
+        block_number: u64,
+    ) -> eyre::Result<(Env, Option<CreateFork>, Option<Chain>)> {
+        evm_opts.fork_url = Some(fork_url);
+        evm_opts.fork_block_number = Some(block_number);
+
+        let env = evm_opts.evm_env(env_cache).await?;
+
+        let fork = evm_opts.get_fork(config, env.clone());
+
+        Ok((env, fork, evm_opts.get_remote_chain_id().await))
+    }
+
+    /// Traces the given transaction against the latest block of the configured RPC, see
+    /// [`Self::trace_outcome`].
+    ///
+    /// Resolves the latest block, forks at it and builds an executor with the configured EVM
+    /// version in one step, e.g. for ad-hoc tracing against the current head.
+    pub async fn trace_latest(
+        config: &Config,
+        evm_opts: EvmOpts,
+        tx: TxEnv,
+    ) -> eyre::Result<TracedOutcome> {
+        let fork_url = config.get_rpc_url_or_localhost_http()?.into_owned();
+        let provider = ProviderBuilder::new(&fork_url)
+            .compute_units_per_second(evm_opts.get_compute_units_per_second())
+            .build()?;
+
+        let env_cache = Arc::new(EnvironmentCache::default());
+        let block_number = env_cache.get_latest_block_number(&provider, &fork_url).await?;
+
+        let (env, fork, _) =
+            Self::get_fork_material_at_block(config, evm_opts, fork_url, env_cache, block_number)
+                .await?;
+        let mut executor =
+            Self::builder().fork(fork).spec(evm_spec_id(&config.evm_version)).build(env);
+        Ok(executor.trace_outcome(tx))
+    }
+}
+
+/// Resolves the signature of the event emitted by the given log, preferring the known ABIs of the
+/// `decoder` over its signature identifier, see [`CallTraceDecoder::decode_event`]
+async fn event_signature(decoder: &CallTraceDecoder, log: &LogData) -> Option<String> {
+    let &[topic, ..] = log.topics() else { return None };
+    if let Some(events) = decoder.events.get(&(topic, log.topics().len() - 1)) {
+        return events
+            .iter()
+            .find(|event| event.decode_log(log, false).is_ok())
+            .map(|event| event.signature());
+    }
+    let identifier = decoder.signature_identifier.as_ref()?;
+    let event = identifier.write().await.identify_event(&topic[..]).await?;
+    Some(event.signature())
+}
+
+/// The outcome of a traced call, see [`TracingExecutor::trace_outcome`].
+#[derive(Clone, Debug)]
+pub enum TracedOutcome {
+    /// The call succeeded.
+    Success {
+        /// The trace of the call.
+        trace: CallTraceArena,
+        /// The data returned by the call.
+        return_data: Bytes,
+        /// The gas used by the call.
+        gas_used: u64,
+    },
+    /// The call reverted.
+    Revert {
+        /// The trace of the call, up to and including the reverting call.
+        trace: CallTraceArena,
+        /// The decoded revert reason.
+        reason: String,
+        /// The gas used by the call.
+        gas_used: u64,
+    },
+}
+
+impl TracedOutcome {
+    /// Creates the outcome of the given call result, treating a call that couldn't be executed as
+    /// reverted with an empty trace.
+    fn new(result: eyre::Result<RawCallResult>) -> Self {
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                return Self::Revert {
+                    trace: CallTraceArena::default(),
+                    reason: err.to_string(),
+                    gas_used: 0,
+                }
+            }
+        };
+
+        let trace = result.traces.unwrap_or_default();
+        if result.reverted {
+            let reason = RevertDecoder::new().decode(&result.result, Some(result.exit_reason));
+            Self::Revert { trace, reason, gas_used: result.gas_used }
+        } else {
+            Self::Success { trace, return_data: result.result, gas_used: result.gas_used }
+        }
+    }
+
+    /// Returns the trace of the call.
+    pub fn trace(&self) -> &CallTraceArena {
+        match self {
+            Self::Success { trace, .. } | Self::Revert { trace, .. } => trace,
+        }
+    }
+
+    /// Returns the gas used by the call.
+    pub fn gas_used(&self) -> u64 {
+        match self {
+            Self::Success { gas_used, .. } | Self::Revert { gas_used, .. } => *gas_used,
+        }
+    }
+
+    /// Returns whether the call reverted.
+    pub fn is_revert(&self) -> bool {
+        matches!(self, Self::Revert { .. })
+    }
+}
+
+/// The accounts and storage slots changed by a traced transaction.
+///
+/// See [`TracingExecutor::trace_state_diff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// The changes of the changed accounts.
+    pub accounts: BTreeMap<Address, AccountDiff>,
+}
+
+/// The changes of an account of a [`StateDiff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountDiff {
+    /// The change of the balance, if changed.
+    pub balance: Option<Change<U256>>,
+    /// The change of the nonce, if changed.
+    pub nonce: Option<Change<u64>>,
+    /// The change of the code hash, if the code changed, e.g. because the account was created.
+    pub code_hash: Option<Change<B256>>,
+    /// The changed storage slots.
+    pub storage: BTreeMap<U256, Change<U256>>,
+}
+
+impl AccountDiff {
+    /// Returns whether nothing about the account changed.
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none() &&
+            self.nonce.is_none() &&
+            self.code_hash.is_none() &&
+            self.storage.is_empty()
+    }
+}
+
+/// A value before and after a traced transaction, see [`StateDiff`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    /// The value before the transaction.
+    pub before: T,
+    /// The value after the transaction.
+    pub after: T,
+}
+
+impl<T: PartialEq> Change<T> {
+    /// Returns the change between the given values, if they differ.
+    fn new(before: T, after: T) -> Option<Self> {
+        (before != after).then_some(Self { before, after })
+    }
+}
+
+/// A call trace whose calls are annotated with their decoded signatures.
+///
+/// See [`TracingExecutor::decoded_trace`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodedTrace {
+    /// The calls of the trace, in the order of the nodes of the trace arena.
+    pub calls: Vec<DecodedTraceCall>,
+}
+
+/// A call of a [`DecodedTrace`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedTraceCall {
+    /// The depth of the call.
+    pub depth: usize,
+    /// The address of the called contract.
+    pub address: Address,
+    /// The function signature, or the raw selector if unknown.
+    pub signature: String,
+    /// The event signatures of the logs emitted by the call, or the raw topic if unknown.
+    pub events: Vec<String>,
+}
+
+/// The builder that allows to configure a [`TracingExecutor`].
+///
+/// By default, tracing is enabled, debugging is disabled and the spec of the default EVM version
+/// is used.
+#[derive(Clone, Debug)]
+#[must_use = "builders do nothing unless you call `build` on them"]
+pub struct TracingExecutorBuilder {
+    /// The fork to spawn the backend with.
+    fork: Option<CreateFork>,
+    /// The memory limit per EVM execution in bytes.
+    memory_limit: Option<u64>,
+    /// The spec ID.
+    spec_id: SpecId,
+    /// Whether to enable the debugger.
+    debug: bool,
+    /// Whether to enable tracing.
+    trace: bool,
+    /// The inspector stack, tracing and the debugger are configured on build unless set.
+    stack: InspectorStackBuilder,
+    /// The wall-clock time a traced transaction may run for.
+    timeout: Option<Duration>,
+    /// The gas limit of traced transactions is capped at.
+    gas_cap: Option<u64>,
+}
+
+impl Default for TracingExecutorBuilder {
+    fn default() -> Self {
+        Self {
+            fork: None,
+            memory_limit: None,
+            spec_id: evm_spec_id(&EvmVersion::default()),
+            debug: false,
+            trace: true,
+            stack: InspectorStackBuilder::default(),
+            timeout: None,
+            gas_cap: None,
+        }
+    }
+}
+
+impl TracingExecutorBuilder {
+    /// Sets the memory limit per EVM execution in bytes.
+    ///
+    /// Overrides the memory limit of the env, including the default limit of its chain.
+    pub fn memory_limit(mut self, memory_limit: u64) -> Self {
+        self.memory_limit = Some(memory_limit);
+        self
+    }
+
+    /// Sets the EVM spec to use.
+    pub fn spec(mut self, spec: SpecId) -> Self {
+        self.spec_id = spec;
+        self
+    }
+
+    /// Sets whether to enable the debugger.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Sets whether to enable tracing.
+    pub fn trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Sets the fork to spawn the backend with.
+    pub fn fork(mut self, fork: Option<CreateFork>) -> Self {
+        self.fork = fork;
+        self
+    }
+
+    /// Sets the wall-clock time a transaction traced with [`TracingExecutor::trace_outcome`] or
+    /// estimated with [`TracingExecutor::estimate_gas`] may run for, e.g. to bound pathological
+    /// loops.
+    ///
+    /// Executions running for longer are halted and fail with a timeout error. Unbounded by
+    /// default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Caps the gas limit of the transactions traced with [`TracingExecutor::trace_outcome`] or
+    /// estimated with [`TracingExecutor::estimate_gas`].
+    pub fn gas_cap(mut self, gas_cap: u64) -> Self {
+        self.gas_cap = Some(gas_cap);
+        self
+    }
+
+    /// Modifies the inspector stack.
+    ///
+    /// Tracing and the debugger set on the stack take precedence over [`Self::trace`] and
+    /// [`Self::debug`].
+    pub fn inspectors(
+        mut self,
+        f: impl FnOnce(InspectorStackBuilder) -> InspectorStackBuilder,
+    ) -> Self {
+        self.stack = f(self.stack);
+        self
+    }
+
+    /// Builds the [`TracingExecutor`] as configured.
+    ///
+    /// Unless a memory limit was set, the memory limit of the env is raised to the default limit
+    /// of its chain, see [`chain_memory_limit`].
+    pub fn build(self, mut env: Env) -> TracingExecutor {
+        let Self { fork, memory_limit, spec_id, debug, trace, mut stack, timeout, gas_cap } = self;
+        match memory_limit {
+            Some(memory_limit) => env.cfg.memory_limit = memory_limit,
+            None => {
+                if let Some(chain_limit) = chain_memory_limit(env.cfg.chain_id) {
+                    env.cfg.memory_limit = env.cfg.memory_limit.max(chain_limit);
+                }
+            }
+        }
+        stack.trace = stack.trace.or(Some(trace));
+        stack.debug = stack.debug.or(Some(debug));
+        let db = Backend::spawn(fork);
+        let forks =
+            db.active_fork_id().map(|id| (DEFAULT_FORK.to_string(), id)).into_iter().collect();
+        TracingExecutor {
+            // configures a bare version of the evm executor: no cheatcode inspector is enabled,
+            // tracing will be enabled only for the targeted transaction
+            executor: ExecutorBuilder::new().inspectors(|_| stack).spec(spec_id).build(env, db),
+            forks,
+            timeout,
+            gas_cap,
+        }
+    }
 }
 
 impl Deref for TracingExecutor {
@@ -62,3 +738,580 @@ impl DerefMut for TracingExecutor {
         &mut self.executor
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inspectors::CustomInspector;
+    use alloy_json_abi::JsonAbi;
+    use alloy_primitives::{keccak256, U256};
+    use alloy_rpc_types::Block;
+    use alloy_sol_types::{Revert, SolError};
+    use foundry_evm_core::backend::{AccessType, RevmDbAccess, StateLookup};
+    use foundry_evm_traces::CallTraceDecoderBuilder;
+    use revm::{
+        interpreter::{CallInputs, Interpreter},
+        primitives::{AccountInfo, Bytecode, TxKind},
+    };
+    use serde_json::{json, Value};
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::Mutex,
+    };
+
+    #[test]
+    fn test_builder_memory_limit() {
+        let executor = TracingExecutor::builder()
+            .memory_limit(1024)
+            .spec(SpecId::CANCUN)
+            .debug(true)
+            .build(Env::default());
+
+        assert_eq!(executor.env().cfg.memory_limit, 1024);
+        assert_eq!(executor.spec_id(), SpecId::CANCUN);
+    }
+
+    #[test]
+    fn test_chain_memory_limit() {
+        let mut env = Env::default();
+        env.cfg.chain_id = NamedChain::Arbitrum as u64;
+        env.cfg.memory_limit = 1 << 27;
+
+        let executor = TracingExecutor::builder().build(env.clone());
+        assert_eq!(executor.env().cfg.memory_limit, 1 << 29);
+
+        // An explicit memory limit takes precedence
+        let executor = TracingExecutor::builder().memory_limit(1024).build(env.clone());
+        assert_eq!(executor.env().cfg.memory_limit, 1024);
+
+        // Other chains keep the memory limit of the env
+        env.cfg.chain_id = NamedChain::Mainnet as u64;
+        let executor = TracingExecutor::builder().build(env);
+        assert_eq!(executor.env().cfg.memory_limit, 1 << 27);
+    }
+
+    #[test]
+    fn test_estimate_gas() {
+        let mut executor = TracingExecutor::builder().build(Env::default());
+
+        // PUSH1 0x01 PUSH1 0x00 SSTORE STOP
+        let target = Address::repeat_byte(0x42);
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x60, 0x01, 0x60, 0x00, 0x55, 0x00]));
+        executor.backend_mut().insert_account_info(
+            target,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+
+        let tx = TxEnv { transact_to: TxKind::Call(target), ..Default::default() };
+        let estimate = executor.estimate_gas(tx.clone()).unwrap();
+        // 21000 intrinsic + 2 pushes + a cold zero to non-zero SSTORE
+        assert!((43_000..=45_000).contains(&estimate), "{estimate}");
+
+        let call = |gas_limit| {
+            let mut env = executor.env().clone();
+            env.tx = TxEnv { gas_limit, ..tx.clone() };
+            executor.call_with_env(EnvWithHandlerCfg::new_with_spec_id(
+                Box::new(env),
+                executor.spec_id(),
+            ))
+        };
+        assert!(!call(estimate).unwrap().reverted);
+        assert!(call(estimate - 1).unwrap().reverted);
+    }
+
+    #[test]
+    fn test_estimate_gas_revert() {
+        let mut executor = TracingExecutor::builder().build(Env::default());
+
+        // PUSH1 0x00 PUSH1 0x00 REVERT
+        let target = Address::repeat_byte(0x42);
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x60, 0x00, 0x60, 0x00, 0xfd]));
+        executor.backend_mut().insert_account_info(
+            target,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+
+        let tx = TxEnv { transact_to: TxKind::Call(target), ..Default::default() };
+        assert!(executor.estimate_gas(tx).is_err());
+    }
+
+    #[test]
+    fn test_timeout_and_gas_cap() {
+        // JUMPDEST PUSH1 0x00 JUMP
+        let target = Address::repeat_byte(0x42);
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x5b, 0x60, 0x00, 0x56]));
+        let executor = |builder: TracingExecutorBuilder| {
+            let mut executor = builder.build(Env::default());
+            executor.backend_mut().insert_account_info(
+                target,
+                AccountInfo {
+                    code_hash: code.hash_slow(),
+                    code: Some(code.clone()),
+                    ..Default::default()
+                },
+            );
+            executor
+        };
+        let tx = TxEnv { transact_to: TxKind::Call(target), ..Default::default() };
+
+        let timeout = Duration::from_millis(100);
+        let mut looping = executor(TracingExecutor::builder().timeout(timeout));
+        let TracedOutcome::Revert { reason, .. } = looping.trace_outcome(tx.clone()) else {
+            panic!("expected the loop to time out")
+        };
+        assert!(reason.contains("timed out"), "{reason}");
+        assert!(looping.inspector().deadline.is_none());
+
+        // The loop runs out of the capped gas instead
+        let mut capped = executor(TracingExecutor::builder().gas_cap(100_000));
+        let outcome = capped.trace_outcome(tx);
+        assert!(outcome.is_revert());
+        assert_eq!(outcome.gas_used(), 100_000);
+    }
+
+    #[test]
+    fn test_trace_outcome_success() {
+        let mut executor = TracingExecutor::builder().build(Env::default());
+
+        // PUSH1 0x2a PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN
+        let target = Address::repeat_byte(0x42);
+        let code = Bytecode::new_raw(Bytes::from_static(&[
+            0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3,
+        ]));
+        executor.backend_mut().insert_account_info(
+            target,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+
+        let tx = TxEnv { transact_to: TxKind::Call(target), ..Default::default() };
+        let TracedOutcome::Success { trace, return_data, gas_used } = executor.trace_outcome(tx)
+        else {
+            panic!("expected a successful call")
+        };
+        assert_eq!(return_data, U256::from(42).to_be_bytes_vec());
+        assert!(gas_used > 21_000);
+        assert_eq!(trace.nodes().len(), 1);
+        assert_eq!(trace.nodes()[0].trace.address, target);
+        assert!(trace.nodes()[0].trace.success);
+    }
+
+    /// Counts the executed instructions and calls
+    #[derive(Debug, Default)]
+    struct CountingInspector {
+        steps: usize,
+        calls: usize,
+    }
+
+    impl CustomInspector for CountingInspector {
+        fn step(&mut self, _interp: &Interpreter, _env: &Env) {
+            self.steps += 1;
+        }
+
+        fn call(&mut self, _inputs: &CallInputs, _env: &Env) {
+            self.calls += 1;
+        }
+    }
+
+    #[test]
+    fn test_custom_inspector() {
+        let counter = Arc::new(parking_lot::Mutex::new(CountingInspector::default()));
+        let mut executor =
+            TracingExecutor::with_inspectors(Env::default(), None, None, false, |stack| {
+                stack.custom(counter.clone())
+            });
+        assert!(executor.inspector().tracer.is_some());
+
+        // PUSH1 0x01 PUSH1 0x00 SSTORE STOP
+        let target = Address::repeat_byte(0x42);
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x60, 0x01, 0x60, 0x00, 0x55, 0x00]));
+        executor.backend_mut().insert_account_info(
+            target,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+        executor.call_raw(Address::ZERO, target, Bytes::new(), U256::ZERO).unwrap();
+
+        let counter = counter.lock();
+        assert_eq!(counter.steps, 4);
+        assert_eq!(counter.calls, 1);
+    }
+
+    #[test]
+    fn test_trace_outcome_revert() {
+        let mut executor = TracingExecutor::builder().build(Env::default());
+
+        // revert with `Error("nope")`
+        let target = Address::repeat_byte(0x42);
+        let error = Revert::from("nope").abi_encode();
+        let mut code = Vec::new();
+        for (i, chunk) in error.chunks(32).enumerate() {
+            let mut word = [0u8; 32];
+            word[..chunk.len()].copy_from_slice(chunk);
+            // PUSH32 <word> PUSH2 <offset> MSTORE
+            code.push(0x7f);
+            code.extend_from_slice(&word);
+            code.extend_from_slice(&[0x61, 0x00, (i * 32) as u8, 0x52]);
+        }
+        // PUSH2 <len> PUSH1 0x00 REVERT
+        code.extend_from_slice(&[0x61, 0x00, error.len() as u8, 0x60, 0x00, 0xfd]);
+        let code = Bytecode::new_raw(code.into());
+        executor.backend_mut().insert_account_info(
+            target,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+
+        let tx = TxEnv { transact_to: TxKind::Call(target), ..Default::default() };
+        let outcome = executor.trace_outcome(tx);
+        assert!(outcome.is_revert());
+        let TracedOutcome::Revert { trace, reason, gas_used } = outcome else { unreachable!() };
+        assert_eq!(reason, "revert: nope");
+        assert!(gas_used > 21_000);
+        assert_eq!(trace.nodes().len(), 1);
+        assert!(!trace.nodes()[0].trace.success);
+    }
+
+    #[test]
+    fn test_dump_state() {
+        let mut executor = TracingExecutor::builder().build(Env::default());
+
+        // PUSH1 0x2a PUSH1 0x01 SSTORE STOP
+        let target = Address::repeat_byte(0x42);
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x60, 0x2a, 0x60, 0x01, 0x55, 0x00]));
+        let code_hash = code.hash_slow();
+        executor.backend_mut().insert_account_info(
+            target,
+            AccountInfo { nonce: 1, code_hash, code: Some(code), ..Default::default() },
+        );
+
+        let mut env = executor.env().clone();
+        env.tx = TxEnv { transact_to: TxKind::Call(target), ..Default::default() };
+        let env = EnvWithHandlerCfg::new_with_spec_id(Box::new(env), executor.spec_id());
+        assert!(!executor.transact_with_env(env).unwrap().reverted);
+
+        let state = executor.dump_state(&[target], &[U256::ZERO, U256::from(1)]).unwrap();
+        assert_eq!(
+            state,
+            json!({
+                target.to_string(): {
+                    "balance": "0x0",
+                    "nonce": 1,
+                    "codeHash": code_hash,
+                    "storage": { "0x0": "0x0", "0x1": "0x2a" },
+                }
+            })
+        );
+
+        // Storage is only dumped if slots are selected
+        let state = executor.dump_state(&[target], &[]).unwrap();
+        assert!(state[target.to_string()].get("storage").is_none());
+    }
+
+    /// The requests received by a [`spawn_rpc_server`], as the method and the address it's for
+    type RecordedRequests = Arc<Mutex<Vec<(String, Option<Address>)>>>;
+
+    /// Spawns a minimal JSON-RPC server for the given chain at block 100, where `target` has the
+    /// given code and all of its storage slots are 42, and records the requests it receives.
+    fn spawn_rpc_server(chain_id: u64, target: Address, code: Bytes) -> (String, RecordedRequests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = RecordedRequests::default();
+
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (code, requests) = (code.clone(), recorded.clone());
+                std::thread::spawn(move || {
+                    let mut stream = BufReader::new(stream.unwrap());
+                    loop {
+                        let mut content_length = 0;
+                        loop {
+                            let mut line = String::new();
+                            if stream.read_line(&mut line).unwrap_or(0) == 0 {
+                                return;
+                            }
+                            if line.trim_end().is_empty() {
+                                break;
+                            }
+                            if let Some((name, value)) = line.split_once(':') {
+                                if name.eq_ignore_ascii_case("content-length") {
+                                    content_length = value.trim().parse().unwrap();
+                                }
+                            }
+                        }
+                        let mut body = vec![0; content_length];
+                        stream.read_exact(&mut body).unwrap();
+
+                        let request: Value = serde_json::from_slice(&body).unwrap();
+                        let method = request["method"].as_str().unwrap();
+                        let address = request["params"][0].as_str().and_then(|p| p.parse().ok());
+                        requests.lock().unwrap().push((method.to_string(), address));
+
+                        let result = match method {
+                            "eth_chainId" => json!(format!("{chain_id:#x}")),
+                            "eth_blockNumber" => json!("0x64"),
+                            "eth_gasPrice" => json!("0x1"),
+                            "eth_getBlockByNumber" => {
+                                let mut block: Block = Block::default();
+                                block.header.number = Some(100);
+                                block.header.gas_limit = 30_000_000;
+                                serde_json::to_value(block).unwrap()
+                            }
+                            "eth_getCode" if address == Some(target) => json!(code),
+                            "eth_getCode" => json!("0x"),
+                            "eth_getStorageAt" => json!(U256::from(42)),
+                            _ => json!("0x0"),
+                        };
+                        let response =
+                            json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+                                .to_string();
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{response}",
+                            response.len()
+                        );
+                        stream.get_mut().write_all(response.as_bytes()).unwrap();
+                    }
+                });
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[test]
+    fn test_warm_from_accesses() {
+        // PUSH1 0x00 SLOAD STOP
+        let target = Address::repeat_byte(0x42);
+        let (url, requests) =
+            spawn_rpc_server(1, target, Bytes::from_static(&[0x60, 0x00, 0x54, 0x00]));
+
+        let mut env = Env::default();
+        env.cfg.chain_id = 1;
+        env.block.number = U256::from(100);
+        let fork = CreateFork {
+            enable_caching: false,
+            url: url.clone(),
+            env: env.clone(),
+            evm_opts: EvmOpts {
+                fork_url: Some(url),
+                fork_block_number: Some(100),
+                ..Default::default()
+            },
+        };
+        let mut executor = TracingExecutor::builder().fork(Some(fork)).build(env);
+
+        let manifest = [
+            RevmDbAccess::Basic(Address::ZERO),
+            RevmDbAccess::Basic(target),
+            RevmDbAccess::Storage(target, U256::ZERO),
+        ]
+        .map(|access| access.to_access(Chain::mainnet(), StateLookup::RollN(0)));
+        executor.warm_from_accesses(&manifest).unwrap();
+
+        let is_manifested = |(method, address): &(String, Option<Address>)| {
+            method != "eth_getBlockByNumber" &&
+                address.is_some_and(|address| address == target || address == Address::ZERO)
+        };
+        let warmed = requests.lock().unwrap().len();
+        assert!(requests.lock().unwrap().iter().any(is_manifested));
+
+        let tx = TxEnv { transact_to: TxKind::Call(target), ..Default::default() };
+        let outcome = executor.trace_outcome(tx);
+        assert!(!outcome.is_revert(), "{outcome:?}");
+        assert_eq!(outcome.trace().nodes()[0].trace.address, target);
+
+        // The manifested reads were served from the cache
+        assert!(!requests.lock().unwrap()[warmed..].iter().any(is_manifested));
+    }
+
+    #[test]
+    fn test_trace_state_diff() {
+        // A minimal ERC20 `transfer(to, amount)` without checks, with the balances mapping at slot
+        // 0:
+        // CALLER PUSH1 0x00 MSTORE PUSH1 0x40 PUSH1 0x00 SHA3
+        // DUP1 SLOAD PUSH1 0x24 CALLDATALOAD SWAP1 SUB SWAP1 SSTORE
+        // PUSH1 0x04 CALLDATALOAD PUSH1 0x00 MSTORE PUSH1 0x40 PUSH1 0x00 SHA3
+        // DUP1 SLOAD PUSH1 0x24 CALLDATALOAD ADD SWAP1 SSTORE STOP
+        let token = Address::repeat_byte(0x42);
+        let code = Bytes::from_static(&[
+            0x33, 0x60, 0x00, 0x52, 0x60, 0x40, 0x60, 0x00, 0x20, 0x80, 0x54, 0x60, 0x24, 0x35,
+            0x90, 0x03, 0x90, 0x55, 0x60, 0x04, 0x35, 0x60, 0x00, 0x52, 0x60, 0x40, 0x60, 0x00,
+            0x20, 0x80, 0x54, 0x60, 0x24, 0x35, 0x01, 0x90, 0x55, 0x00,
+        ]);
+        // Every balance on the fork is 42
+        let (url, _) = spawn_rpc_server(1, token, code);
+
+        let mut env = Env::default();
+        env.cfg.chain_id = 1;
+        env.block.number = U256::from(100);
+        let fork = CreateFork {
+            enable_caching: false,
+            url: url.clone(),
+            env: env.clone(),
+            evm_opts: EvmOpts {
+                fork_url: Some(url),
+                fork_block_number: Some(100),
+                ..Default::default()
+            },
+        };
+        let mut executor = TracingExecutor::builder().fork(Some(fork)).build(env);
+
+        let (from, to) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02));
+        let mut calldata = vec![0xa9, 0x05, 0x9c, 0xbb];
+        calldata.extend_from_slice(to.into_word().as_slice());
+        calldata.extend_from_slice(&U256::from(10).to_be_bytes::<32>());
+        let tx = TxEnv {
+            caller: from,
+            transact_to: TxKind::Call(token),
+            data: calldata.into(),
+            gas_limit: 1_000_000,
+            ..Default::default()
+        };
+        let (outcome, diff) = executor.trace_state_diff(tx).unwrap();
+        assert!(!outcome.is_revert(), "{outcome:?}");
+
+        let balance_slot = |holder: Address| {
+            let mut key = holder.into_word().to_vec();
+            key.extend_from_slice(&[0; 32]);
+            U256::from_be_bytes(keccak256(key).0)
+        };
+        let token_diff = &diff.accounts[&token];
+        assert_eq!(
+            token_diff.storage,
+            BTreeMap::from([
+                (balance_slot(from), Change { before: U256::from(42), after: U256::from(32) }),
+                (balance_slot(to), Change { before: U256::from(42), after: U256::from(52) }),
+            ])
+        );
+        assert!(token_diff.balance.is_none() && token_diff.code_hash.is_none());
+
+        // The transaction wasn't committed
+        assert_eq!(
+            executor.backend().storage_ref(token, balance_slot(from)).unwrap(),
+            U256::from(42)
+        );
+    }
+
+    #[test]
+    fn test_multiple_forks() {
+        // PUSH1 0x00 SLOAD STOP
+        let target = Address::repeat_byte(0x42);
+        let code = Bytes::from_static(&[0x60, 0x00, 0x54, 0x00]);
+        let create_fork = |chain_id: u64| {
+            let (url, _) = spawn_rpc_server(chain_id, target, code.clone());
+            let mut env = Env::default();
+            env.cfg.chain_id = chain_id;
+            env.block.number = U256::from(100);
+            let fork = CreateFork {
+                enable_caching: false,
+                url: url.clone(),
+                env: env.clone(),
+                evm_opts: EvmOpts {
+                    fork_url: Some(url),
+                    fork_block_number: Some(100),
+                    ..Default::default()
+                },
+            };
+            (env, fork)
+        };
+
+        let (env, mainnet) = create_fork(1);
+        let mut executor = TracingExecutor::builder().fork(Some(mainnet)).build(env);
+        let (_, optimism) = create_fork(10);
+        executor.add_fork("optimism", optimism.clone()).unwrap();
+        assert!(executor.add_fork("optimism", optimism).is_err());
+        assert!(executor.select_fork("unknown").is_err());
+
+        let tx =
+            TxEnv { transact_to: TxKind::Call(target), gas_limit: 1_000_000, ..Default::default() };
+        assert!(!executor.trace_outcome(tx.clone()).is_revert());
+
+        executor.select_fork("optimism").unwrap();
+        assert_eq!(executor.env().cfg.chain_id, 10);
+        let outcome = executor.trace_outcome(tx);
+        assert!(!outcome.is_revert(), "{outcome:?}");
+
+        executor.select_fork(DEFAULT_FORK).unwrap();
+        assert_eq!(executor.env().cfg.chain_id, 1);
+        // switching forks doesn't turn the caller into a test contract
+        let caller = executor.env().tx.caller;
+        assert!(!executor.backend().is_persistent(&caller));
+        assert!(executor.backend().test_contract_address().is_none());
+
+        let storage = AccessType::RevmDbAccess(RevmDbAccess::Storage(target, U256::ZERO));
+        let accesses = executor.backend().get_accesses();
+        for chain in [Chain::mainnet(), Chain::optimism_mainnet()] {
+            assert!(
+                accesses
+                    .iter()
+                    .any(|access| access.chain == chain && access.access_type == storage),
+                "{accesses:?}"
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_trace_latest() {
+        // PUSH1 0x00 SLOAD STOP
+        let target = Address::repeat_byte(0x42);
+        let (url, requests) =
+            spawn_rpc_server(1, target, Bytes::from_static(&[0x60, 0x00, 0x54, 0x00]));
+        let config = Config { eth_rpc_url: Some(url), ..Default::default() };
+
+        let tx =
+            TxEnv { transact_to: TxKind::Call(target), gas_limit: 1_000_000, ..Default::default() };
+        let outcome = TracingExecutor::trace_latest(&config, EvmOpts::default(), tx).await.unwrap();
+        assert!(!outcome.is_revert(), "{outcome:?}");
+        assert_eq!(outcome.trace().nodes()[0].trace.address, target);
+
+        // The head was resolved from the RPC rather than configured
+        assert!(requests.lock().unwrap().iter().any(|(method, _)| method == "eth_blockNumber"));
+    }
+
+    #[test]
+    fn test_warm_from_accesses_not_forked() {
+        let mut executor = TracingExecutor::builder().build(Env::default());
+        assert!(executor.warm_from_accesses(&[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decoded_trace() {
+        let mut executor = TracingExecutor::builder().build(Env::default());
+
+        let abi = JsonAbi::parse(["function increment()", "event Incremented(uint256 indexed by)"])
+            .unwrap();
+        let calldata = abi.function("increment").unwrap()[0].selector().to_vec();
+        let topic = abi.event("Incremented").unwrap()[0].selector();
+
+        // PUSH1 0x01 PUSH32 <topic> PUSH1 0x00 PUSH1 0x00 LOG2 STOP
+        let target = Address::repeat_byte(0x42);
+        let mut code = vec![0x60, 0x01, 0x7f];
+        code.extend_from_slice(topic.as_slice());
+        code.extend_from_slice(&[0x60, 0x00, 0x60, 0x00, 0xa2, 0x00]);
+        let code = Bytecode::new_raw(code.into());
+        executor.backend_mut().insert_account_info(
+            target,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+
+        let known = executor.call_raw(Address::ZERO, target, calldata.into(), U256::ZERO).unwrap();
+        let unknown = executor
+            .call_raw(Address::ZERO, target, Bytes::from_static(&[1, 2, 3, 4]), U256::ZERO)
+            .unwrap();
+
+        let decoder = CallTraceDecoderBuilder::new().with_abi(&abi).build();
+        let decoded =
+            TracingExecutor::decoded_trace(known.traces.as_ref().unwrap(), &decoder).await;
+        assert_eq!(decoded.calls.len(), 1);
+        assert_eq!(decoded.calls[0].address, target);
+        assert_eq!(decoded.calls[0].signature, "increment()");
+        assert_eq!(decoded.calls[0].events, ["Incremented(uint256)"]);
+
+        let decoded = TracingExecutor::decoded_trace(
+            unknown.traces.as_ref().unwrap(),
+            &CallTraceDecoderBuilder::new().build(),
+        )
+        .await;
+        assert_eq!(decoded.calls[0].signature, "0x01020304");
+        assert_eq!(decoded.calls[0].events, [topic.to_string()]);
+    }
+}
@@ -0,0 +1,35 @@
+use alloy_primitives::Log;
+use parking_lot::Mutex;
+use revm::{
+    interpreter::{CallInputs, CallOutcome, Interpreter},
+    primitives::Env,
+};
+use std::{fmt, sync::Arc};
+
+/// An inspector observing the execution in addition to the built-in inspectors of the
+/// [`InspectorStack`](super::InspectorStack), e.g. to record data accesses or collect metrics.
+///
+/// Unlike the built-in inspectors it can't alter the execution, and it doesn't get access to the
+/// database, so it can be installed regardless of the database the stack is run with.
+pub trait CustomInspector: fmt::Debug + Send + Sync {
+    /// Called before each instruction is executed.
+    fn step(&mut self, _interp: &Interpreter, _env: &Env) {}
+
+    /// Called after each instruction is executed.
+    fn step_end(&mut self, _interp: &Interpreter, _env: &Env) {}
+
+    /// Called when a log is emitted.
+    fn log(&mut self, _log: &Log) {}
+
+    /// Called before a call is executed.
+    fn call(&mut self, _inputs: &CallInputs, _env: &Env) {}
+
+    /// Called after a call is executed, with the outcome of the built-in inspectors.
+    fn call_end(&mut self, _inputs: &CallInputs, _outcome: &CallOutcome) {}
+}
+
+/// A [`CustomInspector`] installed in an [`InspectorStack`](super::InspectorStack).
+///
+/// The inspector is shared by the clones of the stack, so that the executions of an executor, which
+/// each run with a clone of its stack, are all observed by the same inspector.
+pub type SharedInspector = Arc<Mutex<dyn CustomInspector>>;
@@ -10,6 +10,9 @@ pub use revm_inspectors::access_list::AccessListInspector;
 mod chisel_state;
 pub use chisel_state::ChiselState;
 
+mod custom;
+pub use custom::{CustomInspector, SharedInspector};
+
 mod debugger;
 pub use debugger::Debugger;
 
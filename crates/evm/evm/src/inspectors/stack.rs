@@ -1,6 +1,6 @@
 use super::{
-    Cheatcodes, CheatsConfig, ChiselState, CoverageCollector, Debugger, Fuzzer, LogCollector,
-    StackSnapshotType, TracingInspector, TracingInspectorConfig,
+    Cheatcodes, CheatsConfig, ChiselState, CoverageCollector, CustomInspector, Debugger, Fuzzer,
+    LogCollector, SharedInspector, StackSnapshotType, TracingInspector, TracingInspectorConfig,
 };
 use alloy_primitives::{Address, Bytes, Log, U256};
 use foundry_evm_core::{
@@ -19,7 +19,7 @@ use revm::{
     primitives::{BlockEnv, CreateScheme, Env, EnvWithHandlerCfg, ExecutionResult, Output, TxKind},
     DatabaseCommit, EvmContext, Inspector,
 };
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 
 #[derive(Clone, Debug, Default)]
 #[must_use = "builders do nothing unless you call `build` on them"]
@@ -54,6 +54,8 @@ pub struct InspectorStackBuilder {
     /// In isolation mode all top-level calls are executed as a separate transaction in a separate
     /// EVM context, enabling more precise gas accounting and transaction state changes.
     pub enable_isolation: bool,
+    /// The custom inspectors, see [`CustomInspector`].
+    pub custom: Vec<SharedInspector>,
 }
 
 impl InspectorStackBuilder {
@@ -141,6 +143,13 @@ impl InspectorStackBuilder {
         self
     }
 
+    /// Adds a custom inspector observing the execution, see [`CustomInspector`].
+    #[inline]
+    pub fn custom(mut self, inspector: SharedInspector) -> Self {
+        self.custom.push(inspector);
+        self
+    }
+
     /// Builds the stack of inspectors to use when transacting/committing on the EVM.
     pub fn build(self) -> InspectorStack {
         let Self {
@@ -155,6 +164,7 @@ impl InspectorStackBuilder {
             print,
             chisel_state,
             enable_isolation,
+            custom,
         } = self;
         let mut stack = InspectorStack::new();
 
@@ -175,6 +185,7 @@ impl InspectorStackBuilder {
         stack.tracing(trace.unwrap_or(false));
 
         stack.enable_isolation(enable_isolation);
+        stack.custom = custom;
 
         // environment, must come after all of the inspectors
         if let Some(block) = block {
@@ -274,7 +285,11 @@ pub struct InspectorStack {
     pub log_collector: Option<LogCollector>,
     pub printer: Option<CustomPrintTracer>,
     pub tracer: Option<TracingInspector>,
+    pub custom: Vec<SharedInspector>,
     pub enable_isolation: bool,
+    /// The instant after which the execution is halted as out of gas, if any, e.g. to bound the
+    /// wall-clock time of a pathological loop.
+    pub deadline: Option<Instant>,
 
     /// Flag marking if we are in the inner EVM context.
     pub in_inner_context: bool,
@@ -306,6 +321,9 @@ impl InspectorStack {
                 };
             }
             push!(cheatcodes, chisel_state, coverage, debugger, fuzzer, log_collector, printer, tracer);
+            if !self.custom.is_empty() {
+                enabled.push("custom");
+            }
             if self.enable_isolation {
                 enabled.push("isolation");
             }
@@ -399,6 +417,20 @@ impl InspectorStack {
         });
     }
 
+    /// Adds a custom inspector observing the execution, see [`CustomInspector`].
+    #[inline]
+    pub fn add_custom(&mut self, inspector: SharedInspector) {
+        self.custom.push(inspector);
+    }
+
+    /// Calls the given function on each custom inspector.
+    #[inline]
+    fn call_custom(&self, mut f: impl FnMut(&mut dyn CustomInspector)) {
+        for inspector in &self.custom {
+            f(&mut *inspector.lock());
+        }
+    }
+
     /// Collects all the data gathered during inspection into a single struct.
     #[inline]
     pub fn collect(self) -> InspectorData {
@@ -615,6 +647,11 @@ impl<DB: DatabaseExt + DatabaseCommit> Inspector<&mut DB> for InspectorStack {
     }
 
     fn step(&mut self, interpreter: &mut Interpreter, ecx: &mut EvmContext<&mut DB>) {
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            interpreter.instruction_result = InstructionResult::OutOfGas;
+            return;
+        }
+        self.call_custom(|inspector| inspector.step(interpreter, &ecx.env));
         call_inspectors_adjust_depth!(
             [
                 &mut self.fuzzer,
@@ -631,6 +668,7 @@ impl<DB: DatabaseExt + DatabaseCommit> Inspector<&mut DB> for InspectorStack {
     }
 
     fn step_end(&mut self, interpreter: &mut Interpreter, ecx: &mut EvmContext<&mut DB>) {
+        self.call_custom(|inspector| inspector.step_end(interpreter, &ecx.env));
         call_inspectors_adjust_depth!(
             [&mut self.tracer, &mut self.chisel_state, &mut self.printer],
             |inspector| inspector.step_end(interpreter, ecx),
@@ -640,6 +678,7 @@ impl<DB: DatabaseExt + DatabaseCommit> Inspector<&mut DB> for InspectorStack {
     }
 
     fn log(&mut self, ecx: &mut EvmContext<&mut DB>, log: &Log) {
+        self.call_custom(|inspector| inspector.log(log));
         call_inspectors_adjust_depth!(
             [&mut self.tracer, &mut self.log_collector, &mut self.cheatcodes, &mut self.printer],
             |inspector| inspector.log(ecx, log),
@@ -658,6 +697,7 @@ impl<DB: DatabaseExt + DatabaseCommit> Inspector<&mut DB> for InspectorStack {
             return None;
         }
 
+        self.call_custom(|inspector| inspector.call(call, &ecx.env));
         call_inspectors_adjust_depth!(
             #[ret]
             [
@@ -713,6 +753,7 @@ impl<DB: DatabaseExt + DatabaseCommit> Inspector<&mut DB> for InspectorStack {
         }
 
         let outcome = self.do_call_end(ecx, inputs, outcome);
+        self.call_custom(|inspector| inspector.call_end(inputs, &outcome));
         if outcome.result.is_revert() {
             // Encountered a revert, since cheatcodes may have altered the evm state in such a way
             // that violates some constraints, e.g. `deal`, we need to manually roll back on revert
@@ -1,16 +1,55 @@
 use clap::{Parser, ValueHint};
 use eyre::Result;
 use foundry_cli::{
-    opts::{CoreBuildArgs, ProjectPathsArgs},
+    opts::{CompilerArgs, CoreBuildArgs, ProjectPathsArgs},
     utils::LoadConfig,
 };
 use foundry_common::{compile::with_compilation_reporter, fs};
 use foundry_compilers::{
-    compilers::solc::SolcLanguage,
+    artifacts::{EvmVersion, Source},
+    compilers::{multi::MultiCompilerParsedSource, solc::SolcLanguage},
     error::SolcError,
     flatten::{Flattener, FlattenerError},
+    Graph, Project,
 };
-use std::path::PathBuf;
+use foundry_config::filter::GlobMatcher;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// Matches the names of top-level definitions, which the flattener emits unindented
+static DEFINITION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?m)^(?:abstract\s+)?(?:contract|interface|library|struct|enum|error|event|function|type)\s+([A-Za-z_$][\w$]*)",
+    )
+    .unwrap()
+});
+
+/// Matches `pragma abicoder` and `pragma experimental` directives
+static PRAGMA_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*pragma\s+(abicoder|experimental)\s+([^;\s]+)\s*;").unwrap());
+
+/// Matches SPDX license identifiers
+static LICENSE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"SPDX-License-Identifier:\s*([^\s*]+)").unwrap());
+
+/// Matches the top-level `import` and `from ... import` statements of Vyper sources
+static VYPER_IMPORT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?:from\s+(?P<from>\.*[\w.]*)\s+)?import\s+(?P<module>[\w.]+)(?:\s+as\s+(?P<alias>\w+))?\s*(?:#.*)?$",
+    )
+    .unwrap()
+});
+
+/// Matches the public state variables of Vyper sources, e.g. `owner: public(address)`
+static VYPER_PUBLIC_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(\w+)\s*:\s*public\((.+)\)\s*(?:#.*)?$").unwrap());
+
+/// The extensions of Vyper source files
+const VYPER_EXTENSIONS: &[&str] = &["vy", "vyi"];
 
 /// CLI arguments for `forge flatten`.
 #[derive(Clone, Debug, Parser)]
@@ -30,20 +69,122 @@ pub struct FlattenArgs {
     )]
     pub output: Option<PathBuf>,
 
+    /// The path to output the solc standard JSON input for the contract.
+    ///
+    /// The standard JSON input contains the target, all of its imports and the project's
+    /// compiler settings, and is emitted alongside the flattened contract. This is the preferred
+    /// format for verifying contracts on Etherscan.
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+    )]
+    pub output_artifact: Option<PathBuf>,
+
+    /// The target EVM version to flatten for.
+    ///
+    /// Overrides the EVM version of the config when compiling the target, so that the flattened
+    /// source is validated against the intended EVM version.
+    #[arg(long, value_name = "VERSION")]
+    pub evm_version: Option<EvmVersion>,
+
+    /// Annotate the flattened contract with a banner comment before the definitions of each
+    /// source file, e.g. `// ===== File: src/Counter.sol =====`.
+    #[arg(long)]
+    pub annotate: bool,
+
+    /// Consolidate the `pragma abicoder` and `pragma experimental` directives of all flattened
+    /// sources into a single directive each.
+    ///
+    /// Fails if the sources require conflicting ABI coders, e.g. `v1` and `v2`.
+    #[arg(long)]
+    pub normalize: bool,
+
+    /// Omit the source files matching the given glob from the flattened contract, e.g. test
+    /// helpers or interfaces provided separately. Can be repeated.
+    ///
+    /// Fails if the remaining sources use a definition of an excluded source file, unless
+    /// `--force` is given.
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<GlobMatcher>,
+
+    /// Omit the excluded source files even if the remaining sources depend on them.
+    #[arg(long, requires = "exclude")]
+    pub force: bool,
+
+    /// Print a summary of the SPDX license identifiers of the flattened sources, along with the
+    /// source files using each.
+    ///
+    /// The summary is printed to stderr if the flattened contract is output to stdout.
+    #[arg(long)]
+    pub license_report: bool,
+
     #[command(flatten)]
     project_paths: ProjectPathsArgs,
 }
 
 impl FlattenArgs {
     pub fn run(self) -> Result<()> {
-        let Self { target_path, output, project_paths } = self;
+        let Self {
+            target_path,
+            output,
+            output_artifact,
+            evm_version,
+            annotate,
+            normalize,
+            exclude,
+            force,
+            license_report,
+            project_paths,
+        } = self;
 
         // flatten is a subset of `BuildArgs` so we can reuse that to get the config
-        let build_args = CoreBuildArgs { project_paths, ..Default::default() };
+        let build_args = CoreBuildArgs {
+            project_paths,
+            compiler: CompilerArgs { evm_version, ..Default::default() },
+            ..Default::default()
+        };
         let config = build_args.try_load_config_emit_warnings()?;
         let project = config.create_project(false, true)?;
 
         let target_path = dunce::canonicalize(target_path)?;
+        let is_vyper = target_path
+            .extension()
+            .is_some_and(|extension| VYPER_EXTENSIONS.iter().any(|vyper| extension == *vyper));
+        if is_vyper && (annotate || normalize || license_report || !exclude.is_empty()) {
+            eyre::bail!(
+                "--annotate, --normalize, --license-report and --exclude are not supported for \
+                 Vyper sources"
+            );
+        }
+
+        if let Some(output_artifact) = output_artifact {
+            let input = project
+                .standard_json_input(&target_path)
+                .map_err(|err| eyre::eyre!("Failed to get standard json input: {err}"))?;
+            fs::create_dir_all(output_artifact.parent().unwrap())?;
+            fs::write_json_file(&output_artifact, &input)?;
+            // Without `--output` the flattened source is printed to stdout, so keep it clean
+            let message = format!("Standard JSON input written at {}", output_artifact.display());
+            if output.is_some() {
+                println!("{message}");
+            } else {
+                eprintln!("{message}");
+            }
+        }
+
+        if is_vyper {
+            let flattened = flatten_vyper(&project, &target_path)?;
+            match output {
+                Some(output) => {
+                    fs::create_dir_all(output.parent().unwrap())?;
+                    fs::write(&output, flattened)?;
+                    println!("Flattened file written at {}", output.display());
+                }
+                None => println!("{flattened}"),
+            }
+            return Ok(());
+        }
 
         let flattener = with_compilation_reporter(build_args.silent, || {
             Flattener::new(project.clone(), &target_path)
@@ -55,21 +196,488 @@ impl FlattenArgs {
                 // Fallback to the old flattening implementation if we couldn't compile the target
                 // successfully. This would be the case if the target has invalid
                 // syntax. (e.g. Solang)
-                project.paths.with_language::<SolcLanguage>().flatten(&target_path)
+                project.paths.clone().with_language::<SolcLanguage>().flatten(&target_path)
             }
             Err(FlattenerError::Other(err)) => Err(err),
         }
         .map_err(|err: SolcError| eyre::eyre!("Failed to flatten: {err}"))?;
 
+        let flattened = if exclude.is_empty() {
+            flattened
+        } else {
+            exclude_sources(&project, &target_path, &flattened, &exclude, force)?
+        };
+        let flattened = if normalize {
+            normalize_pragmas(&project, &target_path, &flattened)?
+        } else {
+            flattened
+        };
+        let flattened =
+            if annotate { annotate_sources(&project, &target_path, flattened)? } else { flattened };
+        let report =
+            if license_report { Some(report_licenses(&project, &target_path)?) } else { None };
+
         match output {
             Some(output) => {
                 fs::create_dir_all(output.parent().unwrap())?;
                 fs::write(&output, flattened)?;
                 println!("Flattened file written at {}", output.display());
+                if let Some(report) = report {
+                    println!("{report}");
+                }
+            }
+            None => {
+                println!("{flattened}");
+                if let Some(report) = report {
+                    eprintln!("{report}");
+                }
             }
-            None => println!("{flattened}"),
         };
 
         Ok(())
     }
 }
+
+/// Returns the paths of the target and all of the source files it imports, directly or indirectly.
+fn target_sources(project: &Project, target_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut sources = project.paths.read_input_files()?;
+    sources.insert(target_path.to_path_buf(), Source::read(target_path)?);
+    let graph = Graph::<MultiCompilerParsedSource>::resolve_sources(&project.paths, sources)?;
+
+    Ok(graph.imports(target_path).into_iter().cloned().chain([target_path.to_path_buf()]).collect())
+}
+
+/// Returns a summary of the distinct SPDX license identifiers of the source files of the target,
+/// each followed by the source files using it.
+///
+/// Source files without an identifier are listed under `none`.
+fn report_licenses(project: &Project, target_path: &Path) -> Result<String> {
+    let mut licenses = BTreeMap::<String, Vec<String>>::new();
+    for path in target_sources(project, target_path)? {
+        let content = fs::read_to_string(&path)?;
+        let mut identifiers =
+            LICENSE_RE.captures_iter(&content).map(|license| license[1].to_string()).peekable();
+        let identifiers = if identifiers.peek().is_none() {
+            vec!["none".to_string()]
+        } else {
+            identifiers.collect::<Vec<_>>()
+        };
+
+        let path = path.strip_prefix(&project.paths.root).unwrap_or(&path).display().to_string();
+        for identifier in identifiers {
+            let files = licenses.entry(identifier).or_default();
+            if !files.contains(&path) {
+                files.push(path.clone());
+            }
+        }
+    }
+
+    let mut report = String::from("Licenses:");
+    for (license, mut files) in licenses {
+        files.sort();
+        report.push_str(&format!("\n  {license}"));
+        for file in files {
+            report.push_str(&format!("\n    {file}"));
+        }
+    }
+    Ok(report)
+}
+
+/// Replaces the path comment the flattener emits before the definitions of each source file of the
+/// target with a banner comment.
+fn annotate_sources(project: &Project, target_path: &Path, flattened: String) -> Result<String> {
+    let mut source_paths = target_sources(project, target_path)?
+        .iter()
+        .map(|path| path.strip_prefix(&project.paths.root).unwrap_or(path).display().to_string())
+        .collect::<HashSet<_>>();
+
+    let mut annotated = flattened
+        .lines()
+        .map(|line| match line.strip_prefix("// ") {
+            // Only the first occurrence is the flattener's, others are part of the sources
+            Some(path) if source_paths.remove(path) => format!("// ===== File: {path} ====="),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    annotated.push('\n');
+    Ok(annotated)
+}
+
+/// Removes the definitions of the source files of the target matching any of the `exclude` globs
+/// from the flattened contract.
+///
+/// Fails if a top-level definition of an excluded source is still referenced by the remaining
+/// sources, unless `force` is set.
+fn exclude_sources(
+    project: &Project,
+    target_path: &Path,
+    flattened: &str,
+    exclude: &[GlobMatcher],
+    force: bool,
+) -> Result<String> {
+    let root = &project.paths.root;
+    let target = target_path.strip_prefix(root).unwrap_or(target_path).display().to_string();
+    let mut source_paths = target_sources(project, target_path)?
+        .iter()
+        .map(|path| path.strip_prefix(root).unwrap_or(path).display().to_string())
+        .collect::<HashSet<_>>();
+
+    let mut kept = Vec::new();
+    // (path, lines) of the excluded sources
+    let mut excluded: Vec<(String, Vec<&str>)> = Vec::new();
+    let mut excluding = false;
+    for line in flattened.lines() {
+        match line.strip_prefix("// ") {
+            // Only the first occurrence is the flattener's, others are part of the sources
+            Some(path) if source_paths.remove(path) => {
+                excluding = exclude.iter().any(|glob| glob.is_match(Path::new(path)));
+                if excluding {
+                    if path == target {
+                        eyre::bail!("cannot exclude the target {path}");
+                    }
+                    excluded.push((path.to_string(), Vec::new()));
+                    continue
+                }
+            }
+            _ if excluding => {
+                excluded.last_mut().unwrap().1.push(line);
+                continue
+            }
+            _ => {}
+        }
+        kept.push(line);
+    }
+
+    let mut remaining = kept.join("\n");
+    if !force {
+        for (path, lines) in &excluded {
+            let source = lines.join("\n");
+            for definition in DEFINITION_RE.captures_iter(&source) {
+                let name = &definition[1];
+                let used = Regex::new(&format!(r"(^|[^\w$]){}($|[^\w$])", regex::escape(name)))?;
+                if used.is_match(&remaining) {
+                    eyre::bail!(
+                        "cannot exclude {path}: `{name}` is used by the flattened sources, \
+                         pass `--force` to exclude it anyway"
+                    );
+                }
+            }
+        }
+    }
+
+    remaining.push('\n');
+    Ok(remaining)
+}
+
+/// Replaces the `pragma abicoder` and `pragma experimental` directives of the flattened contract
+/// with the consolidated directives of all of its sources, placed after the version pragma.
+///
+/// `pragma experimental ABIEncoderV2` is the legacy form of `pragma abicoder v2`, which is emitted
+/// if any of the sources uses it.
+fn normalize_pragmas(project: &Project, target_path: &Path, flattened: &str) -> Result<String> {
+    // (abicoder version, first source requiring it)
+    let mut abicoder: Option<(&str, PathBuf)> = None;
+    let mut abicoder_pragma = false;
+    let mut experimental = Vec::new();
+    for path in target_sources(project, target_path)? {
+        let content = fs::read_to_string(&path)?;
+        for pragma in PRAGMA_RE.captures_iter(&content) {
+            let (kind, value) = (&pragma[1], &pragma[2]);
+            let version = match (kind, value) {
+                ("abicoder", "v1") => "v1",
+                ("abicoder", "v2") => {
+                    abicoder_pragma = true;
+                    "v2"
+                }
+                ("experimental", "ABIEncoderV2") => "v2",
+                ("experimental", feature) => {
+                    if !experimental.iter().any(|f| f == feature) {
+                        experimental.push(feature.to_string());
+                    }
+                    continue
+                }
+                (_, version) => eyre::bail!("unknown ABI coder {version} in {}", path.display()),
+            };
+            match &abicoder {
+                Some((existing, source)) if *existing != version => eyre::bail!(
+                    "conflicting ABI coders: {} requires {existing}, but {} requires {version}",
+                    source.display(),
+                    path.display()
+                ),
+                Some(_) => {}
+                None => abicoder = Some((version, path.clone())),
+            }
+        }
+    }
+
+    let mut pragmas = Vec::new();
+    match abicoder.map(|(version, _)| version) {
+        Some("v2") if !abicoder_pragma => pragmas.push("pragma experimental ABIEncoderV2;".into()),
+        Some(version) => pragmas.push(format!("pragma abicoder {version};")),
+        None => {}
+    }
+    pragmas.extend(experimental.iter().map(|feature| format!("pragma experimental {feature};")));
+
+    let mut lines = flattened
+        .lines()
+        .filter(|line| !PRAGMA_RE.is_match(line))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let position = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("pragma solidity"))
+        .or_else(|| lines.iter().position(|line| line.contains("SPDX-License-Identifier")))
+        .map_or(0, |position| position + 1);
+    lines.splice(position..position, pragmas);
+
+    let mut normalized = lines.join("\n");
+    normalized.push('\n');
+    Ok(normalized)
+}
+
+/// Flattens the given Vyper source by replacing its imports of other Vyper sources with `interface`
+/// definitions of their external functions and public state variables.
+///
+/// Imports of the built-in interfaces, e.g. `from vyper.interfaces import ERC20`, are kept. Fails
+/// if an imported module is used as a module rather than an interface, e.g. with `initializes:`,
+/// as its code can't be inlined.
+fn flatten_vyper(project: &Project, target_path: &Path) -> Result<String> {
+    let source = fs::read_to_string(target_path)?;
+    let target_dir = target_path.parent().unwrap();
+
+    let mut interfaces = Vec::new();
+    let mut lines = Vec::new();
+    let mut first_import = None;
+    for line in source.lines() {
+        let Some(import) = VYPER_IMPORT_RE.captures(line) else {
+            lines.push(line.to_string());
+            continue
+        };
+        let from = import.name("from").map(|from| from.as_str());
+        let module = &import["module"];
+        let path = match from {
+            Some(from) if from.ends_with('.') => format!("{from}{module}"),
+            Some(from) => format!("{from}.{module}"),
+            None => module.to_string(),
+        };
+        if path.starts_with("vyper.") || path.starts_with("ethereum.") {
+            lines.push(line.to_string());
+            continue
+        }
+
+        let name = import.name("alias").map_or_else(
+            || module.rsplit('.').next().unwrap().to_string(),
+            |alias| alias.as_str().to_string(),
+        );
+        if source.lines().any(|line| {
+            ["initializes:", "uses:", "exports:"].iter().any(|keyword| {
+                line.trim_start().strip_prefix(keyword).is_some_and(|used| {
+                    used.split(|c: char| !c.is_alphanumeric() && c != '_').any(|used| used == name)
+                })
+            })
+        }) {
+            eyre::bail!(
+                "flattening Vyper is not supported for module imports, `{name}` is used as a module"
+            );
+        }
+
+        let imported = resolve_vyper_import(project, target_dir, &path)?;
+        if !interfaces.iter().any(|(existing, _)| *existing == name) {
+            let interface = vyper_interface(&name, &fs::read_to_string(&imported)?);
+            interfaces.push((name, interface));
+        }
+        first_import.get_or_insert(lines.len());
+    }
+
+    if let Some(position) = first_import {
+        let interfaces = interfaces.into_iter().map(|(_, interface)| interface);
+        lines.splice(position..position, interfaces);
+    }
+    let mut flattened = lines.join("\n");
+    flattened.push('\n');
+    Ok(flattened)
+}
+
+/// Resolves the dotted path of a Vyper import, e.g. `.interfaces.IERC20`, to a Vyper source file.
+///
+/// Relative paths are resolved against the directory of the importing file, others against that
+/// directory, the project root, its sources directory and its libraries.
+fn resolve_vyper_import(project: &Project, target_dir: &Path, path: &str) -> Result<PathBuf> {
+    let relative = path.trim_start_matches('.');
+    let dots = path.len() - relative.len();
+    let relative = &relative.split('.').collect::<PathBuf>();
+
+    let dirs = if dots > 0 {
+        let mut dir = target_dir.to_path_buf();
+        for _ in 1..dots {
+            dir.pop();
+        }
+        vec![dir]
+    } else {
+        [target_dir, &project.paths.root, &project.paths.sources]
+            .into_iter()
+            .map(Path::to_path_buf)
+            .chain(project.paths.libraries.iter().cloned())
+            .collect()
+    };
+
+    dirs.iter()
+        .flat_map(|dir| {
+            VYPER_EXTENSIONS
+                .iter()
+                .map(move |extension| dir.join(relative).with_extension(extension))
+        })
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| eyre::eyre!("failed to resolve the Vyper import `{path}`"))
+}
+
+/// Returns the `interface` definition of the given Vyper source, with its external functions and
+/// the getters of its public state variables.
+fn vyper_interface(name: &str, source: &str) -> String {
+    let mut functions = Vec::new();
+    let mut decorators = Vec::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if let Some(decorator) = line.strip_prefix('@') {
+            decorators.push(decorator.split_whitespace().next().unwrap_or_default().to_string());
+            continue
+        }
+
+        if line.starts_with("def ") {
+            // The signature may span multiple lines, up to the colon after the parameters
+            let code = |line: &str| line.split('#').next().unwrap_or_default().trim().to_string();
+            let mut signature = code(line);
+            while signature.matches('(').count() > signature.matches(')').count() ||
+                !signature.ends_with(':')
+            {
+                let Some(line) = lines.next() else { break };
+                signature.push(' ');
+                signature.push_str(&code(line));
+            }
+            let signature = signature.split_whitespace().collect::<Vec<_>>().join(" ");
+            let signature = signature
+                .trim_end_matches(':')
+                .replace("( ", "(")
+                .replace(" )", ")")
+                .replace(",)", ")");
+            let is_special = signature.starts_with("def __");
+            if decorators.iter().any(|decorator| decorator == "external") && !is_special {
+                let mutability = ["view", "pure", "payable", "nonpayable"]
+                    .into_iter()
+                    .find(|mutability| decorators.iter().any(|decorator| decorator == mutability))
+                    .unwrap_or("nonpayable");
+                functions.push(format!("    {signature}: {mutability}"));
+            }
+        } else if let Some(public) = VYPER_PUBLIC_RE.captures(line) {
+            functions.push(format!("    {}: view", vyper_getter(&public[1], public[2].trim())));
+        }
+        decorators.clear();
+    }
+
+    let mut interface = format!("interface {name}:\n");
+    if functions.is_empty() {
+        interface.push_str("    pass\n");
+    }
+    for function in functions {
+        interface.push_str(&function);
+        interface.push('\n');
+    }
+    interface
+}
+
+/// Returns the signature of the getter of a public Vyper state variable of the given type, taking
+/// the keys of `HashMap`s and the indices of arrays as parameters.
+fn vyper_getter(name: &str, mut ty: &str) -> String {
+    let mut params = Vec::new();
+    loop {
+        if let Some(map) = ty.strip_prefix("HashMap[").and_then(|map| map.strip_suffix(']')) {
+            let (key, value) = map.split_once(',').unwrap_or((map, ""));
+            params.push(format!("arg{}: {}", params.len(), key.trim()));
+            ty = value.trim();
+        } else if let Some(array) = ty.strip_prefix("DynArray[").and_then(|a| a.strip_suffix(']')) {
+            params.push(format!("arg{}: uint256", params.len()));
+            ty = array.rsplit_once(',').map_or(array, |(element, _)| element).trim();
+        } else if let Some((element, _)) = ty
+            .strip_suffix(']')
+            .and_then(|array| array.rsplit_once('['))
+            .filter(|(element, _)| !matches!(element.trim(), "String" | "Bytes"))
+        {
+            params.push(format!("arg{}: uint256", params.len()));
+            ty = element.trim();
+        } else {
+            break
+        }
+    }
+    format!("def {name}({}) -> {ty}", params.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use foundry_config::Config;
+
+    #[test]
+    fn flattens_vyper_imports() {
+        let root = tempfile::tempdir().unwrap();
+        let src = root.path().join("src");
+        std::fs::create_dir_all(src.join("interfaces")).unwrap();
+        std::fs::write(
+            src.join("interfaces/IToken.vyi"),
+            "@external\n@view\ndef balanceOf(owner: address) -> uint256:\n    ...\n",
+        )
+        .unwrap();
+        std::fs::write(
+            src.join("Vault.vy"),
+            r#"# @version ^0.3.10
+from vyper.interfaces import ERC20
+from .interfaces import IToken
+import interfaces.IToken as Token
+
+@external
+@payable
+def deposit():
+    pass
+"#,
+        )
+        .unwrap();
+
+        let project = Config::with_root(root.path()).project().unwrap();
+        let flattened = flatten_vyper(&project, &src.join("Vault.vy")).unwrap();
+        assert_eq!(
+            flattened,
+            r#"# @version ^0.3.10
+from vyper.interfaces import ERC20
+interface IToken:
+    def balanceOf(owner: address) -> uint256: view
+
+interface Token:
+    def balanceOf(owner: address) -> uint256: view
+
+
+@external
+@payable
+def deposit():
+    pass
+"#
+        );
+
+        std::fs::write(src.join("Broken.vy"), "import interfaces.IMissing as IMissing\n").unwrap();
+        assert!(flatten_vyper(&project, &src.join("Broken.vy")).is_err());
+    }
+
+    #[test]
+    fn vyper_getters() {
+        assert_eq!(vyper_getter("owner", "address"), "def owner() -> address");
+        assert_eq!(vyper_getter("name", "String[64]"), "def name() -> String[64]");
+        assert_eq!(
+            vyper_getter("balances", "HashMap[address, uint256]"),
+            "def balances(arg0: address) -> uint256"
+        );
+        assert_eq!(
+            vyper_getter("holders", "DynArray[address, 10]"),
+            "def holders(arg0: uint256) -> address"
+        );
+        assert_eq!(vyper_getter("fees", "uint256[3]"), "def fees(arg0: uint256) -> uint256");
+    }
+}
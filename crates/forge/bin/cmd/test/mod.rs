@@ -46,7 +46,10 @@ use yansi::Paint;
 
 mod filter;
 mod summary;
-use summary::TestSummaryReporter;
+use summary::{
+    DurationUnit, SummaryBaseline, SummaryColumn, SummarySort, TestSummaryReporter,
+    DEFAULT_PASS_RATE_THRESHOLD,
+};
 
 pub use filter::{FilterArgs, ProjectPathsAwareFilter};
 use forge::traces::render_trace_arena;
@@ -136,6 +139,80 @@ pub struct TestArgs {
     #[arg(long, help_heading = "Display options", requires = "summary")]
     pub detailed: bool,
 
+    /// The unit to render durations in, in the detailed test summary table.
+    ///
+    /// Fixed units align durations, so that the column can be scanned and sorted.
+    #[arg(
+        long,
+        help_heading = "Display options",
+        value_enum,
+        default_value = "auto",
+        requires = "detailed"
+    )]
+    pub duration_unit: DurationUnit,
+
+    /// The pass rate in percent below which the pass rate of a suite is highlighted in the
+    /// detailed test summary table.
+    #[arg(
+        long,
+        help_heading = "Display options",
+        value_name = "PERCENT",
+        default_value_t = DEFAULT_PASS_RATE_THRESHOLD,
+        requires = "detailed"
+    )]
+    pub pass_rate_threshold: f64,
+
+    /// Display the file paths in the detailed test summary table relative to the project root.
+    #[arg(long, help_heading = "Display options", requires = "detailed")]
+    pub relative_paths: bool,
+
+    /// List the test suites that ran no tests in the test summary table, marked with "no tests",
+    /// e.g. to notice misnamed tests. They are omitted otherwise.
+    #[arg(long, help_heading = "Display options", requires = "summary")]
+    pub show_empty_suites: bool,
+
+    /// The columns of the test summary table, in order, e.g. `suite,failed,passed` to show the
+    /// failed tests first. Must include the suite column.
+    ///
+    /// Defaults to the columns of the test summary table, or the detailed one with `--detailed`.
+    #[arg(
+        long,
+        help_heading = "Display options",
+        value_enum,
+        value_name = "COLUMNS",
+        value_delimiter = ',',
+        requires = "summary"
+    )]
+    pub summary_columns: Vec<SummaryColumn>,
+
+    /// The order of the test suites in the test summary, e.g. `failures` to show the suites with
+    /// the most failed tests first.
+    #[arg(
+        long,
+        help_heading = "Display options",
+        value_enum,
+        default_value = "path",
+        requires = "summary"
+    )]
+    pub summary_sort: SummarySort,
+
+    /// Print the test summary as a TAP version 13 stream instead of a table, e.g. for CI systems
+    /// consuming the Test Anything Protocol.
+    #[arg(long, help_heading = "Display options", requires = "summary")]
+    pub tap: bool,
+
+    /// Print the test summary as a JSON document instead of a table, e.g. for CI pipelines,
+    /// with the passed, failed and skipped tests, file path and duration of each suite, and the
+    /// totals across suites.
+    #[arg(long, help_heading = "Display options", requires = "summary", conflicts_with = "tap")]
+    pub summary_json: bool,
+
+    /// Compare the test summary table against the `--json` output of an earlier run, annotating
+    /// each suite with its delta of passed tests and highlighting newly failing and newly passing
+    /// suites.
+    #[arg(long, help_heading = "Display options", value_name = "PATH", requires = "summary")]
+    pub baseline: Option<PathBuf>,
+
     /// Show test execution progress.
     #[arg(long)]
     pub show_progress: bool,
@@ -149,7 +226,9 @@ impl TestArgs {
 
     pub async fn run(self) -> Result<TestOutcome> {
         trace!(target: "forge::test", "executing test command");
-        shell::set_shell(shell::Shell::from_args(self.opts.silent, self.json))?;
+        // Nothing but the machine-readable output goes to stdout, e.g. failing tests aren't listed
+        let json = self.json || self.prints_machine_summary();
+        shell::set_shell(shell::Shell::from_args(self.opts.silent, json))?;
         self.execute_tests().await
     }
 
@@ -268,7 +347,7 @@ impl TestArgs {
         let sources_to_compile = self.get_sources_to_compile(&config, &filter)?;
 
         let compiler = ProjectCompiler::new()
-            .quiet_if(self.json || self.opts.silent)
+            .quiet_if(self.json || self.opts.silent || self.prints_machine_summary())
             .files(sources_to_compile);
 
         let output = compiler.compile(&project)?;
@@ -425,6 +504,15 @@ impl TestArgs {
 
         let mut outcome = TestOutcome::empty(self.allow_failure);
 
+        // A machine-readable summary is the only output on stdout, so that it can be parsed
+        let report = |msg: &dyn std::fmt::Display| {
+            if self.prints_machine_summary() {
+                shell::eprintln(msg)
+            } else {
+                shell::println(msg)
+            }
+        };
+
         let mut any_test_failed = false;
         for (contract_name, suite_result) in rx {
             let tests = &suite_result.test_results;
@@ -436,30 +524,30 @@ impl TestArgs {
             let identify_addresses = verbosity >= 3 || self.gas_report || self.debug.is_some();
 
             // Print suite header.
-            println!();
+            report(&"")?;
             for warning in suite_result.warnings.iter() {
                 eprintln!("{} {warning}", "Warning:".yellow().bold());
             }
             if !tests.is_empty() {
                 let len = tests.len();
                 let tests = if len > 1 { "tests" } else { "test" };
-                println!("Ran {len} {tests} for {contract_name}");
+                report(&format_args!("Ran {len} {tests} for {contract_name}"))?;
             }
 
             // Process individual test results, printing logs and traces when necessary.
             for (name, result) in tests {
-                shell::println(result.short_result(name))?;
+                report(&result.short_result(name))?;
 
                 // We only display logs at level 2 and above
                 if verbosity >= 2 {
                     // We only decode logs from Hardhat and DS-style console events
                     let console_logs = decode_console_logs(&result.logs);
                     if !console_logs.is_empty() {
-                        println!("Logs:");
+                        report(&"Logs:")?;
                         for log in console_logs {
-                            println!("  {log}");
+                            report(&format_args!("  {log}"))?;
                         }
-                        println!();
+                        report(&"")?;
                     }
                 }
 
@@ -501,9 +589,9 @@ impl TestArgs {
                 }
 
                 if !decoded_traces.is_empty() {
-                    shell::println("Traces:")?;
+                    report(&"Traces:")?;
                     for trace in &decoded_traces {
-                        shell::println(trace)?;
+                        report(trace)?;
                     }
                 }
 
@@ -532,7 +620,7 @@ impl TestArgs {
             }
 
             // Print suite summary.
-            shell::println(suite_result.summary())?;
+            report(&suite_result.summary())?;
 
             // Add the suite result to the outcome.
             outcome.results.insert(contract_name, suite_result);
@@ -549,17 +637,32 @@ impl TestArgs {
 
         if let Some(gas_report) = gas_report {
             let finalized = gas_report.finalize();
-            shell::println(&finalized)?;
+            report(&finalized)?;
             outcome.gas_report = Some(finalized);
         }
 
         if !outcome.results.is_empty() {
-            shell::println(outcome.summary(duration))?;
+            report(&outcome.summary(duration))?;
 
             if self.summary {
-                let mut summary_table = TestSummaryReporter::new(self.detailed);
-                shell::println("\n\nTest Summary:")?;
-                summary_table.print_summary(&outcome);
+                let mut summary_table = TestSummaryReporter::new(self.detailed)
+                    .with_duration_unit(self.duration_unit)
+                    .with_pass_rate_threshold(self.pass_rate_threshold)
+                    .with_root(self.relative_paths.then(|| config.root.0.clone()))
+                    .with_empty_suites(self.show_empty_suites)
+                    .with_sort(self.summary_sort)
+                    .with_baseline(self.baseline.as_deref().map(SummaryBaseline::load).transpose()?)
+                    .with_columns(self.summary_columns.clone())?;
+                if !self.prints_machine_summary() {
+                    shell::println("\n\nTest Summary:")?;
+                }
+                if self.tap {
+                    summary_table.write_tap(&outcome, std::io::stdout().lock())?;
+                } else if self.summary_json {
+                    summary_table.write_json(&outcome, std::io::stdout().lock())?;
+                } else {
+                    summary_table.print_summary(&outcome);
+                }
             }
         }
 
@@ -574,6 +677,12 @@ impl TestArgs {
         Ok(outcome)
     }
 
+    /// Returns whether the test summary is printed in a machine-readable format, see `--tap` and
+    /// `--summary-json`.
+    fn prints_machine_summary(&self) -> bool {
+        self.summary && (self.tap || self.summary_json)
+    }
+
     /// Returns the flattened [`FilterArgs`] arguments merged with [`Config`].
     pub fn filter(&self, config: &Config) -> ProjectPathsAwareFilter {
         self.filter.clone().merge_with_config(config)
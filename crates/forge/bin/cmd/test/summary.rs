@@ -1,57 +1,423 @@
 use comfy_table::{
     modifiers::UTF8_ROUND_CORNERS, Attribute, Cell, CellAlignment, Color, Row, Table,
 };
-use forge::result::TestOutcome;
+use forge::result::{SuiteResult, TestKind, TestOutcome, TestStatus};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The maximum length of the first failing test name shown in the detailed summary.
+const MAX_FIRST_FAILURE_LEN: usize = 40;
+
+/// The width of durations rendered in a fixed unit, excluding the unit.
+const FIXED_DURATION_WIDTH: usize = 10;
+
+/// The default pass rate in percent below which the pass rate is highlighted in the detailed
+/// summary.
+pub const DEFAULT_PASS_RATE_THRESHOLD: f64 = 100.0;
+
+/// The unit durations are rendered in by the detailed summary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DurationUnit {
+    /// The most appropriate unit for each duration, e.g. `12.00ms` or `1.50s`.
+    #[default]
+    Auto,
+    /// Microseconds.
+    Us,
+    /// Milliseconds.
+    Ms,
+    /// Seconds.
+    S,
+}
+
+impl DurationUnit {
+    /// Formats the duration in this unit.
+    ///
+    /// Fixed units are padded to the same width, so that durations align and sort lexically.
+    fn format(self, duration: Duration) -> String {
+        let (value, unit) = match self {
+            Self::Auto => return format!("{duration:.2?}"),
+            Self::Us => (duration.as_secs_f64() * 1e6, "µs"),
+            Self::Ms => (duration.as_secs_f64() * 1e3, "ms"),
+            Self::S => (duration.as_secs_f64(), "s"),
+        };
+        format!("{value:>FIXED_DURATION_WIDTH$.2}{unit}")
+    }
+}
+
+/// A column of the test summary table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SummaryColumn {
+    /// The name of the test suite, required.
+    Suite,
+    /// The number of passed tests.
+    Passed,
+    /// The number of failed tests.
+    Failed,
+    /// The number of skipped tests.
+    Skipped,
+    /// The percentage of passed tests, detailed summary only by default.
+    PassRate,
+    /// The file path of the test suite, detailed summary only by default.
+    Path,
+    /// The duration of the test suite, detailed summary only by default.
+    Duration,
+    /// The gas used by the tests of the test suite, detailed summary only by default.
+    Gas,
+    /// The name of the first failing test, detailed summary only by default.
+    FirstFailure,
+}
+
+impl SummaryColumn {
+    /// Returns the columns rendered by default, in order.
+    fn defaults(is_detailed: bool) -> Vec<Self> {
+        let mut columns = vec![Self::Suite, Self::Passed, Self::Failed, Self::Skipped];
+        if is_detailed {
+            columns.extend([
+                Self::PassRate,
+                Self::Path,
+                Self::Duration,
+                Self::Gas,
+                Self::FirstFailure,
+            ]);
+        }
+        columns
+    }
+
+    /// Returns the header cell of the column.
+    fn header(self) -> Cell {
+        let cell = match self {
+            Self::Suite => Cell::new("Test Suite"),
+            Self::Passed => Cell::new("Passed").fg(Color::Green),
+            Self::Failed => Cell::new("Failed").fg(Color::Red),
+            Self::Skipped => Cell::new("Skipped").fg(Color::Yellow),
+            Self::PassRate => Cell::new("Pass %"),
+            Self::Path => Cell::new("File Path"),
+            Self::Duration => Cell::new("Duration"),
+            Self::Gas => Cell::new("Total Gas"),
+            Self::FirstFailure => Cell::new("First Failure").fg(Color::Red),
+        };
+        cell.set_alignment(CellAlignment::Center).add_attribute(Attribute::Bold)
+    }
+}
+
+/// The order of the test suites in the test summary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SummarySort {
+    /// By file path, then by suite name.
+    #[default]
+    Path,
+    /// By the number of failed tests, most first, then by file path and suite name.
+    Failures,
+}
+
+/// The test results of an earlier run to compare the summary against, e.g. the `--json` output of
+/// `forge test`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct SummaryBaseline {
+    /// The results by test suite, `path:Suite -> suite`.
+    suites: BTreeMap<String, BaselineSuite>,
+}
+
+/// The test results of a suite in a [`SummaryBaseline`].
+#[derive(Clone, Debug, Default, Deserialize)]
+struct BaselineSuite {
+    test_results: BTreeMap<String, BaselineTest>,
+}
+
+/// The result of a test in a [`SummaryBaseline`], of which only the status is compared.
+#[derive(Clone, Debug, Deserialize)]
+struct BaselineTest {
+    status: TestStatus,
+}
+
+impl BaselineSuite {
+    fn count(&self, status: TestStatus) -> usize {
+        self.test_results.values().filter(|result| result.status == status).count()
+    }
+}
+
+impl SummaryBaseline {
+    /// Loads a baseline from the `forge test --json` output at the given path.
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        Ok(foundry_common::fs::read_json_file(path)?)
+    }
+
+    /// Returns the number of tests of the suite that passed in the baseline.
+    fn passed(&self, suite: &str) -> usize {
+        self.suites.get(suite).map_or(0, |suite| suite.count(TestStatus::Success))
+    }
+
+    /// Returns whether the suite had failing tests in the baseline, `None` if it didn't run.
+    fn failed(&self, suite: &str) -> Option<bool> {
+        self.suites.get(suite).map(|suite| suite.count(TestStatus::Failure) > 0)
+    }
+
+    /// Returns whether the suite fails now but didn't fail, or didn't run, in the baseline.
+    fn is_newly_failing(&self, suite: &str, failed: usize) -> bool {
+        failed > 0 && self.failed(suite) != Some(true)
+    }
+
+    /// Returns whether the suite failed in the baseline but doesn't fail now.
+    fn is_newly_passing(&self, suite: &str, failed: usize) -> bool {
+        failed == 0 && self.failed(suite) == Some(true)
+    }
+}
+
+/// The machine-readable test summary, see [`TestSummaryReporter::write_json`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct JsonSummary {
+    /// The summary of each test suite.
+    pub suites: Vec<JsonSuiteSummary>,
+    /// The number of passed tests across all suites.
+    pub passed: usize,
+    /// The number of failed tests across all suites.
+    pub failed: usize,
+    /// The number of skipped tests across all suites.
+    pub skipped: usize,
+    /// The total duration of the test suites in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// The summary of a test suite in a [`JsonSummary`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct JsonSuiteSummary {
+    /// The file path of the test suite.
+    pub suite_path: String,
+    /// The name of the test suite.
+    pub suite_name: String,
+    /// The number of passed tests.
+    pub passed: usize,
+    /// The number of failed tests.
+    pub failed: usize,
+    /// The number of skipped tests.
+    pub skipped: usize,
+    /// The duration of the test suite in milliseconds.
+    pub duration_ms: u64,
+}
 
 /// A simple summary reporter that prints the test results in a table.
 pub struct TestSummaryReporter {
     /// The test summary table.
     pub(crate) table: Table,
     pub(crate) is_detailed: bool,
+    /// The columns to render, in order.
+    pub(crate) columns: Vec<SummaryColumn>,
+    /// The unit durations are rendered in.
+    pub(crate) duration_unit: DurationUnit,
+    /// The pass rate in percent below which the pass rate of a suite is highlighted.
+    pub(crate) pass_rate_threshold: f64,
+    /// The project root file paths are displayed relative to, if any.
+    pub(crate) root: Option<PathBuf>,
+    /// Whether to list the suites that ran no tests.
+    pub(crate) show_empty_suites: bool,
+    /// The results of an earlier run the suites are compared against, if any.
+    pub(crate) baseline: Option<SummaryBaseline>,
+    /// The order of the suites.
+    pub(crate) sort: SummarySort,
 }
 
 impl TestSummaryReporter {
     pub(crate) fn new(is_detailed: bool) -> Self {
-        let mut table = Table::new();
-        table.apply_modifier(UTF8_ROUND_CORNERS);
-        let mut row = Row::from(vec![
-            Cell::new("Test Suite")
-                .set_alignment(CellAlignment::Center)
-                .add_attribute(Attribute::Bold),
-            Cell::new("Passed")
-                .set_alignment(CellAlignment::Center)
-                .add_attribute(Attribute::Bold)
-                .fg(Color::Green),
-            Cell::new("Failed")
-                .set_alignment(CellAlignment::Center)
-                .add_attribute(Attribute::Bold)
-                .fg(Color::Red),
-            Cell::new("Skipped")
-                .set_alignment(CellAlignment::Center)
-                .add_attribute(Attribute::Bold)
-                .fg(Color::Yellow),
-        ]);
-        if is_detailed {
-            row.add_cell(
-                Cell::new("File Path")
-                    .set_alignment(CellAlignment::Center)
-                    .add_attribute(Attribute::Bold),
-            );
-            row.add_cell(
-                Cell::new("Duration")
-                    .set_alignment(CellAlignment::Center)
-                    .add_attribute(Attribute::Bold),
-            );
+        let columns = SummaryColumn::defaults(is_detailed);
+        Self {
+            table: summary_table(&columns),
+            is_detailed,
+            columns,
+            duration_unit: DurationUnit::default(),
+            pass_rate_threshold: DEFAULT_PASS_RATE_THRESHOLD,
+            root: None,
+            show_empty_suites: false,
+            baseline: None,
+            sort: SummarySort::default(),
+        }
+    }
+
+    /// Sets the columns to render, in order, e.g. to show the failed tests first.
+    ///
+    /// Empty keeps the default columns. Errors if the suite column is missing or a column is
+    /// repeated.
+    pub(crate) fn with_columns(mut self, columns: Vec<SummaryColumn>) -> eyre::Result<Self> {
+        let columns =
+            if columns.is_empty() { SummaryColumn::defaults(self.is_detailed) } else { columns };
+        if !columns.contains(&SummaryColumn::Suite) {
+            eyre::bail!("the summary columns must include the suite column");
         }
-        table.set_header(row);
+        if let Some((i, column)) =
+            columns.iter().enumerate().find(|(i, column)| columns[..*i].contains(column))
+        {
+            eyre::bail!("the summary column {column:?} is repeated at position {}", i + 1);
+        }
+        self.table = summary_table(&columns);
+        self.columns = columns;
+        Ok(self)
+    }
+
+    /// Sets the unit durations are rendered in.
+    pub(crate) fn with_duration_unit(mut self, duration_unit: DurationUnit) -> Self {
+        self.duration_unit = duration_unit;
+        self
+    }
+
+    /// Sets the pass rate in percent below which the pass rate of a suite is highlighted.
+    pub(crate) fn with_pass_rate_threshold(mut self, pass_rate_threshold: f64) -> Self {
+        self.pass_rate_threshold = pass_rate_threshold;
+        self
+    }
+
+    /// Sets the project root file paths are displayed relative to.
+    ///
+    /// Paths outside of the root are displayed as is.
+    pub(crate) fn with_root(mut self, root: Option<PathBuf>) -> Self {
+        self.root = root;
+        self
+    }
 
-        Self { table, is_detailed }
+    /// Sets whether to list the suites that ran no tests, marked with "no tests", rather than
+    /// omitting them.
+    pub(crate) fn with_empty_suites(mut self, show_empty_suites: bool) -> Self {
+        self.show_empty_suites = show_empty_suites;
+        self
+    }
+
+    /// Sets the results of an earlier run to compare against.
+    ///
+    /// The passed tests of each suite are annotated with their delta to the baseline, suites that
+    /// newly fail are highlighted in red and suites that newly pass in green.
+    pub(crate) fn with_baseline(mut self, baseline: Option<SummaryBaseline>) -> Self {
+        self.baseline = baseline;
+        self
+    }
+
+    /// Sets the order of the suites.
+    pub(crate) fn with_sort(mut self, sort: SummarySort) -> Self {
+        self.sort = sort;
+        self
     }
 
     pub(crate) fn print_summary(&mut self, outcome: &TestOutcome) {
+        self.add_rows(outcome);
+        println!("\n{}", self.table);
+        if let Some(verdict) = self.baseline_verdict(outcome) {
+            println!("{verdict}");
+        }
+    }
+
+    /// Returns the number of suites failing now that didn't fail in the baseline as a one-line
+    /// verdict, e.g. `2 new failures vs baseline`, or `None` if there is no baseline.
+    pub(crate) fn baseline_verdict(&self, outcome: &TestOutcome) -> Option<String> {
+        let baseline = self.baseline.as_ref()?;
+        let new_failures = outcome
+            .results
+            .iter()
+            .filter(|(contract, suite)| {
+                baseline.is_newly_failing(contract, suite.failures().count())
+            })
+            .count();
+        let noun = if new_failures == 1 { "failure" } else { "failures" };
+        Some(format!("{new_failures} new {noun} vs baseline"))
+    }
+
+    /// Writes the outcome as a TAP version 13 stream, with a test point per test, in the order the
+    /// suites are summarized in, see [`SummarySort`].
+    ///
+    /// Failed tests include a YAML diagnostic block with the revert reason, if any, and skipped
+    /// tests a `# SKIP` directive.
+    pub(crate) fn write_tap<W: Write>(&self, outcome: &TestOutcome, mut w: W) -> eyre::Result<()> {
+        writeln!(w, "TAP version 13")?;
+        writeln!(w, "1..{}", outcome.tests().count())?;
+
+        let mut number = 0;
+        for (contract, suite) in self.sorted_suites(outcome) {
+            let (suite_path, suite_name) = contract.split_once(':').unwrap();
+            let suite_path = display_path(suite_path, self.root.as_deref());
+            for (test_name, result) in &suite.test_results {
+                number += 1;
+                let description = format!("{suite_path}:{suite_name}::{test_name}");
+                match result.status {
+                    TestStatus::Success => writeln!(w, "ok {number} - {description}")?,
+                    TestStatus::Skipped => match &result.reason {
+                        Some(reason) => writeln!(w, "ok {number} - {description} # SKIP {reason}")?,
+                        None => writeln!(w, "ok {number} - {description} # SKIP")?,
+                    },
+                    TestStatus::Failure => {
+                        writeln!(w, "not ok {number} - {description}")?;
+                        if let Some(reason) = &result.reason {
+                            writeln!(w, "  ---")?;
+                            writeln!(w, "  message: {}", serde_json::to_string(reason)?)?;
+                            writeln!(w, "  ...")?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the machine-readable summary of the outcome, with a summary per test suite and the
+    /// totals across suites.
+    ///
+    /// The file path and duration of each suite are included regardless of the detailed flag.
+    pub(crate) fn json_summary(&self, outcome: &TestOutcome) -> JsonSummary {
+        let mut summary = JsonSummary::default();
+        for (contract, suite) in self.sorted_suites(outcome) {
+            if suite.test_results.is_empty() && !self.show_empty_suites {
+                continue;
+            }
+
+            let (suite_path, suite_name) = contract.split_once(':').unwrap();
+            let suite = JsonSuiteSummary {
+                suite_path: display_path(suite_path, self.root.as_deref()),
+                suite_name: suite_name.to_string(),
+                passed: suite.successes().count(),
+                failed: suite.failures().count(),
+                skipped: suite.skips().count(),
+                duration_ms: suite.duration.as_millis().try_into().unwrap_or(u64::MAX),
+            };
+            summary.passed += suite.passed;
+            summary.failed += suite.failed;
+            summary.skipped += suite.skipped;
+            summary.duration_ms = summary.duration_ms.saturating_add(suite.duration_ms);
+            summary.suites.push(suite);
+        }
+        summary
+    }
+
+    /// Writes the outcome as a JSON document, see [`Self::json_summary`].
+    pub(crate) fn write_json<W: Write>(&self, outcome: &TestOutcome, mut w: W) -> eyre::Result<()> {
+        serde_json::to_writer_pretty(&mut w, &self.json_summary(outcome))?;
+        writeln!(w)?;
+        Ok(())
+    }
+
+    /// Returns the suites of the outcome in the order they are summarized, see [`SummarySort`].
+    fn sorted_suites<'a>(&self, outcome: &'a TestOutcome) -> Vec<(&'a String, &'a SuiteResult)> {
+        let mut suites = outcome.results.iter().collect::<Vec<_>>();
+        suites.sort_by(|(a, _), (b, _)| {
+            let (a_path, a_name) = a.split_once(':').unwrap();
+            let (b_path, b_name) = b.split_once(':').unwrap();
+            (a_path, a_name).cmp(&(b_path, b_name))
+        });
+        if self.sort == SummarySort::Failures {
+            suites.sort_by_key(|(_, suite)| std::cmp::Reverse(suite.failures().count()));
+        }
+        suites
+    }
+
+    /// Adds a row for each test suite in the outcome to the table.
+    fn add_rows(&mut self, outcome: &TestOutcome) {
         // Traverse the test_results vector and build the table
-        for (contract, suite) in &outcome.results {
-            let mut row = Row::new();
+        for (contract, suite) in self.sorted_suites(outcome) {
+            let is_empty = suite.test_results.is_empty();
+            if is_empty && !self.show_empty_suites {
+                continue;
+            }
+
             let (suite_path, suite_name) = contract.split_once(':').unwrap();
 
             let passed = suite.successes().count();
@@ -63,31 +429,650 @@ impl TestSummaryReporter {
             let skipped = suite.skips().count();
             let mut skipped_cell = Cell::new(skipped).set_alignment(CellAlignment::Center);
 
-            row.add_cell(Cell::new(suite_name));
+            let name_cell = if is_empty {
+                Cell::new(format!("{suite_name} (no tests)")).fg(Color::Yellow)
+            } else {
+                // Suites with retried tests are annotated and italicized, as they may be flaky
+                let retries = suite.retries();
+                let mut name_cell = match retries {
+                    0 => Cell::new(suite_name),
+                    1 => Cell::new(format!("{suite_name} (1 retry)")),
+                    _ => Cell::new(format!("{suite_name} ({retries} retries)")),
+                };
+                if retries > 0 {
+                    name_cell = name_cell.add_attribute(Attribute::Italic);
+                }
+                if let Some(baseline) = &self.baseline {
+                    if baseline.is_newly_failing(contract, failed) {
+                        name_cell = name_cell.fg(Color::Red);
+                    } else if baseline.is_newly_passing(contract, failed) {
+                        name_cell = name_cell.fg(Color::Green);
+                    }
+                }
+                name_cell
+            };
 
+            if let Some(baseline) = &self.baseline {
+                let delta = passed as i64 - baseline.passed(contract) as i64;
+                if delta != 0 {
+                    passed_cell = Cell::new(format!("{passed} ({delta:+})"))
+                        .set_alignment(CellAlignment::Center);
+                }
+            }
             if passed > 0 {
                 passed_cell = passed_cell.fg(Color::Green);
             }
-            row.add_cell(passed_cell);
-
             if failed > 0 {
                 failed_cell = failed_cell.fg(Color::Red);
             }
-            row.add_cell(failed_cell);
-
             if skipped > 0 {
                 skipped_cell = skipped_cell.fg(Color::Yellow);
             }
-            row.add_cell(skipped_cell);
 
-            if self.is_detailed {
-                row.add_cell(Cell::new(suite_path));
-                row.add_cell(Cell::new(format!("{:.2?}", suite.duration).to_string()));
+            let mut row = Row::new();
+            for column in &self.columns {
+                let cell = match column {
+                    SummaryColumn::Suite => name_cell.clone(),
+                    SummaryColumn::Passed => passed_cell.clone(),
+                    SummaryColumn::Failed => failed_cell.clone(),
+                    SummaryColumn::Skipped => skipped_cell.clone(),
+                    SummaryColumn::PassRate => match pass_rate(passed, failed) {
+                        Some(rate) => Cell::new(format!("{rate:.1}%"))
+                            .fg(pass_rate_color(rate, self.pass_rate_threshold)),
+                        None => Cell::new("--"),
+                    }
+                    .set_alignment(CellAlignment::Center),
+                    SummaryColumn::Path => {
+                        Cell::new(display_path(suite_path, self.root.as_deref()))
+                    }
+                    SummaryColumn::Duration => Cell::new(self.duration_unit.format(suite.duration)),
+                    SummaryColumn::Gas => match total_gas(suite) {
+                        Some(gas) => Cell::new(gas),
+                        None => Cell::new("--"),
+                    }
+                    .set_alignment(CellAlignment::Right),
+                    SummaryColumn::FirstFailure => {
+                        let first_failure = suite
+                            .failures()
+                            .next()
+                            .map(|(name, _)| truncate(name, MAX_FIRST_FAILURE_LEN))
+                            .unwrap_or_default();
+                        Cell::new(first_failure).fg(Color::Red)
+                    }
+                };
+                row.add_cell(cell);
             }
 
             self.table.add_row(row);
         }
+    }
+}
 
-        println!("\n{}", self.table);
+/// Returns an empty summary table with the header of the given columns.
+fn summary_table(columns: &[SummaryColumn]) -> Table {
+    let mut table = Table::new();
+    table.apply_modifier(UTF8_ROUND_CORNERS);
+    table.set_header(columns.iter().map(|column| column.header()).collect::<Vec<_>>());
+    table
+}
+
+/// Returns the percentage of passed tests among the passed and failed tests, ignoring skipped
+/// tests.
+///
+/// Returns `None` if no tests passed or failed.
+fn pass_rate(passed: usize, failed: usize) -> Option<f64> {
+    let total = passed + failed;
+    (total > 0).then(|| passed as f64 / total as f64 * 100.0)
+}
+
+/// Returns the gas used by the tests of the suite, the median gas for fuzz tests.
+///
+/// Returns `None` if no test reports gas, e.g. if all tests were skipped or are invariant tests.
+fn total_gas(suite: &SuiteResult) -> Option<u64> {
+    suite
+        .test_results
+        .values()
+        .filter(|result| {
+            result.status != TestStatus::Skipped &&
+                !matches!(result.kind, TestKind::Invariant { .. })
+        })
+        .map(|result| result.kind.report().gas())
+        .reduce(u64::saturating_add)
+}
+
+/// Returns the color of the given pass rate, red if it is below the threshold.
+fn pass_rate_color(rate: f64, threshold: f64) -> Color {
+    if rate < threshold {
+        Color::Red
+    } else {
+        Color::Green
+    }
+}
+
+/// Returns the given path relative to the root, or as is if there's no root or it's outside of it.
+fn display_path(path: &str, root: Option<&Path>) -> String {
+    root.and_then(|root| Path::new(path).strip_prefix(root).ok())
+        .map(|relative| relative.display().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Truncates the given string to `max_len` characters, appending an ellipsis if truncated.
+fn truncate(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        return s.to_string();
+    }
+    let mut truncated: String = s.chars().take(max_len.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use forge::result::TestResult;
+    use std::collections::BTreeMap;
+
+    fn test_result(status: TestStatus) -> TestResult {
+        TestResult { status, ..Default::default() }
+    }
+
+    #[test]
+    fn shows_first_failure() {
+        let test_results = BTreeMap::from([
+            ("testA()".to_string(), test_result(TestStatus::Success)),
+            ("testB()".to_string(), test_result(TestStatus::Failure)),
+            ("testC()".to_string(), test_result(TestStatus::Failure)),
+        ]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([(
+                "src/Foo.t.sol:FooTest".to_string(),
+                SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+            )]),
+            false,
+        );
+
+        let mut reporter = TestSummaryReporter::new(true);
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains("testB()"));
+        assert!(!table.contains("testC()"));
+    }
+
+    #[test]
+    fn shows_pass_rate() {
+        let test_results = BTreeMap::from([
+            ("testA()".to_string(), test_result(TestStatus::Success)),
+            ("testB()".to_string(), test_result(TestStatus::Success)),
+            ("testC()".to_string(), test_result(TestStatus::Failure)),
+            ("testD()".to_string(), test_result(TestStatus::Skipped)),
+        ]);
+        let skipped_results =
+            BTreeMap::from([("testA()".to_string(), test_result(TestStatus::Skipped))]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                (
+                    "src/Foo.t.sol:FooTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+                ),
+                (
+                    "src/Bar.t.sol:BarTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), skipped_results, vec![]),
+                ),
+            ]),
+            false,
+        );
+
+        let mut reporter = TestSummaryReporter::new(true).with_pass_rate_threshold(80.0);
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains("Pass %"));
+        assert!(table.contains("66.7%"));
+        assert!(table.contains("--"));
+
+        // Skipped tests are excluded
+        let rate = pass_rate(2, 1).unwrap();
+        assert!((rate - 66.67).abs() < 0.01, "{rate}");
+        assert_eq!(pass_rate(0, 0), None);
+
+        assert_eq!(pass_rate_color(rate, 80.0), Color::Red);
+        assert_eq!(pass_rate_color(rate, 50.0), Color::Green);
+        assert_eq!(pass_rate_color(100.0, DEFAULT_PASS_RATE_THRESHOLD), Color::Green);
+        assert_eq!(pass_rate_color(99.9, DEFAULT_PASS_RATE_THRESHOLD), Color::Red);
+    }
+
+    #[test]
+    fn shows_total_gas() {
+        let gas_result =
+            |status, gas| TestResult { status, kind: TestKind::Unit { gas }, ..Default::default() };
+        let test_results = BTreeMap::from([
+            ("testA()".to_string(), gas_result(TestStatus::Success, 21_000)),
+            ("testB()".to_string(), gas_result(TestStatus::Failure, 30_000)),
+            ("testC()".to_string(), gas_result(TestStatus::Skipped, 5_000)),
+        ]);
+        let skipped_results =
+            BTreeMap::from([("testA()".to_string(), gas_result(TestStatus::Skipped, 5_000))]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                (
+                    "src/Foo.t.sol:FooTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+                ),
+                (
+                    "src/Bar.t.sol:BarTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), skipped_results, vec![]),
+                ),
+            ]),
+            false,
+        );
+
+        assert_eq!(total_gas(&outcome.results["src/Foo.t.sol:FooTest"]), Some(51_000));
+        assert_eq!(total_gas(&outcome.results["src/Bar.t.sol:BarTest"]), None);
+
+        let mut reporter = TestSummaryReporter::new(true);
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains("Total Gas"), "{table}");
+        assert!(table.contains("51000"), "{table}");
+
+        // Only in the detailed summary
+        let mut reporter = TestSummaryReporter::new(false);
+        reporter.add_rows(&outcome);
+        assert!(!reporter.table.to_string().contains("Total Gas"));
+    }
+
+    #[test]
+    fn shows_paths_relative_to_root() {
+        let root = Path::new("/project");
+        assert_eq!(display_path("/project/test/Foo.t.sol", Some(root)), "test/Foo.t.sol");
+        assert_eq!(display_path("/other/test/Foo.t.sol", Some(root)), "/other/test/Foo.t.sol");
+        assert_eq!(display_path("/project/test/Foo.t.sol", None), "/project/test/Foo.t.sol");
+
+        let test_results =
+            BTreeMap::from([("testA()".to_string(), test_result(TestStatus::Success))]);
+        let suite = SuiteResult::new(Duration::from_millis(10), test_results, vec![]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                ("/project/test/Foo.t.sol:FooTest".to_string(), suite.clone()),
+                ("/lib/test/Bar.t.sol:BarTest".to_string(), suite),
+            ]),
+            false,
+        );
+
+        let mut reporter = TestSummaryReporter::new(true).with_root(Some(root.to_path_buf()));
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains(" test/Foo.t.sol "), "{table}");
+        assert!(!table.contains("/project/test/Foo.t.sol"), "{table}");
+        assert!(table.contains("/lib/test/Bar.t.sol"), "{table}");
+    }
+
+    #[test]
+    fn flags_empty_suites() {
+        let test_results =
+            BTreeMap::from([("testA()".to_string(), test_result(TestStatus::Success))]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                (
+                    "src/Foo.t.sol:FooTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+                ),
+                (
+                    "src/Empty.t.sol:EmptyTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), BTreeMap::new(), vec![]),
+                ),
+            ]),
+            false,
+        );
+
+        let mut reporter = TestSummaryReporter::new(false);
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains("FooTest"), "{table}");
+        assert!(!table.contains("EmptyTest"), "{table}");
+
+        let mut reporter = TestSummaryReporter::new(false).with_empty_suites(true);
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains("EmptyTest (no tests)"), "{table}");
+        assert!(!table.contains("FooTest (no tests)"), "{table}");
+    }
+
+    #[test]
+    fn flags_regressions_against_baseline() {
+        let baseline: SummaryBaseline = serde_json::from_value(serde_json::json!({
+            "src/Foo.t.sol:FooTest": {
+                "duration": "10ms",
+                "test_results": {
+                    "testA()": { "status": "Success" },
+                    "testB()": { "status": "Success" },
+                },
+                "warnings": [],
+            },
+            "src/Bar.t.sol:BarTest": {
+                "test_results": { "testA()": { "status": "Failure" } },
+            },
+        }))
+        .unwrap();
+
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                (
+                    "src/Foo.t.sol:FooTest".to_string(),
+                    SuiteResult::new(
+                        Duration::from_millis(10),
+                        BTreeMap::from([
+                            ("testA()".to_string(), test_result(TestStatus::Success)),
+                            ("testB()".to_string(), test_result(TestStatus::Failure)),
+                        ]),
+                        vec![],
+                    ),
+                ),
+                (
+                    "src/Bar.t.sol:BarTest".to_string(),
+                    SuiteResult::new(
+                        Duration::from_millis(10),
+                        BTreeMap::from([("testA()".to_string(), test_result(TestStatus::Success))]),
+                        vec![],
+                    ),
+                ),
+            ]),
+            false,
+        );
+
+        let foo = "src/Foo.t.sol:FooTest";
+        let bar = "src/Bar.t.sol:BarTest";
+        assert!(baseline.is_newly_failing(foo, 1));
+        assert!(!baseline.is_newly_failing(bar, 0));
+        assert!(baseline.is_newly_passing(bar, 0));
+        // Suites that didn't run in the baseline are new failures if they fail
+        assert!(baseline.is_newly_failing("src/Baz.t.sol:BazTest", 1));
+
+        let mut reporter = TestSummaryReporter::new(false).with_baseline(Some(baseline));
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains("1 (-1)"), "{table}");
+        assert!(table.contains("1 (+1)"), "{table}");
+        assert_eq!(reporter.baseline_verdict(&outcome).unwrap(), "1 new failure vs baseline");
+
+        // Without a baseline nothing is annotated
+        let mut reporter = TestSummaryReporter::new(false);
+        reporter.add_rows(&outcome);
+        assert!(!reporter.table.to_string().contains("(+1)"));
+        assert_eq!(reporter.baseline_verdict(&outcome), None);
+    }
+
+    #[test]
+    fn renders_columns_in_order() {
+        let test_results = BTreeMap::from([
+            ("testA()".to_string(), test_result(TestStatus::Success)),
+            ("testB()".to_string(), test_result(TestStatus::Success)),
+            ("testC()".to_string(), test_result(TestStatus::Failure)),
+        ]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([(
+                "src/Foo.t.sol:FooTest".to_string(),
+                SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+            )]),
+            false,
+        );
+
+        let columns = vec![SummaryColumn::Failed, SummaryColumn::Passed, SummaryColumn::Suite];
+        let mut reporter = TestSummaryReporter::new(false).with_columns(columns).unwrap();
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        let header = table.lines().find(|line| line.contains("Test Suite")).unwrap();
+        let row = table.lines().find(|line| line.contains("FooTest")).unwrap();
+        assert!(header.find("Failed") < header.find("Passed"), "{table}");
+        assert!(header.find("Passed") < header.find("Test Suite"), "{table}");
+        assert!(!header.contains("Skipped"), "{table}");
+        let cells = row
+            .split(['│', '|'])
+            .map(str::trim)
+            .filter(|cell| !cell.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(cells, ["1", "2", "FooTest"], "{table}");
+
+        // The defaults are kept if no columns are given
+        let reporter = TestSummaryReporter::new(true).with_columns(vec![]).unwrap();
+        assert_eq!(reporter.columns, SummaryColumn::defaults(true));
+
+        assert!(TestSummaryReporter::new(false)
+            .with_columns(vec![SummaryColumn::Passed, SummaryColumn::Failed])
+            .is_err());
+        assert!(TestSummaryReporter::new(false)
+            .with_columns(vec![SummaryColumn::Suite, SummaryColumn::Passed, SummaryColumn::Passed])
+            .is_err());
+    }
+
+    #[test]
+    fn shows_retries() {
+        let test_results = BTreeMap::from([
+            ("testA()".to_string(), TestResult { retries: 2, ..test_result(TestStatus::Success) }),
+            ("testB()".to_string(), TestResult { retries: 1, ..test_result(TestStatus::Failure) }),
+        ]);
+        let stable_results =
+            BTreeMap::from([("testA()".to_string(), test_result(TestStatus::Success))]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                (
+                    "src/Foo.t.sol:FooTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+                ),
+                (
+                    "src/Bar.t.sol:BarTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), stable_results, vec![]),
+                ),
+            ]),
+            false,
+        );
+
+        let mut reporter = TestSummaryReporter::new(false);
+        reporter.add_rows(&outcome);
+        let table = reporter.table.to_string();
+        assert!(table.contains("FooTest (3 retries)"), "{table}");
+        assert!(!table.contains("BarTest ("), "{table}");
+    }
+
+    #[test]
+    fn writes_tap() {
+        let test_results = BTreeMap::from([
+            ("testA()".to_string(), test_result(TestStatus::Success)),
+            (
+                "testB()".to_string(),
+                TestResult {
+                    status: TestStatus::Failure,
+                    reason: Some("revert: \"nope\"".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "testC()".to_string(),
+                TestResult {
+                    status: TestStatus::Skipped,
+                    reason: Some("not on mainnet".to_string()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([(
+                "/project/test/Foo.t.sol:FooTest".to_string(),
+                SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+            )]),
+            false,
+        );
+
+        let reporter = TestSummaryReporter::new(false).with_root(Some("/project".into()));
+        let mut tap = Vec::new();
+        reporter.write_tap(&outcome, &mut tap).unwrap();
+        let tap = String::from_utf8(tap).unwrap();
+
+        let lines = tap.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..3],
+            ["TAP version 13", "1..3", "ok 1 - test/Foo.t.sol:FooTest::testA()"]
+        );
+        assert_eq!(lines.iter().filter(|line| line.starts_with("ok ")).count(), 2, "{tap}");
+        assert_eq!(lines.iter().filter(|line| line.starts_with("not ok ")).count(), 1, "{tap}");
+        assert!(tap.contains("not ok 2 - test/Foo.t.sol:FooTest::testB()\n  ---\n  message: \"revert: \\\"nope\\\"\"\n  ...\n"), "{tap}");
+        assert!(
+            tap.contains("ok 3 - test/Foo.t.sol:FooTest::testC() # SKIP not on mainnet"),
+            "{tap}"
+        );
+    }
+
+    #[test]
+    fn writes_tap_in_summary_order() {
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                (
+                    "test/A.t.sol:ATest".to_string(),
+                    SuiteResult::new(
+                        Duration::from_millis(10),
+                        BTreeMap::from([("testA()".to_string(), test_result(TestStatus::Success))]),
+                        vec![],
+                    ),
+                ),
+                (
+                    "test/B.t.sol:BTest".to_string(),
+                    SuiteResult::new(
+                        Duration::from_millis(10),
+                        BTreeMap::from([("testB()".to_string(), test_result(TestStatus::Failure))]),
+                        vec![],
+                    ),
+                ),
+            ]),
+            false,
+        );
+
+        let reporter = TestSummaryReporter::new(false).with_sort(SummarySort::Failures);
+        let mut tap = Vec::new();
+        reporter.write_tap(&outcome, &mut tap).unwrap();
+        let tap = String::from_utf8(tap).unwrap();
+        assert_eq!(
+            tap.lines().skip(2).collect::<Vec<_>>(),
+            ["not ok 1 - test/B.t.sol:BTest::testB()", "ok 2 - test/A.t.sol:ATest::testA()"]
+        );
+    }
+
+    #[test]
+    fn writes_json() {
+        let test_results = BTreeMap::from([
+            ("testA()".to_string(), test_result(TestStatus::Success)),
+            ("testB()".to_string(), test_result(TestStatus::Failure)),
+            ("testC()".to_string(), test_result(TestStatus::Skipped)),
+        ]);
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                (
+                    "/project/test/Foo.t.sol:FooTest".to_string(),
+                    SuiteResult::new(Duration::from_millis(10), test_results, vec![]),
+                ),
+                (
+                    "/project/test/Bar.t.sol:BarTest".to_string(),
+                    SuiteResult::new(
+                        Duration::from_millis(5),
+                        BTreeMap::from([("testA()".to_string(), test_result(TestStatus::Success))]),
+                        vec![],
+                    ),
+                ),
+            ]),
+            false,
+        );
+
+        // The path and duration are included without the detailed flag
+        let reporter = TestSummaryReporter::new(false).with_root(Some("/project".into()));
+        let mut json = Vec::new();
+        reporter.write_json(&outcome, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "suites": [
+                    {
+                        "suite_path": "test/Bar.t.sol",
+                        "suite_name": "BarTest",
+                        "passed": 1,
+                        "failed": 0,
+                        "skipped": 0,
+                        "duration_ms": 5,
+                    },
+                    {
+                        "suite_path": "test/Foo.t.sol",
+                        "suite_name": "FooTest",
+                        "passed": 1,
+                        "failed": 1,
+                        "skipped": 1,
+                        "duration_ms": 10,
+                    },
+                ],
+                "passed": 2,
+                "failed": 1,
+                "skipped": 1,
+                "duration_ms": 15,
+            })
+        );
+    }
+
+    #[test]
+    fn sorts_suites() {
+        let suite = |failures: usize| {
+            let test_results = (0..failures)
+                .map(|i| (format!("test{i}()"), test_result(TestStatus::Failure)))
+                .chain([("testOk()".to_string(), test_result(TestStatus::Success))])
+                .collect();
+            SuiteResult::new(Duration::from_millis(10), test_results, vec![])
+        };
+        // A path that is a prefix of another sorts before it, unlike its `path:Suite` key
+        let outcome = TestOutcome::new(
+            BTreeMap::from([
+                ("test/b/Foo.t.sol:FooTest".to_string(), suite(1)),
+                ("test/a.t.sol:ZedTest".to_string(), suite(0)),
+                ("test/a.t.sol.bak:BarTest".to_string(), suite(2)),
+                ("test/a.t.sol:AlphaTest".to_string(), suite(0)),
+            ]),
+            false,
+        );
+        let rows = |reporter: &mut TestSummaryReporter| {
+            reporter.add_rows(&outcome);
+            let table = reporter.table.to_string();
+            ["AlphaTest", "ZedTest", "BarTest", "FooTest"]
+                .into_iter()
+                .map(|name| (table.find(name).unwrap(), name))
+                .collect::<BTreeMap<_, _>>()
+                .into_values()
+                .collect::<Vec<_>>()
+        };
+
+        let expected = ["AlphaTest", "ZedTest", "BarTest", "FooTest"];
+        assert_eq!(rows(&mut TestSummaryReporter::new(false)), expected);
+        let json = TestSummaryReporter::new(false).json_summary(&outcome);
+        assert_eq!(
+            json.suites.iter().map(|suite| suite.suite_name.as_str()).collect::<Vec<_>>(),
+            expected
+        );
+
+        let mut reporter = TestSummaryReporter::new(false).with_sort(SummarySort::Failures);
+        assert_eq!(rows(&mut reporter), ["BarTest", "FooTest", "AlphaTest", "ZedTest"]);
+    }
+
+    #[test]
+    fn truncates_long_names() {
+        assert_eq!(truncate("testShort()", 40), "testShort()");
+        assert_eq!(truncate("testAVeryLongName()", 8), "testAVe…");
+    }
+
+    #[test]
+    fn formats_durations_in_fixed_unit() {
+        let long = Duration::from_millis(1500);
+        let short = Duration::from_millis(12);
+
+        assert_eq!(DurationUnit::Auto.format(long), "1.50s");
+        assert_eq!(DurationUnit::Auto.format(short), "12.00ms");
+
+        assert_eq!(DurationUnit::Ms.format(long), "   1500.00ms");
+        assert_eq!(DurationUnit::Ms.format(short), "     12.00ms");
+        assert_eq!(DurationUnit::S.format(long), "      1.50s");
+        assert_eq!(DurationUnit::S.format(short), "      0.01s");
     }
 }
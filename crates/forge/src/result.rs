@@ -254,6 +254,11 @@ impl SuiteResult {
         self.test_results.len()
     }
 
+    /// Returns the total number of retries of the tests in this suite.
+    pub fn retries(&self) -> usize {
+        self.test_results.values().map(|result| result.retries).sum()
+    }
+
     /// Sums up all the durations of all individual tests in this suite.
     ///
     /// Note that this is not necessarily the wall clock time of the entire test suite.
@@ -385,6 +390,11 @@ pub struct TestResult {
 
     /// pc breakpoint char map
     pub breakpoints: Breakpoints,
+
+    /// The number of times the test was retried before producing this result, e.g. because it
+    /// is flaky
+    #[serde(default)]
+    pub retries: usize,
 }
 
 impl fmt::Display for TestResult {
@@ -1776,3 +1776,301 @@ forgetest_init!(can_inspect_counter_pretty, |prj, cmd| {
 }"
     );
 });
+
+// checks that `forge flatten --output-artifact` emits the standard json input of the target
+forgetest_init!(can_flatten_with_standard_json_output, |prj, cmd| {
+    prj.write_config(Config { optimizer_runs: 1337, ..Default::default() });
+    prj.add_source(
+        "Importer.sol",
+        r#"
+pragma solidity ^0.8.10;
+import "./Counter.sol";
+
+contract Importer is Counter {}
+"#,
+    )
+    .unwrap();
+
+    let artifact = prj.root().join("out/Importer.input.json");
+    cmd.args(["flatten", "src/Importer.sol", "--output-artifact"]).arg(&artifact);
+    cmd.assert_non_empty_stdout();
+
+    let input: serde_json::Value = foundry_compilers::utils::read_json_file(&artifact).unwrap();
+    assert_eq!(input["language"], "Solidity");
+    let sources = input["sources"].as_object().unwrap();
+    assert!(sources.contains_key("src/Importer.sol"));
+    assert!(sources.contains_key("src/Counter.sol"));
+    assert_eq!(input["settings"]["optimizer"]["runs"], 1337);
+});
+
+// checks that `forge flatten --output-artifact` only prints the flattened source to stdout
+forgetest_init!(can_flatten_to_stdout_with_standard_json_output, |prj, cmd| {
+    let flattened = prj.root().join("flat/Counter.sol");
+    cmd.args(["flatten", "src/Counter.sol", "--output"]).arg(&flattened);
+    cmd.assert_non_empty_stdout();
+    let flattened = fs::read_to_string(&flattened).unwrap();
+
+    let artifact = prj.root().join("out/Counter.input.json");
+    cmd.forge_fuse().args(["flatten", "src/Counter.sol", "--output-artifact"]).arg(&artifact);
+    assert_eq!(cmd.stdout_lossy(), format!("{flattened}\n"));
+    assert!(artifact.exists());
+});
+
+// checks that `forge flatten --evm-version` overrides the EVM version the target is compiled for
+forgetest_init!(can_flatten_with_evm_version, |prj, cmd| {
+    let artifact = prj.root().join("out/Counter.input.json");
+    cmd.args(["flatten", "src/Counter.sol", "--evm-version", "london", "--output-artifact"])
+        .arg(&artifact);
+    cmd.assert_non_empty_stdout();
+
+    let input: serde_json::Value = foundry_compilers::utils::read_json_file(&artifact).unwrap();
+    assert_eq!(input["settings"]["evmVersion"], "london");
+});
+
+// checks that `forge flatten --annotate` emits a banner for each source file of the target
+forgetest_init!(can_flatten_with_annotations, |prj, cmd| {
+    prj.add_source(
+        "Importer.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.10;
+import "./Counter.sol";
+
+contract Importer is Counter {}
+"#,
+    )
+    .unwrap();
+
+    cmd.args(["flatten", "src/Importer.sol", "--annotate"]);
+    let output = cmd.stdout_lossy();
+
+    for path in ["src/Importer.sol", "src/Counter.sol"] {
+        let banner = format!("// ===== File: {path} =====");
+        assert_eq!(output.matches(&banner).count(), 1, "{output}");
+    }
+    // The license and pragmas are still consolidated before the sources
+    let license = output.find("// SPDX-License-Identifier: MIT\npragma solidity").unwrap();
+    assert!(license < output.find("// ===== File:").unwrap(), "{output}");
+    assert_eq!(output.matches("pragma solidity").count(), 1, "{output}");
+});
+
+forgetest_init!(can_flatten_with_normalized_pragmas, |prj, cmd| {
+    prj.add_source(
+        "CoderA.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.10;
+pragma abicoder v2;
+
+contract CoderA {}
+"#,
+    )
+    .unwrap();
+    prj.add_source(
+        "CoderB.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.10;
+pragma abicoder v2;
+import "./CoderA.sol";
+
+contract CoderB is CoderA {}
+"#,
+    )
+    .unwrap();
+
+    cmd.args(["flatten", "src/CoderB.sol", "--normalize"]);
+    let output = cmd.stdout_lossy();
+    assert_eq!(output.matches("pragma abicoder").count(), 1, "{output}");
+    assert!(output.contains("pragma solidity ^0.8.10;\npragma abicoder v2;"), "{output}");
+
+    // conflicting ABI coders can't be consolidated
+    prj.add_source(
+        "CoderV1.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.10;
+pragma abicoder v1;
+import "./CoderA.sol";
+
+contract CoderV1 {}
+"#,
+    )
+    .unwrap();
+    cmd.forge_fuse().args(["flatten", "src/CoderV1.sol", "--normalize"]);
+    cmd.assert_err();
+});
+
+// checks that `forge flatten --exclude` omits the matching source files of the target
+forgetest_init!(can_flatten_with_exclude, |prj, cmd| {
+    prj.add_source(
+        "Helper.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.10;
+
+contract Helper {}
+"#,
+    )
+    .unwrap();
+    prj.add_source(
+        "Importer.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.10;
+import "./Counter.sol";
+import "./Helper.sol";
+
+contract Importer is Counter {}
+"#,
+    )
+    .unwrap();
+
+    let flattened = prj.root().join("flat/Importer.sol");
+    cmd.args(["flatten", "src/Importer.sol", "--exclude", "src/Helper.sol", "--output"])
+        .arg(&flattened);
+    cmd.assert_non_empty_stdout();
+
+    let output = std::fs::read_to_string(&flattened).unwrap();
+    assert!(!output.contains("contract Helper"), "{output}");
+    assert!(output.contains("contract Counter"), "{output}");
+
+    // the flattened contract still compiles without the excluded source
+    cmd.forge_fuse().args(["build", "flat/Importer.sol"]);
+    cmd.assert_success();
+
+    // excluding a source the target depends on requires `--force`
+    cmd.forge_fuse().args(["flatten", "src/Importer.sol", "--exclude", "src/Counter.sol"]);
+    cmd.assert_err();
+    cmd.arg("--force");
+    let output = cmd.stdout_lossy();
+    assert!(!output.contains("contract Counter"), "{output}");
+});
+
+// writes a Vyper `Counter` and a `Caller` importing it into the sources of the project at `root`
+fn write_vyper_sources(root: &Path) {
+    fs::write(
+        root.join("src/Counter.vy"),
+        r#"# @version ^0.3.10
+
+number: public(uint256)
+allowances: public(HashMap[address, HashMap[address, uint256]])
+
+@external
+def increment():
+    self.number += 1
+
+@external
+@view
+def get(
+    offset: uint256,  # added to the number
+) -> uint256:
+    return self.number + offset
+
+@internal
+def _helper():
+    pass
+"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("src/Caller.vy"),
+        r#"# @version ^0.3.10
+
+from vyper.interfaces import ERC20
+import Counter as Counter
+
+@external
+def call(counter: address) -> uint256:
+    Counter(counter).increment()
+    return Counter(counter).get(1)
+"#,
+    )
+    .unwrap();
+}
+
+// checks that `forge flatten` inlines the imported Vyper sources of a Vyper target as interfaces
+forgetest!(can_flatten_vyper, |prj, cmd| {
+    write_vyper_sources(prj.root());
+
+    let output = prj.root().join("flat/Caller.vy");
+    cmd.args(["flatten", "src/Caller.vy", "--output"]).arg(&output);
+    cmd.assert_non_empty_stdout();
+
+    let flattened = fs::read_to_string(&output).unwrap();
+    assert!(flattened.contains("from vyper.interfaces import ERC20"), "{flattened}");
+    assert!(!flattened.contains("import Counter"), "{flattened}");
+    assert!(
+        flattened.contains(
+            "interface Counter:
+    def number() -> uint256: view
+    def allowances(arg0: address, arg1: address) -> uint256: view
+    def increment(): nonpayable
+    def get(offset: uint256) -> uint256: view
+"
+        ),
+        "{flattened}"
+    );
+    assert!(!flattened.contains("_helper"), "{flattened}");
+
+    // Modules can't be inlined
+    fs::write(
+        prj.root().join("src/Module.vy"),
+        r#"import Counter
+
+initializes: Counter
+"#,
+    )
+    .unwrap();
+    cmd.forge_fuse().args(["flatten", "src/Module.vy"]);
+    cmd.assert_err();
+});
+
+// checks that the output of `forge flatten` for a Vyper target compiles on its own
+forgetest!(
+    #[ignore = "requires a vyper compiler"]
+    can_compile_flattened_vyper,
+    |prj, cmd| {
+        write_vyper_sources(prj.root());
+
+        let output = prj.root().join("flat/Caller.vy");
+        cmd.args(["flatten", "src/Caller.vy", "--output"]).arg(&output);
+        cmd.assert_non_empty_stdout();
+
+        let vyper = Command::new("vyper").arg(&output).output().expect("vyper is not installed");
+        assert!(vyper.status.success(), "{}", String::from_utf8_lossy(&vyper.stderr));
+    }
+);
+
+// checks that `forge flatten --license-report` lists the licenses of the flattened sources
+forgetest_init!(can_flatten_with_license_report, |prj, cmd| {
+    prj.add_source(
+        "Licensed.sol",
+        r#"
+// SPDX-License-Identifier: GPL-3.0
+pragma solidity ^0.8.10;
+
+contract Licensed {}
+"#,
+    )
+    .unwrap();
+    prj.add_source(
+        "Importer.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.10;
+import "./Licensed.sol";
+
+contract Importer is Licensed {}
+"#,
+    )
+    .unwrap();
+
+    let flattened = prj.root().join("flat/Importer.sol");
+    cmd.args(["flatten", "src/Importer.sol", "--license-report", "--output"]).arg(&flattened);
+    let output = cmd.stdout_lossy();
+    assert!(
+        output.contains("Licenses:\n  GPL-3.0\n    src/Licensed.sol\n  MIT\n    src/Importer.sol"),
+        "{output}"
+    );
+});
@@ -97,6 +97,8 @@ forgetest!(can_extract_config_values, |prj, cmd| {
         block_gas_limit: Some(100u64.into()),
         disable_block_gas_limit: false,
         memory_limit: 1 << 27,
+        block_env_cache_capacity: Some(2000),
+        code_cache_capacity: Some(20_000),
         eth_rpc_url: Some("localhost".to_string()),
         eth_rpc_jwt: None,
         etherscan_api_key: None,
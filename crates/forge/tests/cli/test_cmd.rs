@@ -596,3 +596,58 @@ contract CounterTest is Test {
     // make sure there are only 61 runs (with proptest shrinking same test results in 298 runs)
     assert_eq!(runs.unwrap().parse::<usize>().unwrap(), 61);
 });
+
+// tests that the JSON test summary is the only output on stdout
+forgetest!(summary_json_is_only_stdout_output, |prj, cmd| {
+    prj.insert_ds_test();
+
+    prj.add_source(
+        "ATest.t.sol",
+        r#"
+import "./test.sol";
+contract ATest is DSTest {
+    function testPass() external {
+        assertTrue(true);
+    }
+
+    function testFail() external {
+        assertTrue(false);
+    }
+}
+   "#,
+    )
+    .unwrap();
+
+    cmd.args(["test", "--summary", "--summary-json"]);
+    let stdout = cmd.stdout_lossy();
+    let summary: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(summary["passed"], 2, "{stdout}");
+    assert_eq!(summary["failed"], 0, "{stdout}");
+    assert_eq!(summary["suites"][0]["suite_name"], "ATest", "{stdout}");
+});
+
+// tests that the TAP test summary is the only output on stdout
+forgetest!(summary_tap_is_only_stdout_output, |prj, cmd| {
+    prj.insert_ds_test();
+
+    prj.add_source(
+        "ATest.t.sol",
+        r#"
+import "./test.sol";
+contract ATest is DSTest {
+    function testPass() external {
+        assertTrue(true);
+    }
+}
+   "#,
+    )
+    .unwrap();
+
+    cmd.args(["test", "--summary", "--tap"]);
+    let stdout = cmd.stdout_lossy();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    assert_eq!(lines[..2], ["TAP version 13", "1..1"], "{stdout}");
+    assert!(lines[2].starts_with("ok 1 - "), "{stdout}");
+    assert!(lines[2].ends_with("ATest.t.sol:ATest::testPass()"), "{stdout}");
+});
@@ -64,16 +64,14 @@ impl VerifyBundle {
 
         let config_path = config.get_config_path();
 
-        let project_paths = ProjectPathsArgs {
-            root: Some(project.paths.root.clone()),
-            contracts: Some(project.paths.sources.clone()),
-            remappings: project.paths.remappings.clone(),
-            remappings_env: None,
-            cache_path: Some(project.paths.cache.clone()),
-            lib_paths: project.paths.libraries.clone(),
-            hardhat: config.profile == Config::HARDHAT_PROFILE,
-            config_path: if config_path.exists() { Some(config_path) } else { None },
-        };
+        let mut project_paths = ProjectPathsArgs::default();
+        project_paths.root = Some(project.paths.root.clone());
+        project_paths.contracts = vec![project.paths.sources.clone()];
+        project_paths.remappings = project.paths.remappings.clone();
+        project_paths.cache_path = Some(project.paths.cache.clone());
+        project_paths.lib_paths = project.paths.libraries.clone();
+        project_paths.hardhat = config.profile == Config::HARDHAT_PROFILE;
+        project_paths.config_path = config_path.exists().then_some(config_path);
 
         let via_ir = config.via_ir;
 